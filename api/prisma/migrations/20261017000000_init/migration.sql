@@ -0,0 +1,89 @@
+-- The schema the registry started from: users, one `packages` row per
+-- published version, their dependencies and tags.
+--
+-- Databases created with `prisma db push` already have these tables. Mark
+-- this migration, and each later one their schema already has, as applied
+-- with
+--   prisma migrate resolve --applied 20261017000000_init
+-- instead of running them.
+
+CREATE TYPE "UserRole" AS ENUM ('ADMIN', 'USER');
+
+CREATE TYPE "DependencyType" AS ENUM ('REQUIRES', 'RECOMMENDS', 'SUGGESTS', 'CONFLICTS');
+
+CREATE TABLE "users" (
+    "id" TEXT NOT NULL,
+    "username" TEXT NOT NULL,
+    "email" TEXT NOT NULL,
+    "password" TEXT NOT NULL,
+    "role" "UserRole" NOT NULL DEFAULT 'USER',
+    "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "updatedAt" TIMESTAMP(3) NOT NULL,
+
+    CONSTRAINT "users_pkey" PRIMARY KEY ("id")
+);
+
+CREATE TABLE "packages" (
+    "id" TEXT NOT NULL,
+    "name" TEXT NOT NULL,
+    "version" TEXT NOT NULL,
+    "description" TEXT,
+    "maintainer" TEXT NOT NULL,
+    "architecture" TEXT NOT NULL,
+    "size" INTEGER NOT NULL,
+    "checksum" TEXT NOT NULL,
+    "filePath" TEXT NOT NULL,
+    "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "updatedAt" TIMESTAMP(3) NOT NULL,
+    "authorId" TEXT NOT NULL,
+
+    CONSTRAINT "packages_pkey" PRIMARY KEY ("id")
+);
+
+CREATE TABLE "package_dependencies" (
+    "id" TEXT NOT NULL,
+    "packageId" TEXT NOT NULL,
+    "depName" TEXT NOT NULL,
+    "depVersion" TEXT NOT NULL,
+    "type" "DependencyType" NOT NULL DEFAULT 'REQUIRES',
+
+    CONSTRAINT "package_dependencies_pkey" PRIMARY KEY ("id")
+);
+
+CREATE TABLE "tags" (
+    "id" TEXT NOT NULL,
+    "name" TEXT NOT NULL,
+    "color" TEXT NOT NULL DEFAULT '#007bff',
+
+    CONSTRAINT "tags_pkey" PRIMARY KEY ("id")
+);
+
+CREATE TABLE "package_tags" (
+    "id" TEXT NOT NULL,
+    "packageId" TEXT NOT NULL,
+    "tagId" TEXT NOT NULL,
+
+    CONSTRAINT "package_tags_pkey" PRIMARY KEY ("id")
+);
+
+CREATE UNIQUE INDEX "users_username_key" ON "users"("username");
+
+CREATE UNIQUE INDEX "users_email_key" ON "users"("email");
+
+CREATE UNIQUE INDEX "packages_name_version_key" ON "packages"("name", "version");
+
+CREATE UNIQUE INDEX "tags_name_key" ON "tags"("name");
+
+CREATE UNIQUE INDEX "package_tags_packageId_tagId_key" ON "package_tags"("packageId", "tagId");
+
+ALTER TABLE "packages" ADD CONSTRAINT "packages_authorId_fkey"
+    FOREIGN KEY ("authorId") REFERENCES "users"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+ALTER TABLE "package_dependencies" ADD CONSTRAINT "package_dependencies_packageId_fkey"
+    FOREIGN KEY ("packageId") REFERENCES "packages"("id") ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "package_tags" ADD CONSTRAINT "package_tags_packageId_fkey"
+    FOREIGN KEY ("packageId") REFERENCES "packages"("id") ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "package_tags" ADD CONSTRAINT "package_tags_tagId_fkey"
+    FOREIGN KEY ("tagId") REFERENCES "tags"("id") ON DELETE CASCADE ON UPDATE CASCADE;
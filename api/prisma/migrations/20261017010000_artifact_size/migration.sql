@@ -0,0 +1,2 @@
+-- Artifacts can be larger than 2 GiB.
+ALTER TABLE "packages" ALTER COLUMN "size" SET DATA TYPE BIGINT;
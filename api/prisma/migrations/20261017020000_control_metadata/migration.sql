@@ -0,0 +1,8 @@
+-- Metadata read from the uploaded control file, and the alternatives of a
+-- `Depends`-style field, which share a group number.
+ALTER TABLE "packages" ADD COLUMN "section" TEXT,
+ADD COLUMN "priority" TEXT,
+ADD COLUMN "installedSize" INTEGER,
+ADD COLUMN "control" TEXT;
+
+ALTER TABLE "package_dependencies" ADD COLUMN "alternativeGroup" INTEGER NOT NULL DEFAULT 0;
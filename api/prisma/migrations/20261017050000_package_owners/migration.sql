@@ -0,0 +1,43 @@
+-- Owners and maintainers of package names, and transfers of ownership.
+-- Names published before this have no owners yet; the
+-- backfill_package_owners migration records the publishers of their versions.
+CREATE TYPE "OwnerRole" AS ENUM ('OWNER', 'MAINTAINER');
+
+CREATE TABLE "package_owners" (
+    "id" TEXT NOT NULL,
+    "packageName" TEXT NOT NULL,
+    "userId" TEXT NOT NULL,
+    "role" "OwnerRole" NOT NULL DEFAULT 'MAINTAINER',
+    "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "package_owners_pkey" PRIMARY KEY ("id")
+);
+
+CREATE TABLE "ownership_transfers" (
+    "id" TEXT NOT NULL,
+    "packageName" TEXT NOT NULL,
+    "fromUserId" TEXT NOT NULL,
+    "toUserId" TEXT NOT NULL,
+    "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "acceptedAt" TIMESTAMP(3),
+    "cancelledAt" TIMESTAMP(3),
+
+    CONSTRAINT "ownership_transfers_pkey" PRIMARY KEY ("id")
+);
+
+CREATE UNIQUE INDEX "package_owners_packageName_userId_key" ON "package_owners"("packageName", "userId");
+
+CREATE INDEX "package_owners_userId_idx" ON "package_owners"("userId");
+
+CREATE INDEX "ownership_transfers_packageName_idx" ON "ownership_transfers"("packageName");
+
+CREATE INDEX "ownership_transfers_toUserId_idx" ON "ownership_transfers"("toUserId");
+
+ALTER TABLE "package_owners" ADD CONSTRAINT "package_owners_userId_fkey"
+    FOREIGN KEY ("userId") REFERENCES "users"("id") ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "ownership_transfers" ADD CONSTRAINT "ownership_transfers_fromUserId_fkey"
+    FOREIGN KEY ("fromUserId") REFERENCES "users"("id") ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE "ownership_transfers" ADD CONSTRAINT "ownership_transfers_toUserId_fkey"
+    FOREIGN KEY ("toUserId") REFERENCES "users"("id") ON DELETE CASCADE ON UPDATE CASCADE;
@@ -0,0 +1,69 @@
+-- Splits `packages`, which held one row per (name, version), into a
+-- name-level `packages` table and `package_versions`, keeping every
+-- existing version, dependency and tag.
+
+-- The existing rows are versions.
+ALTER TABLE "packages" RENAME TO "package_versions";
+ALTER TABLE "package_versions" RENAME CONSTRAINT "packages_pkey" TO "package_versions_pkey";
+ALTER TABLE "package_versions" RENAME COLUMN "authorId" TO "publisherId";
+ALTER TABLE "package_versions" RENAME CONSTRAINT "packages_authorId_fkey" TO "package_versions_publisherId_fkey";
+DROP INDEX "packages_name_version_key";
+
+CREATE TABLE "packages" (
+    "id" TEXT NOT NULL,
+    "name" TEXT NOT NULL,
+    "description" TEXT,
+    "homepage" TEXT,
+    "repository" TEXT,
+    "deprecated" TEXT,
+    "downloads" BIGINT NOT NULL DEFAULT 0,
+    "createdAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "updatedAt" TIMESTAMP(3) NOT NULL,
+    "authorId" TEXT NOT NULL,
+
+    CONSTRAINT "packages_pkey" PRIMARY KEY ("id")
+);
+
+CREATE UNIQUE INDEX "packages_name_key" ON "packages"("name");
+
+-- One package per name, described by its newest version and authored by
+-- whoever published the first one.
+INSERT INTO "packages" ("id", "name", "description", "createdAt", "updatedAt", "authorId")
+SELECT
+    md5('package:' || "name"),
+    "name",
+    (ARRAY_AGG("description" ORDER BY "createdAt" DESC))[1],
+    MIN("createdAt"),
+    MAX("updatedAt"),
+    (ARRAY_AGG("publisherId" ORDER BY "createdAt" ASC))[1]
+FROM "package_versions"
+GROUP BY "name";
+
+ALTER TABLE "packages" ADD CONSTRAINT "packages_authorId_fkey"
+    FOREIGN KEY ("authorId") REFERENCES "users"("id") ON DELETE RESTRICT ON UPDATE CASCADE;
+
+ALTER TABLE "package_versions" ADD COLUMN "packageId" TEXT;
+UPDATE "package_versions" v SET "packageId" = p."id" FROM "packages" p WHERE p."name" = v."name";
+ALTER TABLE "package_versions" ALTER COLUMN "packageId" SET NOT NULL;
+ALTER TABLE "package_versions" DROP COLUMN "name";
+
+CREATE UNIQUE INDEX "package_versions_packageId_version_key" ON "package_versions"("packageId", "version");
+ALTER TABLE "package_versions" ADD CONSTRAINT "package_versions_packageId_fkey"
+    FOREIGN KEY ("packageId") REFERENCES "packages"("id") ON DELETE CASCADE ON UPDATE CASCADE;
+
+-- Dependencies stay with their version.
+ALTER TABLE "package_dependencies" RENAME COLUMN "packageId" TO "versionId";
+ALTER TABLE "package_dependencies" RENAME CONSTRAINT "package_dependencies_packageId_fkey" TO "package_dependencies_versionId_fkey";
+
+-- Tags move to the package; a tag that was on several versions is kept once.
+ALTER TABLE "package_tags" DROP CONSTRAINT "package_tags_packageId_fkey";
+DROP INDEX "package_tags_packageId_tagId_key";
+
+UPDATE "package_tags" t SET "packageId" = v."packageId" FROM "package_versions" v WHERE v."id" = t."packageId";
+DELETE FROM "package_tags" t
+USING "package_tags" d
+WHERE t."packageId" = d."packageId" AND t."tagId" = d."tagId" AND t."id" > d."id";
+
+CREATE UNIQUE INDEX "package_tags_packageId_tagId_key" ON "package_tags"("packageId", "tagId");
+ALTER TABLE "package_tags" ADD CONSTRAINT "package_tags_packageId_fkey"
+    FOREIGN KEY ("packageId") REFERENCES "packages"("id") ON DELETE CASCADE ON UPDATE CASCADE;
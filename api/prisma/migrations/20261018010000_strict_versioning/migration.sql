@@ -0,0 +1,2 @@
+-- Lets maintainers require every new version to be newer than the latest.
+ALTER TABLE "packages" ADD COLUMN "strictVersioning" BOOLEAN NOT NULL DEFAULT false;
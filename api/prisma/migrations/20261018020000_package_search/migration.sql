@@ -0,0 +1,87 @@
+-- Full-text package search. Each package keeps a weighted tsvector of its
+-- name (A), tags (B), description (C) and maintainers (D), refreshed by
+-- triggers whenever any of those change, and a trigram index on the name
+-- for typo-tolerant matching.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+ALTER TABLE "packages" ADD COLUMN "searchVector" tsvector;
+
+CREATE FUNCTION package_search_vector(package_id TEXT) RETURNS tsvector
+LANGUAGE sql STABLE AS $$
+    SELECT
+        -- `@scope/name` is indexed as its parts as well as whole words.
+        setweight(to_tsvector('simple', translate(p."name", '@/', '  ')), 'A')
+        || setweight(to_tsvector('simple', coalesce((
+            SELECT string_agg(t."name", ' ')
+            FROM "package_tags" pt JOIN "tags" t ON t."id" = pt."tagId"
+            WHERE pt."packageId" = p."id"
+        ), '')), 'B')
+        || setweight(to_tsvector('english', coalesce(p."description", '')), 'C')
+        || setweight(to_tsvector('simple', coalesce((
+            SELECT string_agg(DISTINCT v."maintainer", ' ')
+            FROM "package_versions" v
+            WHERE v."packageId" = p."id"
+        ), '')), 'D')
+    FROM "packages" p
+    WHERE p."id" = package_id
+$$;
+
+CREATE FUNCTION refresh_package_search(package_id TEXT) RETURNS void
+LANGUAGE sql AS $$
+    UPDATE "packages" SET "searchVector" = package_search_vector(package_id) WHERE "id" = package_id;
+$$;
+
+CREATE FUNCTION packages_search_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    PERFORM refresh_package_search(NEW."id");
+    RETURN NULL;
+END
+$$;
+
+CREATE FUNCTION package_children_search_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM refresh_package_search(OLD."packageId");
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        PERFORM refresh_package_search(NEW."packageId");
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE FUNCTION tags_search_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    PERFORM refresh_package_search(pt."packageId")
+    FROM "package_tags" pt
+    WHERE pt."tagId" = NEW."id";
+    RETURN NULL;
+END
+$$;
+
+-- Only the indexed columns fire the trigger, so its own update of
+-- "searchVector" does not recurse.
+CREATE TRIGGER "packages_search"
+    AFTER INSERT OR UPDATE OF "name", "description" ON "packages"
+    FOR EACH ROW EXECUTE FUNCTION packages_search_trigger();
+
+CREATE TRIGGER "package_versions_search"
+    AFTER INSERT OR DELETE OR UPDATE OF "maintainer", "packageId" ON "package_versions"
+    FOR EACH ROW EXECUTE FUNCTION package_children_search_trigger();
+
+CREATE TRIGGER "package_tags_search"
+    AFTER INSERT OR DELETE OR UPDATE ON "package_tags"
+    FOR EACH ROW EXECUTE FUNCTION package_children_search_trigger();
+
+CREATE TRIGGER "tags_search"
+    AFTER UPDATE OF "name" ON "tags"
+    FOR EACH ROW EXECUTE FUNCTION tags_search_trigger();
+
+UPDATE "packages" SET "searchVector" = package_search_vector("id");
+
+CREATE INDEX "packages_searchVector_idx" ON "packages" USING GIN ("searchVector");
+CREATE INDEX "packages_name_trgm_idx" ON "packages" USING GIN ("name" gin_trgm_ops);
@@ -0,0 +1,3 @@
+-- Detached OpenPGP signatures of package artifacts, made on publish when
+-- the registry has a signing key.
+ALTER TABLE "package_versions" ADD COLUMN "signature" TEXT;
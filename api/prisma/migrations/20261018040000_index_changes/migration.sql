@@ -0,0 +1,104 @@
+-- The package index changes feed. Any change to a package's versions, their
+-- dependencies or the package's tags appends its name to "index_changes"
+-- with the id of the transaction making the change. Transactions commit in
+-- any order, so the feed only hands out changes below the oldest transaction
+-- still running, and that bound is the cursor `GET /api/index/changes`
+-- takes: nothing can be logged below it afterwards, so a client that has
+-- seen a cursor refetches only the packages named from it on.
+
+-- `prisma db push` creates the table from the schema, without triggers.
+CREATE TABLE IF NOT EXISTS "index_changes" (
+    "id" BIGSERIAL NOT NULL,
+    "packageName" TEXT NOT NULL,
+    "changedAt" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "txid" xid8 NOT NULL DEFAULT pg_current_xact_id(),
+
+    CONSTRAINT "index_changes_pkey" PRIMARY KEY ("id")
+);
+
+CREATE INDEX IF NOT EXISTS "index_changes_txid_idx" ON "index_changes" ("txid");
+
+CREATE FUNCTION log_index_change(package_id TEXT) RETURNS void
+LANGUAGE sql AS $$
+    INSERT INTO "index_changes" ("packageName")
+    SELECT "name" FROM "packages" WHERE "id" = package_id;
+$$;
+
+-- Deleting a package cascades to its versions and tags after the package
+-- row is gone, so the package's own trigger logs it by name.
+CREATE FUNCTION packages_index_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    INSERT INTO "index_changes" ("packageName") VALUES (OLD."name");
+    IF TG_OP = 'UPDATE' THEN
+        INSERT INTO "index_changes" ("packageName") VALUES (NEW."name");
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE FUNCTION package_children_index_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM log_index_change(OLD."packageId");
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        PERFORM log_index_change(NEW."packageId");
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE FUNCTION package_dependencies_index_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM log_index_change(v."packageId")
+        FROM "package_versions" v
+        WHERE v."id" = OLD."versionId";
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        PERFORM log_index_change(v."packageId")
+        FROM "package_versions" v
+        WHERE v."id" = NEW."versionId";
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE FUNCTION tags_index_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    PERFORM log_index_change(pt."packageId")
+    FROM "package_tags" pt
+    WHERE pt."tagId" = NEW."id";
+    RETURN NULL;
+END
+$$;
+
+-- Download counts are not part of the index, so updates to them do not
+-- fire anything.
+CREATE TRIGGER "packages_index"
+    AFTER DELETE OR UPDATE OF "name" ON "packages"
+    FOR EACH ROW EXECUTE FUNCTION packages_index_trigger();
+
+CREATE TRIGGER "package_versions_index"
+    AFTER INSERT OR DELETE OR UPDATE ON "package_versions"
+    FOR EACH ROW EXECUTE FUNCTION package_children_index_trigger();
+
+CREATE TRIGGER "package_tags_index"
+    AFTER INSERT OR DELETE OR UPDATE ON "package_tags"
+    FOR EACH ROW EXECUTE FUNCTION package_children_index_trigger();
+
+CREATE TRIGGER "package_dependencies_index"
+    AFTER INSERT OR DELETE OR UPDATE ON "package_dependencies"
+    FOR EACH ROW EXECUTE FUNCTION package_dependencies_index_trigger();
+
+CREATE TRIGGER "tags_index"
+    AFTER UPDATE OF "name", "color" ON "tags"
+    FOR EACH ROW EXECUTE FUNCTION tags_index_trigger();
+
+-- Start the log with every existing package, so that clients which only
+-- ever follow the feed still learn about them.
+INSERT INTO "index_changes" ("packageName") SELECT "name" FROM "packages" ORDER BY "name";
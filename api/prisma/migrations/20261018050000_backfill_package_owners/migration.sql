@@ -0,0 +1,13 @@
+-- Names published before ownership was tracked have no owners. The
+-- publishers of their versions become their owners, as the first publisher
+-- of a new name does.
+INSERT INTO "package_owners" ("id", "packageName", "userId", "role")
+SELECT gen_random_uuid()::text, "publishers"."name", "publishers"."publisherId", 'OWNER'
+FROM (
+    SELECT DISTINCT p."name", v."publisherId"
+    FROM "package_versions" v
+    JOIN "packages" p ON p."id" = v."packageId"
+    WHERE NOT EXISTS (
+        SELECT 1 FROM "package_owners" o WHERE o."packageName" = p."name"
+    )
+) AS "publishers";
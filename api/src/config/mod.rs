@@ -1,32 +1,303 @@
 use axum::{
+    extract::FromRef,
     middleware,
     routing::get,
     Router,
 };
+use chrono::{DateTime, Utc};
+use std::{env, path::PathBuf, sync::Arc};
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 
 use crate::{
     db::PrismaClient,
-    routes::{package_routes, user_routes, auth_routes},
-    middlewares::{cors_layer, auth_middleware},
+    routes::{package_routes, user_routes, auth_routes, repository_routes, token_routes, owner_routes, transfer_routes, org_routes, tag_routes, index_routes, search_routes},
+    middlewares::{cors_layer, auth_middleware, authorize},
+    services::{
+        auth_services::TokenKeys, index_services::schedule_pruning, repository_services::Repository,
+        signing_services::Signer,
+    },
+    storage::BlobStorage,
 };
 
-pub fn create_app(client: PrismaClient) -> Router {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    Filesystem,
+    S3,
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Settings {
+    pub bucket: String,
+    pub region: String,
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub allow_http: bool,
+}
+
+impl S3Settings {
+    fn from_env() -> Option<Self> {
+        let bucket = env::var("S3_BUCKET").ok()?;
+        Some(Self {
+            bucket,
+            region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            endpoint: env::var("S3_ENDPOINT").ok(),
+            access_key_id: env::var("S3_ACCESS_KEY_ID").ok(),
+            secret_access_key: env::var("S3_SECRET_ACCESS_KEY").ok(),
+            allow_http: env::var("S3_ALLOW_HTTP").map(|v| v == "true").unwrap_or(false),
+        })
+    }
+}
+
+/// Layout of the apt repository served under `/apt`.
+#[derive(Debug, Clone)]
+pub struct AptSettings {
+    pub suite: String,
+    pub component: String,
+    pub origin: String,
+    pub label: String,
+    /// Architectures always listed in `Release`, in addition to those of
+    /// published packages.
+    pub architectures: Vec<String>,
+}
+
+impl AptSettings {
+    fn from_env() -> Self {
+        Self {
+            suite: env::var("APT_SUITE").unwrap_or_else(|_| "stable".to_string()),
+            component: env::var("APT_COMPONENT").unwrap_or_else(|_| "main".to_string()),
+            origin: env::var("APT_ORIGIN").unwrap_or_else(|_| "Sky Genesis Enterprise".to_string()),
+            label: env::var("APT_LABEL").unwrap_or_else(|_| "Registry Service".to_string()),
+            architectures: env::var("APT_ARCHITECTURES")
+                .unwrap_or_else(|_| "amd64 arm64".to_string())
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// OpenPGP keys used to sign the apt repository. During a rotation the new
+/// key goes in `key_path` and the old one in `previous_key_path`; both sign
+/// until `previous_key_until`.
+#[derive(Debug, Clone, Default)]
+pub struct SigningSettings {
+    pub key_path: Option<PathBuf>,
+    pub previous_key_path: Option<PathBuf>,
+    pub previous_key_until: Option<DateTime<Utc>>,
+    pub passphrase_file: Option<PathBuf>,
+}
+
+impl SigningSettings {
+    fn from_env() -> Self {
+        Self {
+            key_path: env::var("SIGNING_KEY_PATH").ok().map(PathBuf::from),
+            previous_key_path: env::var("SIGNING_PREVIOUS_KEY_PATH").ok().map(PathBuf::from),
+            previous_key_until: env::var("SIGNING_PREVIOUS_KEY_UNTIL")
+                .ok()
+                .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                .map(|v| v.with_timezone(&Utc)),
+            passphrase_file: env::var("SIGNING_KEY_PASSPHRASE_FILE").ok().map(PathBuf::from),
+        }
+    }
+}
+
+/// What to do with accounts whose password predates hashing and is still
+/// stored in plaintext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyPasswordPolicy {
+    /// Accept the password once and replace it with a hash.
+    Rehash,
+    /// Refuse the login until the password is reset.
+    Reject,
+}
+
+/// Argon2id cost parameters for password hashes. Stored hashes made with
+/// different parameters are upgraded on the next successful login.
+#[derive(Debug, Clone)]
+pub struct PasswordSettings {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub legacy_policy: LegacyPasswordPolicy,
+}
+
+impl PasswordSettings {
+    fn from_env() -> Self {
+        let number = |key: &str, default: u32| {
+            env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+
+        Self {
+            memory_kib: number("ARGON2_MEMORY_KIB", 19 * 1024),
+            iterations: number("ARGON2_ITERATIONS", 2),
+            parallelism: number("ARGON2_PARALLELISM", 1),
+            legacy_policy: match env::var("LEGACY_PASSWORD_POLICY").as_deref() {
+                Ok("reject") => LegacyPasswordPolicy::Reject,
+                _ => LegacyPasswordPolicy::Rehash,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    Hs256,
+    EdDsa,
+}
+
+/// How access tokens are signed. HS256 uses the shared `JWT_SECRET`; EdDSA
+/// uses an Ed25519 key pair in PEM files, so verifiers only need the
+/// public half.
+#[derive(Debug, Clone)]
+pub struct JwtSettings {
+    pub algorithm: JwtAlgorithm,
+    pub secret: Option<String>,
+    pub private_key_path: Option<PathBuf>,
+    pub public_key_path: Option<PathBuf>,
+    pub issuer: String,
+    /// Lifetime of access tokens, in seconds.
+    pub access_ttl: i64,
+    /// Lifetime of refresh tokens, in seconds.
+    pub refresh_ttl: i64,
+}
+
+impl JwtSettings {
+    fn from_env() -> Self {
+        Self {
+            algorithm: match env::var("JWT_ALGORITHM").as_deref() {
+                Ok("EdDSA") => JwtAlgorithm::EdDsa,
+                _ => JwtAlgorithm::Hs256,
+            },
+            secret: env::var("JWT_SECRET").ok(),
+            private_key_path: env::var("JWT_PRIVATE_KEY_PATH").ok().map(PathBuf::from),
+            public_key_path: env::var("JWT_PUBLIC_KEY_PATH").ok().map(PathBuf::from),
+            issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "registry".to_string()),
+            access_ttl: env::var("JWT_ACCESS_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15 * 60),
+            refresh_ttl: env::var("JWT_REFRESH_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub storage_backend: StorageBackend,
+    /// Local directory for staged uploads, and for blobs when the
+    /// filesystem backend is selected.
+    pub storage_path: PathBuf,
+    pub s3: Option<S3Settings>,
+    pub max_upload_size: usize,
+    /// The most rows a listing endpoint returns per page.
+    pub max_page_size: i64,
+    /// How many days the index changes feed keeps changes for.
+    pub index_retention_days: i64,
+    pub apt: AptSettings,
+    pub signing: SigningSettings,
+    pub passwords: PasswordSettings,
+    pub jwt: JwtSettings,
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        Self {
+            storage_backend: match env::var("STORAGE_BACKEND").as_deref() {
+                Ok("s3") => StorageBackend::S3,
+                _ => StorageBackend::Filesystem,
+            },
+            storage_path: env::var("STORAGE_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("./storage")),
+            s3: S3Settings::from_env(),
+            max_upload_size: env::var("MAX_UPLOAD_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(512 * 1024 * 1024),
+            max_page_size: env::var("MAX_PAGE_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&size| size > 0)
+                .unwrap_or(100),
+            index_retention_days: env::var("INDEX_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&days| days > 0)
+                .unwrap_or(30),
+            apt: AptSettings::from_env(),
+            signing: SigningSettings::from_env(),
+            passwords: PasswordSettings::from_env(),
+            jwt: JwtSettings::from_env(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub client: PrismaClient,
+    pub settings: Arc<Settings>,
+    pub storage: Arc<dyn BlobStorage>,
+    pub repository: Arc<Repository>,
+    pub signer: Option<Arc<Signer>>,
+    pub tokens: Arc<TokenKeys>,
+}
+
+impl FromRef<AppState> for PrismaClient {
+    fn from_ref(state: &AppState) -> Self {
+        state.client.clone()
+    }
+}
+
+pub fn create_app(
+    client: PrismaClient,
+    settings: Settings,
+    storage: Arc<dyn BlobStorage>,
+    signer: Option<Signer>,
+    tokens: TokenKeys,
+) -> Router {
+    let state = AppState {
+        client,
+        settings: Arc::new(settings),
+        storage,
+        repository: Arc::new(Repository::new()),
+        signer: signer.map(Arc::new),
+        tokens: Arc::new(tokens),
+    };
+
+    state.repository.schedule_rebuild(&state);
+    schedule_pruning(state.client.clone(), state.settings.index_retention_days);
+
+    // `authorize` needs the matched route, so both run as route layers:
+    // `auth_middleware` identifies the caller, then `authorize` applies the
+    // route's policy.
     Router::new()
         .route("/", get(health_check))
-        .nest("/api/packages", package_routes())
+        .nest("/api/packages", package_routes(&state.settings))
         .nest("/api/users", user_routes())
         .nest("/api/auth", auth_routes())
+        .nest("/api/auth/tokens", token_routes())
+        .nest("/api/owners", owner_routes())
+        .nest("/api/transfers", transfer_routes())
+        .nest("/api/orgs", org_routes())
+        .nest("/api/tags", tag_routes())
+        .nest("/api/index", index_routes())
+        .nest("/api/search", search_routes())
+        .route_layer(middleware::from_fn(authorize))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
+        .nest("/apt", repository_routes())
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
                 .layer(cors_layer())
         )
-        .layer(middleware::from_fn(auth_middleware))
-        .with_state(client)
+        .with_state(state)
 }
 
 async fn health_check() -> &'static str {
     "Registry Service is running!"
-}
\ No newline at end of file
+}
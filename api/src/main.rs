@@ -3,10 +3,15 @@ mod db;
 mod models;
 mod routes;
 mod middlewares;
+mod errors;
+mod services;
+mod storage;
+mod utils;
 
 use axum::Router;
 use db::PrismaClient;
-use config::create_app;
+use config::{create_app, Settings};
+use services::{auth_services::TokenKeys, signing_services::Signer};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,7 +23,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build()
         .await?;
 
-    let app = create_app(client);
+    let settings = Settings::from_env();
+    let storage = storage::from_settings(&settings)?;
+    let signer = Signer::load(&settings.signing, settings.storage_path.join("gnupg")).await?;
+    if signer.is_none() {
+        tracing::warn!("SIGNING_KEY_PATH is not set, the apt repository will be unsigned");
+    }
+
+    let tokens = TokenKeys::from_settings(&settings.jwt)?;
+
+    let app = create_app(client, settings, storage, signer, tokens);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     tracing::info!("Server running on http://0.0.0.0:3000");
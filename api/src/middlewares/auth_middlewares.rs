@@ -0,0 +1,245 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    services::{
+        auth_services::{session_is_active, Caller, Credential, Scope, TokenError},
+        token_services::{authenticate_api_token, TOKEN_PREFIX},
+    },
+};
+
+/// What a route requires of its caller.
+#[derive(Debug, Clone, Copy)]
+pub enum Access {
+    /// Open to anonymous callers.
+    Public,
+    /// Any authenticated caller. Handlers check ownership themselves.
+    Authenticated,
+    /// An authenticated caller whose credential grants the scope.
+    Scoped(Scope),
+    /// An administrator whose credential grants `users:admin`.
+    Admin,
+    /// A login session; API tokens are refused.
+    Session,
+}
+
+/// The access policy of every API route, by method and route path. Routes
+/// missing from this table are refused, so a new route cannot be exposed
+/// by accident.
+static ROUTE_POLICIES: &[(Method, &str, Access)] = &[
+    (Method::GET, "/", Access::Public),
+    // Packages
+    (Method::GET, "/api/packages", Access::Public),
+    (Method::POST, "/api/packages", Access::Scoped(Scope::PackagesPublish)),
+    (Method::GET, "/api/packages/:name", Access::Public),
+    (Method::PUT, "/api/packages/:name", Access::Scoped(Scope::PackagesPublish)),
+    (Method::GET, "/api/packages/:name/versions", Access::Public),
+    (Method::PUT, "/api/packages/:name/tags/:tag", Access::Scoped(Scope::PackagesPublish)),
+    (Method::DELETE, "/api/packages/:name/tags/:tag", Access::Scoped(Scope::PackagesPublish)),
+    (Method::GET, "/api/packages/:name/:version", Access::Public),
+    (Method::PUT, "/api/packages/:name/:version", Access::Scoped(Scope::PackagesPublish)),
+    (Method::DELETE, "/api/packages/:name/:version", Access::Scoped(Scope::PackagesDelete)),
+    (Method::GET, "/api/packages/:name/:version/download", Access::Public),
+    (Method::GET, "/api/packages/:name/:version/signature", Access::Public),
+    // Users
+    (Method::GET, "/api/users", Access::Admin),
+    (Method::POST, "/api/users", Access::Admin),
+    (Method::GET, "/api/users/:id", Access::Authenticated),
+    (Method::PUT, "/api/users/:id", Access::Session),
+    (Method::DELETE, "/api/users/:id", Access::Session),
+    (Method::GET, "/api/users/:id/packages", Access::Public),
+    // Authentication
+    (Method::POST, "/api/auth/login", Access::Public),
+    (Method::POST, "/api/auth/register", Access::Public),
+    (Method::POST, "/api/auth/refresh", Access::Public),
+    (Method::POST, "/api/auth/logout", Access::Public),
+    (Method::GET, "/api/auth/tokens", Access::Session),
+    (Method::POST, "/api/auth/tokens", Access::Session),
+    (Method::DELETE, "/api/auth/tokens/:id", Access::Session),
+    // Package ownership
+    (Method::GET, "/api/owners/:name", Access::Public),
+    (Method::POST, "/api/owners/:name", Access::Session),
+    (Method::DELETE, "/api/owners/:name/:username", Access::Session),
+    (Method::GET, "/api/transfers", Access::Session),
+    (Method::POST, "/api/transfers", Access::Session),
+    (Method::DELETE, "/api/transfers/:id", Access::Session),
+    (Method::POST, "/api/transfers/:id/accept", Access::Session),
+    // Organizations
+    (Method::GET, "/api/orgs", Access::Authenticated),
+    (Method::POST, "/api/orgs", Access::Session),
+    (Method::GET, "/api/orgs/:org", Access::Authenticated),
+    (Method::DELETE, "/api/orgs/:org", Access::Session),
+    (Method::GET, "/api/orgs/:org/packages", Access::Public),
+    (Method::PUT, "/api/orgs/:org/members/:username", Access::Session),
+    (Method::DELETE, "/api/orgs/:org/members/:username", Access::Session),
+    (Method::POST, "/api/orgs/:org/teams", Access::Session),
+    (Method::PUT, "/api/orgs/:org/teams/:team", Access::Session),
+    (Method::DELETE, "/api/orgs/:org/teams/:team", Access::Session),
+    (Method::PUT, "/api/orgs/:org/teams/:team/members/:username", Access::Session),
+    (Method::DELETE, "/api/orgs/:org/teams/:team/members/:username", Access::Session),
+    // Tags
+    (Method::GET, "/api/tags", Access::Public),
+    (Method::POST, "/api/tags", Access::Admin),
+    (Method::GET, "/api/tags/:tag", Access::Public),
+    (Method::PUT, "/api/tags/:tag", Access::Admin),
+    (Method::DELETE, "/api/tags/:tag", Access::Admin),
+    (Method::POST, "/api/tags/:tag/merge", Access::Admin),
+    // Index
+    (Method::GET, "/api/index", Access::Public),
+    (Method::GET, "/api/index/changes", Access::Public),
+    // Search
+    (Method::GET, "/api/search", Access::Public),
+];
+
+fn route_policy(method: &Method, path: &str) -> Option<Access> {
+    let path = match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    };
+
+    ROUTE_POLICIES
+        .iter()
+        .find(|(m, p, _)| m == method && *p == path)
+        .map(|(_, _, access)| *access)
+}
+
+/// Identifies the caller from the bearer token, either a session JWT or an
+/// API token, and makes the `Caller` available through the request
+/// extensions. Requests without a token pass through anonymously; whether
+/// that is allowed is up to `authorize`. So do requests to public routes
+/// whose token is rejected, which need no caller.
+pub async fn auth_middleware(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty());
+
+    let Some(token) = token else {
+        return next.run(req).await;
+    };
+
+    let caller = match identify(&state, token).await {
+        Ok(Ok(caller)) => caller,
+        Ok(Err(_)) if is_public(&req) => return next.run(req).await,
+        Ok(Err(err)) => return unauthorized(Some(err)),
+        Err(err) => return ApiError::internal(err).into_response(),
+    };
+
+    req.extensions_mut().insert(caller);
+    next.run(req).await
+}
+
+/// The caller a bearer token stands for, or why it was rejected.
+async fn identify(state: &AppState, token: &str) -> anyhow::Result<Result<Caller, TokenError>> {
+    if token.starts_with(TOKEN_PREFIX) {
+        return authenticate_api_token(&state.client, token).await;
+    }
+
+    let claims = match state.tokens.verify(token) {
+        Ok(claims) => claims,
+        Err(err) => return Ok(Err(err)),
+    };
+
+    if let Some(session_id) = &claims.sid
+        && !session_is_active(&state.client, session_id).await?
+    {
+        return Ok(Err(TokenError::Revoked));
+    }
+
+    Ok(Ok(Caller::from_claims(claims)))
+}
+
+fn is_public(req: &Request) -> bool {
+    req.extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| route_policy(req.method(), path.as_str()))
+        .is_some_and(|access| matches!(access, Access::Public))
+}
+
+/// Enforces `ROUTE_POLICIES` for the matched route.
+pub async fn authorize(req: Request, next: Next) -> Response {
+    let access = req
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| route_policy(req.method(), path.as_str()));
+
+    let Some(access) = access else {
+        return forbidden("no_policy", "This route is not available").into_response();
+    };
+
+    if let Access::Public = access {
+        return next.run(req).await;
+    }
+
+    let Some(caller) = req.extensions().get::<Caller>() else {
+        return unauthorized(None);
+    };
+
+    let allowed = match access {
+        Access::Public | Access::Authenticated => Ok(()),
+        Access::Scoped(scope) => caller.require_scope(scope),
+        Access::Admin if caller.acts_as_admin() => Ok(()),
+        Access::Admin => Err(forbidden(
+            "admin_required",
+            "This action is reserved for administrators",
+        )),
+        Access::Session => match caller.credential {
+            Credential::Session { .. } => Ok(()),
+            Credential::ApiToken { .. } => Err(forbidden(
+                "session_required",
+                "This action requires a login session, not an API token",
+            )),
+        },
+    };
+
+    match allowed {
+        Ok(()) => next.run(req).await,
+        Err(err) => err.into_response(),
+    }
+}
+
+fn forbidden(code: &'static str, message: &str) -> ApiError {
+    ApiError::new(StatusCode::FORBIDDEN, code, message)
+}
+
+/// A 401 whose `WWW-Authenticate` challenge follows RFC 6750: requests
+/// without credentials get a bare challenge, rejected tokens get an
+/// `invalid_token` error and the reason.
+fn unauthorized(err: Option<TokenError>) -> Response {
+    let (challenge, message) = match err {
+        Some(err) => (
+            format!(
+                "Bearer realm=\"registry\", error=\"invalid_token\", error_description=\"{}\"",
+                err.description()
+            ),
+            err.description(),
+        ),
+        None => (
+            "Bearer realm=\"registry\"".to_string(),
+            "Authentication is required",
+        ),
+    };
+
+    let error = ApiError::new(
+        StatusCode::UNAUTHORIZED,
+        "unauthorized",
+        message,
+    );
+
+    match HeaderValue::from_str(&challenge) {
+        Ok(value) => ([(header::WWW_AUTHENTICATE, value)], error).into_response(),
+        Err(_) => error.into_response(),
+    }
+}
@@ -1,27 +1,15 @@
-use axum::{
-    http::{Request, StatusCode},
-    middleware::Next,
-    response::Response,
-};
+use axum::http::header::LINK;
 use tower_http::cors::{Any, CorsLayer};
+use crate::utils::pagination::TOTAL_COUNT;
+
+pub mod auth_middlewares;
+
+pub use auth_middlewares::*;
 
 pub fn cors_layer() -> CorsLayer {
     CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any)
+        .expose_headers([LINK, TOTAL_COUNT])
 }
-
-pub async fn auth_middleware<B>(
-    req: Request<B>,
-    next: Next<B>,
-) -> Result<Response, StatusCode> {
-    let auth_header = req.headers().get("authorization");
-    
-    match auth_header {
-        Some(header) if header.to_str().unwrap_or("").starts_with("Bearer ") => {
-            Ok(next.run(req).await)
-        }
-        _ => Err(StatusCode::UNAUTHORIZED),
-    }
-}
\ No newline at end of file
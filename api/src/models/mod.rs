@@ -7,16 +7,25 @@ pub struct PackageRequest {
     pub description: Option<String>,
     pub maintainer: String,
     pub architecture: String,
+    #[serde(default)]
     pub size: i64,
-    pub dependencies: Vec<DependencyRequest>,
-    pub tags: Vec<String>,
+    /// Replaces the dependencies when present; omit to keep them.
+    #[serde(default)]
+    pub dependencies: Option<Vec<DependencyRequest>>,
+    /// Replaces the package's tags when present; omit to keep them.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DependencyRequest {
     pub name: String,
+    #[serde(default)]
     pub version: String,
     pub dependency_type: String,
+    /// Dependencies sharing a group are alternatives (`a | b`).
+    #[serde(default)]
+    pub alternative_group: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,6 +38,8 @@ pub struct PackageResponse {
     pub architecture: String,
     pub size: i64,
     pub checksum: String,
+    /// Whether `/:name/:version/signature` has a signature of the artifact.
+    pub signed: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
     pub author: String,
@@ -36,11 +47,80 @@ pub struct PackageResponse {
     pub tags: Vec<TagResponse>,
 }
 
+/// A package name with the data shared by its versions.
+/// A search result: the package with its newest version, how well it
+/// matched and a description snippet with the matches in `<mark>`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResultResponse {
+    #[serde(flatten)]
+    pub package: PackageResponse,
+    pub relevance: f64,
+    pub snippet: Option<String>,
+}
+
+/// A package in the index with all its versions, newest first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexPackageResponse {
+    pub name: String,
+    pub versions: Vec<PackageResponse>,
+}
+
+/// The whole package index as of the change `cursor`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexResponse {
+    pub cursor: i64,
+    pub packages: Vec<IndexPackageResponse>,
+}
+
+/// The packages that changed after a cursor, up to the change `cursor`.
+/// Each replaces the client's copy; no versions means it was removed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexChangesResponse {
+    pub cursor: i64,
+    /// Whether more changes follow `cursor`.
+    pub more: bool,
+    pub packages: Vec<IndexPackageResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackageDetailResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    /// What to use instead, when the package is deprecated.
+    pub deprecated: Option<String>,
+    /// Whether new versions must be newer than the latest one.
+    pub strict_versioning: bool,
+    pub downloads: i64,
+    pub author: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub tags: Vec<TagResponse>,
+    /// Published versions, newest first by Debian version ordering.
+    pub versions: Vec<String>,
+    pub latest: Option<PackageResponse>,
+}
+
+/// Changes to the name-level data of a package. Omitted fields are left
+/// alone and empty strings clear them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackageMetadataRequest {
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    pub deprecated: Option<String>,
+    pub strict_versioning: Option<bool>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DependencyResponse {
     pub name: String,
     pub version: String,
     pub dependency_type: String,
+    /// Dependencies sharing a group are alternatives (`a | b`).
+    pub alternative_group: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,11 +129,40 @@ pub struct TagResponse {
     pub color: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagRequest {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+/// Renames and/or recolors a tag.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagUpdateRequest {
+    pub name: Option<String>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagMergeRequest {
+    /// The tag that takes over the packages of the merged one.
+    pub into: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagSummaryResponse {
+    pub name: String,
+    pub color: String,
+    pub packages: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserRequest {
     pub username: String,
     pub email: String,
     pub password: String,
+    /// Only administrators may set this.
+    #[serde(default)]
+    pub role: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -74,5 +183,129 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthResponse {
     pub token: String,
+    pub refresh_token: String,
+    /// Seconds until `token` expires.
+    pub expires_in: i64,
     pub user: UserResponse,
-}
\ No newline at end of file
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Restricts the token to packages whose name matches this glob.
+    pub package_pattern: Option<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiTokenResponse {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub package_pattern: Option<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedApiTokenResponse {
+    /// The secret, which is only ever shown in this response.
+    pub token: String,
+    #[serde(flatten)]
+    pub details: ApiTokenResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OwnerRequest {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OwnerResponse {
+    pub user_id: String,
+    pub username: String,
+    /// `OWNER` or `MAINTAINER`.
+    pub role: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferRequest {
+    pub package: String,
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferResponse {
+    pub id: String,
+    pub package: String,
+    pub from: String,
+    pub to: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrganizationRequest {
+    pub name: String,
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrganizationResponse {
+    pub id: String,
+    pub name: String,
+    pub display_name: Option<String>,
+    /// The caller's role, when they are a member.
+    pub role: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrganizationDetailResponse {
+    #[serde(flatten)]
+    pub organization: OrganizationResponse,
+    pub members: Vec<MemberResponse>,
+    pub teams: Vec<TeamResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemberRequest {
+    /// `owner`, `admin` or `member`; defaults to `member`.
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemberResponse {
+    pub user_id: String,
+    pub username: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamRequest {
+    pub name: String,
+    #[serde(default)]
+    pub can_publish: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamUpdateRequest {
+    pub can_publish: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamResponse {
+    pub id: String,
+    pub name: String,
+    pub can_publish: bool,
+    /// Usernames of the team's members.
+    pub members: Vec<String>,
+}
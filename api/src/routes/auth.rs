@@ -6,77 +6,155 @@ use axum::{
     Router,
 };
 use crate::{
-    db::PrismaClient,
-    models::{LoginRequest, AuthResponse},
+    config::{AppState, LegacyPasswordPolicy},
+    errors::ApiError,
+    models::{LoginRequest, AuthResponse, RefreshRequest},
     prisma::{user},
+    services::{
+        auth_services::{
+            end_session, hash_password, refresh_session, start_session, verify_password,
+            IssuedTokens, PasswordCheck,
+        },
+        user_services::user_write_error,
+    },
 };
 
-pub fn auth_routes() -> Router<PrismaClient> {
+pub fn auth_routes() -> Router<AppState> {
     Router::new()
         .route("/login", post(login))
         .route("/register", post(register))
+        .route("/refresh", post(refresh))
         .route("/logout", post(logout))
 }
 
 async fn login(
-    State(client): State<PrismaClient>,
+    State(state): State<AppState>,
     Json(payload): Json<LoginRequest>,
-) -> Result<Json<AuthResponse>, StatusCode> {
-    let user = client
+) -> Result<Json<AuthResponse>, ApiError> {
+    let settings = &state.settings.passwords;
+    let user = state
+        .client
         .user()
         .find_first(vec![user::username::equals(payload.username)])
         .exec()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(ApiError::internal)?;
+
+    let Some(u) = user else {
+        // Do the same amount of work as for a real account so response
+        // times do not reveal which usernames exist.
+        let _ = hash_password(settings, payload.password).await;
+        return Err(invalid_credentials());
+    };
 
-    match user {
-        Some(u) if u.password == payload.password => {
-            let token = format!("token_for_{}", u.username);
-            Ok(Json(AuthResponse {
-                token,
-                user: crate::models::UserResponse {
-                    id: u.id,
-                    username: u.username,
-                    email: u.email,
-                    role: format!("{:?}", u.role),
-                    created_at: u.created_at.into(),
-                },
-            }))
+    let check = verify_password(settings, payload.password.clone(), u.password.clone())
+        .await
+        .map_err(ApiError::internal)?;
+
+    match check {
+        PasswordCheck::Invalid => return Err(invalid_credentials()),
+        PasswordCheck::Legacy if settings.legacy_policy == LegacyPasswordPolicy::Reject => {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "password_reset_required",
+                "This account's password must be reset before logging in",
+            ));
+        }
+        PasswordCheck::Legacy | PasswordCheck::NeedsRehash => {
+            // A failed upgrade is retried on the next login and must not
+            // block this one.
+            if let Err(err) = rehash_password(&state, &u.id, payload.password).await {
+                tracing::warn!("failed to rehash password for user {}: {:#}", u.id, err);
+            }
         }
-        _ => Err(StatusCode::UNAUTHORIZED),
+        PasswordCheck::Valid => {}
+    }
+
+    let issued = start_session(&state.client, &state.tokens, &u)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(Json(auth_response(issued, u)))
+}
+
+async fn refresh(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<AuthResponse>, ApiError> {
+    let (issued, user) = refresh_session(&state.client, &state.tokens, &payload.refresh_token).await?;
+    Ok(Json(auth_response(issued, user)))
+}
+
+fn auth_response(issued: IssuedTokens, user: user::Data) -> AuthResponse {
+    AuthResponse {
+        token: issued.access_token,
+        refresh_token: issued.refresh_token,
+        expires_in: issued.expires_in,
+        user: crate::models::UserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            role: format!("{:?}", user.role),
+            created_at: user.created_at.into(),
+        },
     }
 }
 
+async fn rehash_password(state: &AppState, id: &str, password: String) -> anyhow::Result<()> {
+    let password = hash_password(&state.settings.passwords, password).await?;
+    state
+        .client
+        .user()
+        .update(user::id::equals(id.to_string()), vec![user::password::set(password)])
+        .exec()
+        .await?;
+    Ok(())
+}
+
+fn invalid_credentials() -> ApiError {
+    ApiError::new(
+        StatusCode::UNAUTHORIZED,
+        "invalid_credentials",
+        "Invalid username or password",
+    )
+}
+
 async fn register(
-    State(client): State<PrismaClient>,
+    State(state): State<AppState>,
     Json(payload): Json<crate::models::UserRequest>,
-) -> Result<Json<AuthResponse>, StatusCode> {
-    let user = client
+) -> Result<Json<AuthResponse>, ApiError> {
+    let password = hash_password(&state.settings.passwords, payload.password)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let user = state
+        .client
         .user()
         .create(
             payload.username.clone(),
             payload.email,
-            payload.password,
+            password,
             crate::prisma::user_role::USER,
             vec![],
         )
         .exec()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(user_write_error)?;
 
-    let token = format!("token_for_{}", user.username);
-    Ok(Json(AuthResponse {
-        token,
-        user: crate::models::UserResponse {
-            id: user.id,
-            username: user.username,
-            email: user.email,
-            role: format!("{:?}", user.role),
-            created_at: user.created_at.into(),
-        },
-    }))
+    let issued = start_session(&state.client, &state.tokens, &user)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(Json(auth_response(issued, user)))
 }
 
-async fn logout() -> Result<StatusCode, StatusCode> {
-    Ok(StatusCode::OK)
-}
\ No newline at end of file
+/// Revokes the session of the given refresh token, which also invalidates
+/// access tokens issued for it.
+async fn logout(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<StatusCode, ApiError> {
+    end_session(&state.client, &payload.refresh_token)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
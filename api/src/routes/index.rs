@@ -0,0 +1,92 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use axum_extra::extract::Query;
+use serde::Deserialize;
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    models::IndexResponse,
+    services::index_services::{changes_since, index_packages, log_state, LogState},
+    utils::if_none_match,
+};
+
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    pub since: i64,
+}
+
+pub fn index_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_index))
+        .route("/changes", get(get_changes))
+}
+
+/// Every version of every package, for clients to keep a local index of.
+async fn get_index(State(state): State<AppState>, headers: HeaderMap) -> Result<Response, ApiError> {
+    // The bound is read first, so packages read after it can only be newer.
+    let log = log_state(&state.client).await?;
+    let (response_headers, unchanged) = cache_headers(&log, &headers)?;
+    if unchanged {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let index = IndexResponse {
+        cursor: log.bound,
+        packages: index_packages(&state.client, vec![]).await?,
+    };
+
+    Ok((response_headers, Json(index)).into_response())
+}
+
+/// The packages that changed from the cursor of an earlier index or page
+/// of changes on.
+async fn get_changes(
+    State(state): State<AppState>,
+    Query(query): Query<ChangesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let log = log_state(&state.client).await?;
+    // The bound never goes back, so a cursor past it comes from a different
+    // database; the client has to start over from the whole index.
+    if query.since > log.bound {
+        return Err(ApiError::new(
+            StatusCode::GONE,
+            "unknown_cursor",
+            format!("Cursor {} is unknown; fetch the whole index", query.since),
+        ));
+    }
+    // The changes from an old cursor on may have been pruned.
+    if log.oldest.is_some_and(|oldest| query.since < oldest) {
+        return Err(ApiError::new(
+            StatusCode::GONE,
+            "expired_cursor",
+            format!("Cursor {} has expired; fetch the whole index", query.since),
+        ));
+    }
+
+    let (response_headers, unchanged) = cache_headers(&log, &headers)?;
+    if unchanged {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let changes = changes_since(&state.client, &log, query.since).await?;
+    Ok((response_headers, Json(changes)).into_response())
+}
+
+/// The entity tag of the index as of its newest final change, and whether
+/// the request's `If-None-Match` shows the client already has it. There is
+/// no `Last-Modified`: changes are stamped when made, not when committed,
+/// so a date cannot tell which of them a client has seen.
+fn cache_headers(log: &LogState, headers: &HeaderMap) -> Result<(HeaderMap, bool), ApiError> {
+    let etag = format!("\"{}\"", log.latest.unwrap_or(0));
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&etag).map_err(ApiError::internal)?);
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    Ok((response_headers, if_none_match(headers, &etag)))
+}
@@ -1,7 +1,21 @@
 pub mod packages;
 pub mod users;
 pub mod auth;
+pub mod repository;
+pub mod tokens;
+pub mod owners;
+pub mod orgs;
+pub mod tags;
+pub mod index;
+pub mod search;
 
 pub use packages::*;
 pub use users::*;
-pub use auth::*;
\ No newline at end of file
+pub use auth::*;
+pub use repository::*;
+pub use tokens::*;
+pub use owners::*;
+pub use orgs::*;
+pub use tags::*;
+pub use index::*;
+pub use search::*;
\ No newline at end of file
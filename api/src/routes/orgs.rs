@@ -0,0 +1,412 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post, put},
+    Extension, Router,
+};
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    models::{
+        MemberRequest, MemberResponse, OrganizationDetailResponse, OrganizationRequest,
+        OrganizationResponse, PackageResponse, TeamRequest, TeamResponse, TeamUpdateRequest,
+    },
+    prisma::{org_role, organization, organization_member, package, team, team_member, user, OrgRole},
+    services::{
+        auth_services::Caller,
+        org_services::{
+            create_org, find_org, find_team, org_role_of, parse_org_role, remove_member,
+            require_org_role, set_member, validate_org_name,
+        },
+        packages_services::{latest_response, latest_version_fetch, tags_fetch},
+        user_services::find_user_by_username,
+    },
+};
+
+pub fn org_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_orgs).post(create_organization))
+        .route("/:org", get(get_org).delete(delete_org))
+        .route("/:org/packages", get(get_org_packages))
+        .route("/:org/members/:username", put(put_member).delete(delete_member))
+        .route("/:org/teams", post(create_team))
+        .route("/:org/teams/:team", put(update_team).delete(delete_team))
+        .route(
+            "/:org/teams/:team/members/:username",
+            put(put_team_member).delete(delete_team_member),
+        )
+}
+
+/// The organizations the caller belongs to.
+async fn get_orgs(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<OrganizationResponse>>, ApiError> {
+    let memberships = state
+        .client
+        .organization_member()
+        .find_many(vec![organization_member::user_id::equals(caller.user_id.clone())])
+        .with(organization_member::organization::fetch())
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    memberships
+        .into_iter()
+        .map(|member| {
+            let org = member.organization().map_err(ApiError::internal)?;
+            Ok(org_response(org.clone(), Some(member.role)))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Json)
+}
+
+async fn create_organization(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<OrganizationRequest>,
+) -> Result<(StatusCode, Json<OrganizationResponse>), ApiError> {
+    let name = payload.name.trim().trim_start_matches('@').to_string();
+    validate_org_name(&name, &state.settings.apt.component)?;
+
+    let display_name = payload
+        .display_name
+        .map(|display_name| display_name.trim().to_string())
+        .filter(|display_name| !display_name.is_empty());
+
+    let org = create_org(&state.client, name, display_name, &caller.user_id).await?;
+    Ok((StatusCode::CREATED, Json(org_response(org, Some(org_role::OWNER)))))
+}
+
+async fn get_org(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(name): Path<String>,
+) -> Result<Json<OrganizationDetailResponse>, ApiError> {
+    let org = state
+        .client
+        .organization()
+        .find_unique(organization::name::equals(name.clone()))
+        .with(organization::members::fetch(vec![]).with(organization_member::user::fetch()))
+        .with(
+            organization::teams::fetch(vec![])
+                .with(team::members::fetch(vec![]).with(team_member::user::fetch())),
+        )
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("Organization {} does not exist", name)))?;
+
+    let members = org
+        .members()
+        .map_err(ApiError::internal)?
+        .iter()
+        .map(|member| {
+            Ok(MemberResponse {
+                user_id: member.user_id.clone(),
+                username: member.user().map_err(ApiError::internal)?.username.clone(),
+                role: format!("{:?}", member.role),
+            })
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+
+    let teams = org
+        .teams()
+        .map_err(ApiError::internal)?
+        .iter()
+        .map(team_response)
+        .collect::<Result<Vec<_>, ApiError>>()?;
+
+    let role = members
+        .iter()
+        .find(|member| member.user_id == caller.user_id)
+        .map(|member| member.role.clone());
+
+    Ok(Json(OrganizationDetailResponse {
+        organization: OrganizationResponse {
+            id: org.id.clone(),
+            name: org.name.clone(),
+            display_name: org.display_name.clone(),
+            role,
+            created_at: org.created_at.into(),
+        },
+        members,
+        teams,
+    }))
+}
+
+/// Deletes an organization. Its namespace has to be empty first, so that
+/// nobody else can claim the name and publish over existing packages.
+async fn delete_org(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    require_org_role(&state.client, &caller, &org, org_role::OWNER).await?;
+
+    let packages = state
+        .client
+        .package()
+        .count(vec![package::name::starts_with(format!("@{}/", org.name))])
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if packages > 0 {
+        return Err(ApiError::conflict(
+            "organization_not_empty",
+            format!("{} still has {} published packages", org.name, packages),
+        ));
+    }
+
+    state
+        .client
+        .organization()
+        .delete(organization::id::equals(org.id))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_org_packages(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<PackageResponse>>, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+
+    let packages = state
+        .client
+        .package()
+        .find_many(vec![package::name::starts_with(format!("@{}/", org.name))])
+        .with(latest_version_fetch())
+        .with(tags_fetch())
+        .order_by(package::name::order(prisma_client_rust::Direction::Asc))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    let package_responses: Vec<PackageResponse> = packages
+        .into_iter()
+        .filter_map(latest_response)
+        .collect();
+
+    Ok(Json(package_responses))
+}
+
+/// Adds a member or changes their role.
+async fn put_member(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, username)): Path<(String, String)>,
+    Json(payload): Json<MemberRequest>,
+) -> Result<Json<MemberResponse>, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    let user = find_user_by_username(&state.client, &username).await?;
+    let role = parse_org_role(payload.role.as_deref().unwrap_or("member"))?;
+
+    let member = set_member(&state.client, &caller, &org, &user.id, role).await?;
+    Ok(Json(MemberResponse {
+        user_id: member.user_id,
+        username: user.username,
+        role: format!("{:?}", member.role),
+    }))
+}
+
+async fn delete_member(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, username)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    let user = find_user_by_username(&state.client, &username).await?;
+
+    remove_member(&state.client, &caller, &org, &user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn create_team(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(name): Path<String>,
+    Json(payload): Json<TeamRequest>,
+) -> Result<(StatusCode, Json<TeamResponse>), ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    require_org_role(&state.client, &caller, &org, org_role::ADMIN).await?;
+
+    let team_name = payload.name.trim().to_string();
+    let valid = !team_name.is_empty()
+        && team_name.len() <= 39
+        && team_name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(ApiError::bad_request(
+            "invalid_team_name",
+            "Team names use up to 39 lowercase letters, digits and '-'",
+        ));
+    }
+
+    let existing = state
+        .client
+        .team()
+        .find_unique(team::organization_id_name(org.id.clone(), team_name.clone()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if existing.is_some() {
+        return Err(ApiError::conflict(
+            "team_exists",
+            format!("Team {} already exists in {}", team_name, org.name),
+        ));
+    }
+
+    let team = state
+        .client
+        .team()
+        .create(
+            organization::id::equals(org.id),
+            team_name,
+            vec![team::can_publish::set(payload.can_publish)],
+        )
+        .with(team::members::fetch(vec![]))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok((StatusCode::CREATED, Json(team_response(&team)?)))
+}
+
+async fn update_team(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, team_name)): Path<(String, String)>,
+    Json(payload): Json<TeamUpdateRequest>,
+) -> Result<Json<TeamResponse>, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    require_org_role(&state.client, &caller, &org, org_role::ADMIN).await?;
+    let team = find_team(&state.client, &org, &team_name).await?;
+
+    let team = state
+        .client
+        .team()
+        .update(
+            team::id::equals(team.id),
+            vec![team::can_publish::set(payload.can_publish)],
+        )
+        .with(team::members::fetch(vec![]).with(team_member::user::fetch()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(Json(team_response(&team)?))
+}
+
+async fn delete_team(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, team_name)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    require_org_role(&state.client, &caller, &org, org_role::ADMIN).await?;
+    let team = find_team(&state.client, &org, &team_name).await?;
+
+    state
+        .client
+        .team()
+        .delete(team::id::equals(team.id))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn put_team_member(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, team_name, username)): Path<(String, String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    require_org_role(&state.client, &caller, &org, org_role::ADMIN).await?;
+    let team = find_team(&state.client, &org, &team_name).await?;
+    let user = find_user_by_username(&state.client, &username).await?;
+
+    if org_role_of(&state.client, &org, &user.id).await?.is_none() {
+        return Err(ApiError::bad_request(
+            "not_org_member",
+            format!("{} has to join {} before joining one of its teams", username, org.name),
+        ));
+    }
+
+    state
+        .client
+        .team_member()
+        .upsert(
+            team_member::team_id_user_id(team.id.clone(), user.id.clone()),
+            team_member::create(team::id::equals(team.id), user::id::equals(user.id), vec![]),
+            vec![],
+        )
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_team_member(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, team_name, username)): Path<(String, String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let org = find_org(&state.client, &name).await?;
+    let user = find_user_by_username(&state.client, &username).await?;
+    if caller.user_id != user.id {
+        require_org_role(&state.client, &caller, &org, org_role::ADMIN).await?;
+    }
+    let team = find_team(&state.client, &org, &team_name).await?;
+
+    let removed = state
+        .client
+        .team_member()
+        .delete_many(vec![
+            team_member::team_id::equals(team.id),
+            team_member::user_id::equals(user.id),
+        ])
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    if removed == 0 {
+        return Err(ApiError::not_found(format!("{} is not in team {}", username, team_name)));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn org_response(org: organization::Data, role: Option<OrgRole>) -> OrganizationResponse {
+    OrganizationResponse {
+        id: org.id,
+        name: org.name,
+        display_name: org.display_name,
+        role: role.map(|role| format!("{:?}", role)),
+        created_at: org.created_at.into(),
+    }
+}
+
+fn team_response(team: &team::Data) -> Result<TeamResponse, ApiError> {
+    let members = team
+        .members()
+        .map_err(ApiError::internal)?
+        .iter()
+        .filter_map(|member| member.user().ok().map(|user| user.username.clone()))
+        .collect();
+
+    Ok(TeamResponse {
+        id: team.id.clone(),
+        name: team.name.clone(),
+        can_publish: team.can_publish,
+        members,
+    })
+}
@@ -0,0 +1,139 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{delete, get, post},
+    Extension, Router,
+};
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    models::{OwnerRequest, OwnerResponse, TransferRequest, TransferResponse},
+    prisma::{ownership_transfer, package_owner},
+    services::{
+        auth_services::Caller,
+        ownership_services::{
+            accept_transfer, add_maintainer, cancel_transfer, list_owners, list_transfers,
+            remove_owner, request_transfer, require_package_owner, transfer_expires_at,
+        },
+        user_services::find_user_by_username,
+    },
+};
+
+pub fn owner_routes() -> Router<AppState> {
+    Router::new()
+        .route("/:name", get(get_owners).post(add_owner))
+        .route("/:name/:username", delete(delete_owner))
+}
+
+pub fn transfer_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_transfers).post(create_transfer))
+        .route("/:id", delete(delete_transfer))
+        .route("/:id/accept", post(accept))
+}
+
+async fn get_owners(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<OwnerResponse>>, ApiError> {
+    let owners = list_owners(&state.client, &name)
+        .await
+        .map_err(ApiError::internal)?;
+    if owners.is_empty() {
+        return Err(ApiError::not_found(format!("Package {} does not exist", name)));
+    }
+
+    owners
+        .into_iter()
+        .map(owner_response)
+        .collect::<Result<Vec<_>, _>>()
+        .map(Json)
+}
+
+async fn add_owner(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(name): Path<String>,
+    Json(payload): Json<OwnerRequest>,
+) -> Result<(StatusCode, Json<OwnerResponse>), ApiError> {
+    require_package_owner(&state.client, &caller, &name).await?;
+    let user = find_user_by_username(&state.client, &payload.username).await?;
+
+    let owner = add_maintainer(&state.client, &name, &user.id).await?;
+    Ok((StatusCode::CREATED, Json(owner_response(owner)?)))
+}
+
+async fn delete_owner(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, username)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let user = find_user_by_username(&state.client, &username).await?;
+    remove_owner(&state.client, &caller, &name, &user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_transfers(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<TransferResponse>>, ApiError> {
+    let transfers = list_transfers(&state.client, &caller.user_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    transfers
+        .into_iter()
+        .map(transfer_response)
+        .collect::<Result<Vec<_>, _>>()
+        .map(Json)
+}
+
+async fn create_transfer(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<TransferRequest>,
+) -> Result<(StatusCode, Json<TransferResponse>), ApiError> {
+    let recipient = find_user_by_username(&state.client, &payload.username).await?;
+    let transfer = request_transfer(&state.client, &caller, &payload.package, &recipient.id).await?;
+    Ok((StatusCode::CREATED, Json(transfer_response(transfer)?)))
+}
+
+async fn accept(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<OwnerResponse>>, ApiError> {
+    let name = accept_transfer(&state.client, &caller, &id).await?;
+    get_owners(State(state), Path(name)).await
+}
+
+async fn delete_transfer(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    cancel_transfer(&state.client, &caller, &id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn owner_response(owner: package_owner::Data) -> Result<OwnerResponse, ApiError> {
+    let user = owner.user().map_err(ApiError::internal)?;
+    Ok(OwnerResponse {
+        user_id: owner.user_id.clone(),
+        username: user.username.clone(),
+        role: format!("{:?}", owner.role),
+        created_at: owner.created_at.into(),
+    })
+}
+
+fn transfer_response(transfer: ownership_transfer::Data) -> Result<TransferResponse, ApiError> {
+    Ok(TransferResponse {
+        id: transfer.id.clone(),
+        package: transfer.package_name.clone(),
+        from: transfer.from().map_err(ApiError::internal)?.username.clone(),
+        to: transfer.to().map_err(ApiError::internal)?.username.clone(),
+        created_at: transfer.created_at.into(),
+        expires_at: transfer_expires_at(&transfer),
+    })
+}
@@ -1,245 +1,804 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post, put, delete},
-    Router,
+    extract::{DefaultBodyLimit, Multipart, OriginalUri, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{get, put},
+    Extension, Router,
 };
+use axum_extra::extract::Query;
+use chrono::Utc;
+use common::Version;
+use prisma_client_rust::prisma_errors::query_engine::UniqueKeyViolation;
 use serde::Deserialize;
-use std::collections::HashMap;
 use crate::{
+    config::{AppState, Settings},
     db::PrismaClient,
-    models::{PackageRequest, PackageResponse},
-    prisma::{package, tag, user, package_dependency, package_tag, dependency_type},
+    errors::ApiError,
+    models::{
+        PackageDetailResponse, PackageMetadataRequest, PackageRequest, PackageResponse, TagResponse,
+    },
+    prisma::{package, package_tag, package_version, tag, user},
+    services::{
+        debian_services::{read_deb_metadata, DebMetadata},
+        packages_services::{
+            dependency_specs, discard_artifact, latest_version_fetch, list_packages, lock_blob, lock_package_name,
+            release_blob, replace_dependencies, request_dependency_specs, serve_artifact, sort_newest_first, stage_upload,
+            tag_responses, tags_fetch, validate_identity, version_response, StagedArtifact,
+            PACKAGE_SORTS,
+        },
+        auth_services::Caller,
+        org_services::split_scope,
+        repository_services::strip_epoch,
+        ownership_services::{
+            authorize_publish, claim_name, not_maintainer, require_maintainer, PublishRight,
+        },
+        tag_services::{add_tag, find_tag, normalize_tag, normalize_tags, remove_tag, replace_tags},
+    },
+    storage::blob_key,
+    utils::pagination::{PageQuery, Paginated},
 };
 
 #[derive(Deserialize)]
 pub struct PackageQuery {
     pub search: Option<String>,
-    pub tag: Option<String>,
+    /// Repeatable, e.g. `?tag=web&tag=proxy`; commas also separate tags.
+    #[serde(default)]
+    pub tag: Vec<String>,
+    /// `all` (the default) keeps packages carrying every tag, `any` those
+    /// carrying at least one.
+    #[serde(rename = "match")]
+    pub tag_match: Option<String>,
     pub maintainer: Option<String>,
-    pub limit: Option<i64>,
-    pub offset: Option<i64>,
 }
 
-pub fn package_routes() -> Router<PrismaClient> {
+pub fn package_routes(settings: &Settings) -> Router<AppState> {
     Router::new()
-        .route("/", get(get_packages).post(create_package))
-        .route("/:id", get(get_package).put(update_package).delete(delete_package))
-        .route("/:id/download", get(download_package))
-        .route("/search/:query", get(search_packages))
+        .route(
+            "/",
+            get(get_packages)
+                .post(create_package)
+                .layer(DefaultBodyLimit::max(settings.max_upload_size)),
+        )
+        .route("/:name", get(get_package).put(update_package))
+        .route("/:name/versions", get(get_versions))
+        .route("/:name/tags/:tag", put(put_package_tag).delete(delete_package_tag))
+        .route(
+            "/:name/:version",
+            get(get_version).put(update_version).delete(delete_version),
+        )
+        .route("/:name/:version/download", get(download_package))
+        .route("/:name/:version/signature", get(get_signature))
 }
 
+/// Lists packages, each with its newest version, a page at a time.
 async fn get_packages(
-    State(client): State<PrismaClient>,
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
     Query(params): Query<PackageQuery>,
-) -> Result<Json<Vec<PackageResponse>>, StatusCode> {
-    let mut query = client.package().find_many();
+    Query(page): Query<PageQuery>,
+) -> Result<Paginated<PackageResponse>, ApiError> {
+    let page = page.resolve(PACKAGE_SORTS, state.settings.max_page_size)?;
+
+    let mut filters = vec![];
 
     if let Some(search) = params.search {
-        query = query.with(package::name::contains(search));
+        filters.push(package::name::contains(search));
     }
 
     if let Some(maintainer) = params.maintainer {
-        query = query.with(package::maintainer::equals(maintainer));
+        filters.push(package::versions::some(vec![
+            package_version::maintainer::equals(maintainer),
+        ]));
     }
 
-    let packages = query
-        .with(package::author::fetch())
-        .with(package::dependencies::fetch(vec![]))
-        .with(package::tags::fetch(vec![]))
-        .exec()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let package_responses: Vec<PackageResponse> = packages
-        .into_iter()
-        .map(|p| PackageResponse {
-            id: p.id,
-            name: p.name,
-            version: p.version,
-            description: p.description,
-            maintainer: p.maintainer,
-            architecture: p.architecture,
-            size: p.size,
-            checksum: p.checksum,
-            created_at: p.created_at.into(),
-            updated_at: p.updated_at.into(),
-            author: p.author_id,
-            dependencies: vec![],
-            tags: vec![],
-        })
-        .collect();
+    let tags = requested_tags(&params.tag);
+    if match_any(params.tag_match.as_deref())? {
+        if !tags.is_empty() {
+            filters.push(package::tags::some(vec![package_tag::tag::is(vec![
+                tag::name::in_vec(tags),
+            ])]));
+        }
+    } else {
+        for tag in tags {
+            filters.push(package::tags::some(vec![package_tag::tag::is(vec![
+                tag::name::equals(tag),
+            ])]));
+        }
+    }
 
-    Ok(Json(package_responses))
+    list_packages(&state.client, filters, page, &uri).await
 }
 
 async fn get_package(
-    State(client): State<PrismaClient>,
-    Path(id): Path<String>,
-) -> Result<Json<PackageResponse>, StatusCode> {
-    let package = client
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PackageDetailResponse>, ApiError> {
+    let package = state
+        .client
         .package()
-        .find_unique(package::id::equals(id))
-        .with(package::author::fetch())
-        .with(package::dependencies::fetch(vec![]))
-        .with(package::tags::fetch(vec![]))
+        .find_unique(package::name::equals(name.clone()))
+        .with(latest_version_fetch())
+        .with(tags_fetch())
         .exec()
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    match package {
-        Some(p) => Ok(Json(PackageResponse {
-            id: p.id,
-            name: p.name,
-            version: p.version,
-            description: p.description,
-            maintainer: p.maintainer,
-            architecture: p.architecture,
-            size: p.size,
-            checksum: p.checksum,
-            created_at: p.created_at.into(),
-            updated_at: p.updated_at.into(),
-            author: p.author_id,
-            dependencies: vec![],
-            tags: vec![],
-        })),
-        None => Err(StatusCode::NOT_FOUND),
-    }
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("Package {} does not exist", name)))?;
+
+    let mut versions = package.versions.clone().unwrap_or_default();
+    sort_newest_first(&mut versions);
+
+    Ok(Json(PackageDetailResponse {
+        id: package.id.clone(),
+        name: package.name.clone(),
+        description: package.description.clone(),
+        homepage: package.homepage.clone(),
+        repository: package.repository.clone(),
+        deprecated: package.deprecated.clone(),
+        strict_versioning: package.strict_versioning,
+        downloads: package.downloads,
+        author: package.author_id.clone(),
+        created_at: package.created_at.into(),
+        updated_at: package.updated_at.into(),
+        tags: tag_responses(&package),
+        versions: versions.iter().map(|v| v.version.clone()).collect(),
+        latest: versions
+            .into_iter()
+            .next()
+            .map(|version| version_response(&package, version)),
+    }))
+}
+
+/// All versions of a package, newest first by Debian version ordering.
+async fn get_versions(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<PackageResponse>>, ApiError> {
+    let package = find_package(&state, &name).await?;
+
+    let mut versions = state
+        .client
+        .package_version()
+        .find_many(vec![package_version::package_id::equals(package.id)])
+        .with(package_version::dependencies::fetch(vec![]))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    sort_newest_first(&mut versions);
+
+    Ok(Json(
+        versions
+            .into_iter()
+            .map(|version| version_response(&package, version))
+            .collect(),
+    ))
+}
+
+async fn get_version(
+    State(state): State<AppState>,
+    Path((name, version)): Path<(String, String)>,
+) -> Result<Json<PackageResponse>, ApiError> {
+    let (package, version) = find_version(&state, &name, &version).await?;
+    Ok(Json(version_response(&package, version)))
 }
 
 async fn create_package(
-    State(client): State<PrismaClient>,
-    Json(payload): Json<PackageRequest>,
-) -> Result<Json<PackageResponse>, StatusCode> {
-    let checksum = format!("{:x}", sha2::Sha256::digest(&payload.name));
-    
-    let package = client
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    multipart: Multipart,
+) -> Result<Json<PackageResponse>, ApiError> {
+    let (staged, metadata, scope) = read_upload(&state.settings, multipart).await?;
+
+    match publish_artifact(&state, &caller, &staged, metadata, scope).await {
+        Ok(response) => {
+            state.repository.schedule_rebuild(&state);
+            Ok(Json(response))
+        }
+        Err(err) => {
+            discard_artifact(&staged.temp_path).await;
+            Err(err)
+        }
+    }
+}
+
+/// Reads the `file` and optional `metadata` and `scope` parts of an upload,
+/// staging the file on disk as it arrives.
+async fn read_upload(
+    settings: &Settings,
+    mut multipart: Multipart,
+) -> Result<(StagedArtifact, Option<PackageRequest>, Option<String>), ApiError> {
+    let mut staged: Option<StagedArtifact> = None;
+    let mut metadata: Option<PackageRequest> = None;
+    let mut scope: Option<String> = None;
+
+    let result: Result<(), ApiError> = async {
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|err| ApiError::bad_request("invalid_upload", err.to_string()))?
+        {
+            match field.name() {
+                Some("file") => {
+                    if let Some(previous) = staged.take() {
+                        discard_artifact(&previous.temp_path).await;
+                    }
+                    staged = Some(stage_upload(settings, field).await?);
+                }
+                Some("metadata") => {
+                    let text = field
+                        .text()
+                        .await
+                        .map_err(|err| ApiError::bad_request("invalid_upload", err.to_string()))?;
+                    metadata = Some(
+                        serde_json::from_str(&text)
+                            .map_err(|err| ApiError::bad_request("invalid_metadata", err.to_string()))?,
+                    );
+                }
+                Some("scope") => {
+                    let text = field
+                        .text()
+                        .await
+                        .map_err(|err| ApiError::bad_request("invalid_upload", err.to_string()))?;
+                    scope = Some(text.trim().trim_start_matches('@').to_string())
+                        .filter(|scope| !scope.is_empty());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(err) = result {
+        if let Some(staged) = staged {
+            discard_artifact(&staged.temp_path).await;
+        }
+        return Err(err);
+    }
+
+    match staged {
+        Some(staged) => Ok((staged, metadata, scope)),
+        None => Err(ApiError::bad_request(
+            "missing_file",
+            "The upload must contain a `file` part with the .deb archive",
+        )),
+    }
+}
+
+async fn publish_artifact(
+    state: &AppState,
+    caller: &Caller,
+    staged: &StagedArtifact,
+    metadata: Option<PackageRequest>,
+    scope: Option<String>,
+) -> Result<PackageResponse, ApiError> {
+    let temp_path = staged.temp_path.clone();
+    let mut deb = tokio::task::spawn_blocking(move || read_deb_metadata(&temp_path))
+        .await
+        .map_err(ApiError::internal)?
+        .map_err(|err| ApiError::unprocessable("invalid_package", format!("{:#}", err)))?;
+
+    // The control file only knows the Debian name; the scope puts it in an
+    // organization's namespace.
+    if let Some(scope) = scope {
+        deb.name = format!("@{}/{}", scope, deb.name);
+    }
+
+    validate_identity(&deb.name, &deb.version, &deb.architecture)?;
+    caller.require_package(&deb.name)?;
+
+    if let Some(request) = &metadata {
+        check_request_matches(request, &deb)?;
+    }
+
+    // Dependencies submitted with the metadata take the place of those in
+    // the control file, which lets a maintainer correct them for the index.
+    let dependencies = match metadata.as_ref().and_then(|m| m.dependencies.as_deref()) {
+        Some(requests) => request_dependency_specs(requests)?,
+        None => dependency_specs(&deb),
+    };
+    let tags = match metadata.as_ref().and_then(|m| m.tags.as_deref()) {
+        Some(tags) => Some(normalize_tags(tags)?),
+        None => None,
+    };
+
+    let right = authorize_publish(&state.client, caller, &deb.name).await?;
+
+    let existing = state
+        .client
         .package()
-        .create(
-            payload.name.clone(),
-            payload.version.clone(),
-            payload.maintainer,
-            payload.architecture,
-            payload.size,
-            checksum,
-            payload.description,
-            format!("/packages/{}/{}-{}.deb", payload.name, payload.name, payload.version),
-            user::id::equals("default_user".to_string()),
-        )
+        .find_unique(package::name::equals(deb.name.clone()))
+        .with(package::versions::fetch(vec![]))
         .exec()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(PackageResponse {
-        id: package.id,
-        name: package.name,
-        version: package.version,
-        description: package.description,
-        maintainer: package.maintainer,
-        architecture: package.architecture,
-        size: package.size,
-        checksum: package.checksum,
-        created_at: package.created_at.into(),
-        updated_at: package.updated_at.into(),
-        author: package.author_id,
-        dependencies: vec![],
-        tags: vec![],
-    }))
+        .map_err(ApiError::internal)?;
+
+    if let Some(existing) = existing {
+        check_version_order(&existing, &deb.version)?;
+    }
+
+    let signature = sign_artifact(state, &staged.temp_path).await?;
+
+    state
+        .storage
+        .put(&staged.checksum, &staged.temp_path)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let size = staged.size;
+    let checksum = staged.checksum.clone();
+    let stored_path = blob_key(&staged.checksum).map_err(ApiError::internal)?;
+    let author_id = caller.user_id.clone();
+    let is_admin = caller.acts_as_admin();
+    let (name, version_name) = (deb.name.clone(), deb.version.clone());
+
+    let storage = state.storage.clone();
+    let created = state
+        .client
+        ._transaction()
+        .run(|tx| async move {
+            // A release of the same blob may have deleted it since it was
+            // stored; once locked, none can until this version exists.
+            lock_blob(&tx, &checksum).await?;
+            if !storage.exists(&checksum).await.map_err(ApiError::internal)? {
+                return Err(ApiError::conflict(
+                    "artifact_released",
+                    "A concurrent deletion removed the uploaded artifact; publish it again",
+                ));
+            }
+
+            // Concurrent publishes of the name wait here, so each sees what
+            // the others created.
+            lock_package_name(&tx, &deb.name).await?;
+
+            // Another first publish may have claimed the name since it was
+            // authorized.
+            if right == PublishRight::Claim && !claim_name(&tx, &deb.name, &author_id).await? && !is_admin {
+                return Err(not_maintainer(&deb.name));
+            }
+            check_pool_file(&tx, &deb).await?;
+
+            // The package keeps the description of its newest version.
+            let package = tx
+                .package()
+                .upsert(
+                    package::name::equals(deb.name.clone()),
+                    package::create(
+                        deb.name.clone(),
+                        user::id::equals(author_id.clone()),
+                        vec![package::description::set(Some(deb.description.clone()))],
+                    ),
+                    vec![package::description::set(Some(deb.description.clone()))],
+                )
+                .exec()
+                .await?;
+
+            let version = tx
+                .package_version()
+                .create(
+                    deb.version.clone(),
+                    deb.maintainer,
+                    deb.architecture,
+                    size,
+                    checksum,
+                    stored_path,
+                    user::id::equals(author_id),
+                    package::id::equals(package.id),
+                    vec![
+                        package_version::description::set(Some(deb.description)),
+                        package_version::section::set(deb.section),
+                        package_version::priority::set(deb.priority),
+                        package_version::installed_size::set(deb.installed_size),
+                        package_version::control::set(Some(deb.control.to_string())),
+                        package_version::signature::set(signature),
+                    ],
+                )
+                .exec()
+                .await
+                .map_err(|err| {
+                    // A concurrent publish of the same version got in
+                    // after the check above.
+                    if err.is_prisma_error::<UniqueKeyViolation>() {
+                        version_exists(&deb.name, &deb.version)
+                    } else {
+                        err.into()
+                    }
+                })?;
+
+            replace_dependencies(&tx, &version.id, dependencies).await?;
+
+            if let Some(tags) = tags {
+                replace_tags(&tx, &package.id, &tags).await?;
+            }
+
+            Ok::<_, ApiError>(())
+        })
+        .await;
+
+    if let Err(err) = created {
+        release_blob(state, &staged.checksum).await?;
+        return Err(err);
+    }
+
+    let (package, version) = find_version(state, &name, &version_name).await?;
+    Ok(version_response(&package, version))
+}
+
+/// A detached signature of the staged artifact, when the registry signs.
+async fn sign_artifact(state: &AppState, path: &std::path::Path) -> Result<Option<String>, ApiError> {
+    let Some(signer) = &state.signer else {
+        return Ok(None);
+    };
+
+    let signature = signer
+        .detach_sign_file(path, Utc::now())
+        .await
+        .map_err(ApiError::internal)?;
+
+    String::from_utf8(signature).map(Some).map_err(ApiError::internal)
+}
+
+/// Rejects a version that has already been published and, when the package
+/// has strict versioning, one that is not newer than its latest version.
+fn check_version_order(package: &package::Data, version: &str) -> Result<(), ApiError> {
+    let versions = package.versions.as_deref().unwrap_or_default();
+    if versions.iter().any(|v| v.version == version) {
+        return Err(version_exists(&package.name, version));
+    }
+
+    if !package.strict_versioning {
+        return Ok(());
+    }
+
+    let new = version
+        .parse::<Version>()
+        .map_err(|err| ApiError::bad_request("invalid_version", err.to_string()))?;
+    let latest = versions
+        .iter()
+        .filter_map(|v| v.version.parse::<Version>().ok())
+        .max();
+    match latest {
+        Some(latest) if new <= latest => Err(ApiError::conflict(
+            "version_not_newer",
+            format!(
+                "{} requires new versions to be newer than {}, the latest published",
+                package.name, latest
+            ),
+        )),
+        _ => Ok(()),
+    }
 }
 
+/// Rejects a version whose file in the apt pool, which leaves out the
+/// epoch, would be that of a published version differing only in its epoch.
+async fn check_pool_file(client: &PrismaClient, deb: &DebMetadata) -> Result<(), ApiError> {
+    let versions = client
+        .package_version()
+        .find_many(vec![
+            package_version::package::is(vec![package::name::equals(deb.name.clone())]),
+            package_version::architecture::equals(deb.architecture.clone()),
+        ])
+        .exec()
+        .await?;
+
+    let clashing = versions.iter().find(|v| {
+        v.version != deb.version && strip_epoch(&v.version) == strip_epoch(&deb.version)
+    });
+    match clashing {
+        Some(clashing) => Err(ApiError::conflict(
+            "pool_file_taken",
+            format!(
+                "{} {} would have the same file name as {}, which differs only in its epoch",
+                deb.name, deb.version, clashing.version
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn version_exists(name: &str, version: &str) -> ApiError {
+    ApiError::conflict(
+        "version_exists",
+        format!("{} {} has already been published", name, version),
+    )
+}
+
+/// Rejects uploads whose accompanying metadata names a different package
+/// than the control file inside the archive.
+fn check_request_matches(request: &PackageRequest, deb: &DebMetadata) -> Result<(), ApiError> {
+    let pairs = [
+        ("name", &request.name, &deb.name),
+        ("version", &request.version, &deb.version),
+        ("architecture", &request.architecture, &deb.architecture),
+    ];
+
+    for (field, requested, actual) in pairs {
+        if requested != actual {
+            return Err(ApiError::unprocessable(
+                "metadata_mismatch",
+                format!(
+                    "The request {} '{}' does not match the control file {} '{}'",
+                    field, requested, field, actual
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Edits the name-level data of a package.
 async fn update_package(
-    State(client): State<PrismaClient>,
-    Path(id): Path<String>,
-    Json(payload): Json<PackageRequest>,
-) -> Result<Json<PackageResponse>, StatusCode> {
-    let package = client
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(name): Path<String>,
+    Json(payload): Json<PackageMetadataRequest>,
+) -> Result<Json<PackageDetailResponse>, ApiError> {
+    caller.require_package(&name)?;
+    find_package(&state, &name).await?;
+    require_maintainer(&state.client, &caller, &name).await?;
+
+    let clear_empty = |value: String| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+    let mut updates = vec![];
+    if let Some(description) = payload.description {
+        updates.push(package::description::set(clear_empty(description)));
+    }
+    if let Some(homepage) = payload.homepage {
+        updates.push(package::homepage::set(clear_empty(homepage)));
+    }
+    if let Some(repository) = payload.repository {
+        updates.push(package::repository::set(clear_empty(repository)));
+    }
+    if let Some(deprecated) = payload.deprecated {
+        updates.push(package::deprecated::set(clear_empty(deprecated)));
+    }
+    if let Some(strict_versioning) = payload.strict_versioning {
+        updates.push(package::strict_versioning::set(strict_versioning));
+    }
+
+    state
+        .client
         .package()
-        .update(
-            package::id::equals(id),
-            vec![
-                package::name::set(payload.name),
-                package::version::set(payload.version),
-                package::description::set(payload.description),
-                package::maintainer::set(payload.maintainer),
-                package::architecture::set(payload.architecture),
-                package::size::set(payload.size),
-            ],
-        )
+        .update(package::name::equals(name.clone()), updates)
         .exec()
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    Ok(Json(PackageResponse {
-        id: package.id,
-        name: package.name,
-        version: package.version,
-        description: package.description,
-        maintainer: package.maintainer,
-        architecture: package.architecture,
-        size: package.size,
-        checksum: package.checksum,
-        created_at: package.created_at.into(),
-        updated_at: package.updated_at.into(),
-        author: package.author_id,
-        dependencies: vec![],
-        tags: vec![],
-    }))
+        .map_err(ApiError::internal)?;
+
+    get_package(State(state), Path(name)).await
 }
 
-async fn delete_package(
-    State(client): State<PrismaClient>,
-    Path(id): Path<String>,
-) -> Result<StatusCode, StatusCode> {
-    client
-        .package()
-        .delete(package::id::equals(id))
-        .exec()
+/// Edits the metadata of one version. The name, version and architecture
+/// are fixed by the published archive and cannot change. Dependencies and
+/// tags are replaced when given and left alone when omitted.
+async fn update_version(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, version)): Path<(String, String)>,
+    Json(payload): Json<PackageRequest>,
+) -> Result<Json<PackageResponse>, ApiError> {
+    caller.require_package(&name)?;
+    let (package, existing) = find_version(&state, &name, &version).await?;
+    require_maintainer(&state.client, &caller, &name).await?;
+
+    let pairs = [
+        ("name", &payload.name, &package.name),
+        ("version", &payload.version, &existing.version),
+        ("architecture", &payload.architecture, &existing.architecture),
+    ];
+    for (field, requested, actual) in pairs {
+        if requested != actual {
+            return Err(ApiError::unprocessable(
+                "immutable_field",
+                format!("The {} of a published version cannot be changed", field),
+            ));
+        }
+    }
+
+    let dependencies = match payload.dependencies.as_deref() {
+        Some(requests) => Some(request_dependency_specs(requests)?),
+        None => None,
+    };
+    let tags = match payload.tags.as_deref() {
+        Some(tags) => Some(normalize_tags(tags)?),
+        None => None,
+    };
+
+    let package_id = package.id.clone();
+    state
+        .client
+        ._transaction()
+        .run(|tx| async move {
+            tx.package_version()
+                .update(
+                    package_version::id::equals(existing.id.clone()),
+                    vec![
+                        package_version::description::set(payload.description),
+                        package_version::maintainer::set(payload.maintainer),
+                    ],
+                )
+                .exec()
+                .await?;
+
+            if let Some(dependencies) = dependencies {
+                replace_dependencies(&tx, &existing.id, dependencies).await?;
+            }
+            if let Some(tags) = tags {
+                replace_tags(&tx, &package_id, &tags).await?;
+            }
+
+            Ok::<_, prisma_client_rust::QueryError>(())
+        })
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+        .map_err(ApiError::internal)?;
+
+    state.repository.schedule_rebuild(&state);
+
+    let (package, updated) = find_version(&state, &name, &version).await?;
+    Ok(Json(version_response(&package, updated)))
+}
+
+/// Deletes one version. The package row goes with its last version, but
+/// its owners stay recorded so the name cannot be taken over.
+async fn delete_version(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, version)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    caller.require_package(&name)?;
+    let (package, existing) = find_version(&state, &name, &version).await?;
+    require_maintainer(&state.client, &caller, &name).await?;
+
+    let package_id = package.id.clone();
+    let deleted = state
+        .client
+        ._transaction()
+        .run(|tx| async move {
+            let deleted = tx
+                .package_version()
+                .delete(package_version::id::equals(existing.id))
+                .exec()
+                .await?;
+
+            let remaining = tx
+                .package_version()
+                .count(vec![package_version::package_id::equals(package_id.clone())])
+                .exec()
+                .await?;
+            if remaining == 0 {
+                tx.package()
+                    .delete(package::id::equals(package_id))
+                    .exec()
+                    .await?;
+            }
+
+            Ok::<_, prisma_client_rust::QueryError>(deleted)
+        })
+        .await
+        .map_err(ApiError::internal)?;
+
+    release_blob(&state, &deleted.checksum).await?;
+    state.repository.schedule_rebuild(&state);
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Tags a package, creating the tag if needed. Returns the package's tags.
+async fn put_package_tag(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, tag)): Path<(String, String)>,
+) -> Result<Json<Vec<TagResponse>>, ApiError> {
+    caller.require_package(&name)?;
+    let package = find_package(&state, &name).await?;
+    require_maintainer(&state.client, &caller, &name).await?;
+
+    let tag = normalize_tag(&tag)?;
+    add_tag(&state.client, &package.id, &tag)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let package = find_package(&state, &name).await?;
+    Ok(Json(tag_responses(&package)))
+}
+
+async fn delete_package_tag(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, tag)): Path<(String, String)>,
+) -> Result<Json<Vec<TagResponse>>, ApiError> {
+    caller.require_package(&name)?;
+    let package = find_package(&state, &name).await?;
+    require_maintainer(&state.client, &caller, &name).await?;
+
+    let tag = find_tag(&state.client, &tag).await?;
+    remove_tag(&state.client, &package.id, &tag.id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let package = find_package(&state, &name).await?;
+    Ok(Json(tag_responses(&package)))
+}
+
 async fn download_package(
-    State(_client): State<PrismaClient>,
-    Path(id): Path<String>,
-) -> Result<String, StatusCode> {
-    Ok(format!("Download package with id: {}", id))
+    State(state): State<AppState>,
+    Path((name, version)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let (package, version) = find_version(&state, &name, &version).await?;
+    let (_, debian_name) = split_scope(&package.name);
+    serve_artifact(&state, debian_name, &version, &headers).await
 }
 
-async fn search_packages(
-    State(client): State<PrismaClient>,
-    Path(query): Path<String>,
-) -> Result<Json<Vec<PackageResponse>>, StatusCode> {
-    let packages = client
+/// The armored detached signature of a version's artifact, made with the
+/// key that signs the apt repository (`/apt/key.asc`).
+async fn get_signature(
+    State(state): State<AppState>,
+    Path((name, version)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    let (_, version) = find_version(&state, &name, &version).await?;
+    let signature = version
+        .signature
+        .ok_or_else(|| ApiError::not_found(format!("{} {} is not signed", name, version.version)))?;
+
+    Ok(([(header::CONTENT_TYPE, "application/pgp-signature")], signature).into_response())
+}
+
+async fn find_package(state: &AppState, name: &str) -> Result<package::Data, ApiError> {
+    state
+        .client
         .package()
-        .find_many(vec![
-            package::name::contains(query.clone()),
-            package::description::contains(query),
-        ])
+        .find_unique(package::name::equals(name.to_string()))
+        .with(tags_fetch())
         .exec()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let package_responses: Vec<PackageResponse> = packages
-        .into_iter()
-        .map(|p| PackageResponse {
-            id: p.id,
-            name: p.name,
-            version: p.version,
-            description: p.description,
-            maintainer: p.maintainer,
-            architecture: p.architecture,
-            size: p.size,
-            checksum: p.checksum,
-            created_at: p.created_at.into(),
-            updated_at: p.updated_at.into(),
-            author: p.author_id,
-            dependencies: vec![],
-            tags: vec![],
-        })
-        .collect();
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("Package {} does not exist", name)))
+}
 
-    Ok(Json(package_responses))
-}
\ No newline at end of file
+async fn find_version(
+    state: &AppState,
+    name: &str,
+    version: &str,
+) -> Result<(package::Data, package_version::Data), ApiError> {
+    let package = find_package(state, name).await?;
+
+    let version = state
+        .client
+        .package_version()
+        .find_unique(package_version::package_id_version(
+            package.id.clone(),
+            version.to_string(),
+        ))
+        .with(package_version::dependencies::fetch(vec![]))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("{} {} does not exist", name, version)))?;
+
+    Ok((package, version))
+}
+
+/// The tags a listing filters by: repeated `tag` parameters, each of which
+/// may also list several separated by commas.
+pub fn requested_tags(tags: &[String]) -> Vec<String> {
+    let mut requested: Vec<String> = Vec::new();
+    for tag in tags.iter().flat_map(|tag| tag.split(',')) {
+        let tag = tag.trim().to_ascii_lowercase();
+        if !tag.is_empty() && !requested.contains(&tag) {
+            requested.push(tag);
+        }
+    }
+    requested
+}
+
+/// Whether `match` asks for packages with any of the tags rather than all.
+pub fn match_any(tag_match: Option<&str>) -> Result<bool, ApiError> {
+    match tag_match.unwrap_or("all") {
+        "all" => Ok(false),
+        "any" => Ok(true),
+        other => Err(ApiError::bad_request(
+            "invalid_match",
+            format!("'{}' is not one of all or any", other),
+        )),
+    }
+}
@@ -0,0 +1,99 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    prisma::{package, package_version},
+    services::{
+        packages_services::serve_artifact,
+        repository_services::strip_epoch,
+    },
+};
+
+pub fn repository_routes() -> Router<AppState> {
+    Router::new()
+        .route("/key.asc", get(get_public_key))
+        .route("/dists/*path", get(get_index_file))
+        .route("/pool/*path", get(get_pool_file))
+}
+
+/// The armored public keys that currently sign the repository, for
+/// importing into apt's or cpkgs' trusted keys.
+async fn get_public_key(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let signer = state
+        .signer
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("The repository is not signed"))?;
+
+    let keys = signer
+        .public_keys(Utc::now())
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(([(header::CONTENT_TYPE, "application/pgp-keys")], keys).into_response())
+}
+
+async fn get_index_file(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+) -> Result<Response, ApiError> {
+    let index = state.repository.current();
+    let file = index
+        .get(&path)
+        .ok_or_else(|| ApiError::not_found(format!("dists/{} does not exist", path)))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, file.content_type)],
+        file.data.clone(),
+    )
+        .into_response())
+}
+
+async fn get_pool_file(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let not_found = || ApiError::not_found(format!("pool/{} does not exist", path));
+
+    let component = path.split('/').next().unwrap_or_default();
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    let (name, version, architecture) = file_name
+        .strip_suffix(".deb")
+        .and_then(|stem| {
+            let mut parts = stem.splitn(3, '_');
+            Some((parts.next()?, parts.next()?, parts.next()?))
+        })
+        .ok_or_else(not_found)?;
+
+    let debian_name = name;
+    let name = if component == state.settings.apt.component {
+        name.to_string()
+    } else {
+        format!("@{}/{}", component, name)
+    };
+
+    let candidates = state
+        .client
+        .package_version()
+        .find_many(vec![
+            package_version::package::is(vec![package::name::equals(name)]),
+            package_version::architecture::equals(architecture.to_string()),
+        ])
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    let package = candidates
+        .into_iter()
+        .find(|p| strip_epoch(&p.version) == version)
+        .ok_or_else(not_found)?;
+
+    serve_artifact(&state, debian_name, &package, &headers).await
+}
@@ -0,0 +1,51 @@
+use axum::{
+    extract::{OriginalUri, State},
+    routing::get,
+    Router,
+};
+use axum_extra::extract::Query;
+use serde::Deserialize;
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    models::SearchResultResponse,
+    routes::packages::{match_any, requested_tags},
+    services::search_services::{search_packages, SearchFilters, SEARCH_SORTS},
+    utils::pagination::{PageQuery, Paginated},
+};
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub architecture: Option<String>,
+    pub maintainer: Option<String>,
+    /// Only packages that depend on this one.
+    pub depends: Option<String>,
+    #[serde(default)]
+    pub tag: Vec<String>,
+    #[serde(rename = "match")]
+    pub tag_match: Option<String>,
+}
+
+pub fn search_routes() -> Router<AppState> {
+    Router::new().route("/", get(search))
+}
+
+/// Ranked full-text search over names, descriptions, tags and maintainers.
+async fn search(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<SearchQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Paginated<SearchResultResponse>, ApiError> {
+    let page = page.resolve(SEARCH_SORTS, state.settings.max_page_size)?;
+    let filters = SearchFilters {
+        architecture: params.architecture,
+        maintainer: params.maintainer,
+        depends: params.depends,
+        tags: requested_tags(&params.tag),
+        any_tag: match_any(params.tag_match.as_deref())?,
+    };
+
+    search_packages(&state.client, params.q, filters, page, &uri).await
+}
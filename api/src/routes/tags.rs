@@ -0,0 +1,179 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    models::{TagMergeRequest, TagRequest, TagSummaryResponse, TagUpdateRequest},
+    prisma::tag,
+    services::tag_services::{find_tag, merge_tags, normalize_tag, validate_color},
+};
+
+pub fn tag_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_tags).post(create_tag))
+        .route("/:tag", get(get_tag).put(update_tag).delete(delete_tag))
+        .route("/:tag/merge", post(merge_tag))
+}
+
+/// All tags with the number of packages carrying each, most used first.
+async fn get_tags(State(state): State<AppState>) -> Result<Json<Vec<TagSummaryResponse>>, ApiError> {
+    let tags = state
+        .client
+        .tag()
+        .find_many(vec![])
+        .with(tag::packages::fetch(vec![]))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    let mut summaries: Vec<TagSummaryResponse> = tags.into_iter().map(tag_summary).collect();
+    summaries.sort_by(|a, b| b.packages.cmp(&a.packages).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(Json(summaries))
+}
+
+async fn get_tag(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<TagSummaryResponse>, ApiError> {
+    let tag = state
+        .client
+        .tag()
+        .find_unique(tag::name::equals(name.to_ascii_lowercase()))
+        .with(tag::packages::fetch(vec![]))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("Tag {} does not exist", name)))?;
+
+    Ok(Json(tag_summary(tag)))
+}
+
+async fn create_tag(
+    State(state): State<AppState>,
+    Json(payload): Json<TagRequest>,
+) -> Result<(StatusCode, Json<TagSummaryResponse>), ApiError> {
+    let name = normalize_tag(&payload.name)?;
+    if let Some(color) = &payload.color {
+        validate_color(color)?;
+    }
+    ensure_available(&state, &name).await?;
+
+    let tag = state
+        .client
+        .tag()
+        .create(
+            name,
+            payload.color.into_iter().map(tag::color::set).collect(),
+        )
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok((StatusCode::CREATED, Json(tag_summary(tag))))
+}
+
+/// Renames or recolors a tag. Renaming onto an existing tag is refused;
+/// merge the two instead.
+async fn update_tag(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<TagUpdateRequest>,
+) -> Result<Json<TagSummaryResponse>, ApiError> {
+    let existing = find_tag(&state.client, &name).await?;
+
+    let mut updates = vec![];
+    if let Some(new_name) = payload.name {
+        let new_name = normalize_tag(&new_name)?;
+        if new_name != existing.name {
+            ensure_available(&state, &new_name).await?;
+            updates.push(tag::name::set(new_name));
+        }
+    }
+    if let Some(color) = payload.color {
+        validate_color(&color)?;
+        updates.push(tag::color::set(color));
+    }
+
+    let tag = state
+        .client
+        .tag()
+        .update(tag::id::equals(existing.id), updates)
+        .with(tag::packages::fetch(vec![]))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(Json(tag_summary(tag)))
+}
+
+async fn delete_tag(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let existing = find_tag(&state.client, &name).await?;
+
+    state
+        .client
+        .tag()
+        .delete(tag::id::equals(existing.id))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Merges a tag into another, e.g. `golang` into `go`.
+async fn merge_tag(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<TagMergeRequest>,
+) -> Result<Json<TagSummaryResponse>, ApiError> {
+    let from = find_tag(&state.client, &name).await?;
+    let into = find_tag(&state.client, &payload.into).await?;
+    if from.id == into.id {
+        return Err(ApiError::bad_request(
+            "invalid_merge",
+            "A tag cannot be merged into itself",
+        ));
+    }
+
+    let into_name = into.name.clone();
+    merge_tags(&state.client, from, into)
+        .await
+        .map_err(ApiError::internal)?;
+
+    get_tag(State(state), Path(into_name)).await
+}
+
+async fn ensure_available(state: &AppState, name: &str) -> Result<(), ApiError> {
+    let existing = state
+        .client
+        .tag()
+        .find_unique(tag::name::equals(name.to_string()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    match existing {
+        Some(_) => Err(ApiError::conflict(
+            "tag_exists",
+            format!("Tag {} already exists", name),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn tag_summary(tag: tag::Data) -> TagSummaryResponse {
+    TagSummaryResponse {
+        packages: tag.packages.as_ref().map_or(0, |packages| packages.len() as i64),
+        name: tag.name,
+        color: tag.color,
+    }
+}
@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{delete, get},
+    Extension, Router,
+};
+use chrono::{Duration, Utc};
+use crate::{
+    config::AppState,
+    errors::ApiError,
+    models::{ApiTokenRequest, ApiTokenResponse, CreatedApiTokenResponse},
+    prisma::api_token,
+    services::{
+        auth_services::{Caller, Scope},
+        token_services::{
+            create_api_token, list_api_tokens, parse_scopes, revoke_api_token,
+        },
+    },
+};
+
+pub fn token_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_tokens).post(create_token))
+        .route("/:id", delete(revoke_token))
+}
+
+async fn get_tokens(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<ApiTokenResponse>>, ApiError> {
+    let tokens = list_api_tokens(&state.client, &caller.user_id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(Json(tokens.into_iter().map(token_response).collect()))
+}
+
+async fn create_token(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<ApiTokenRequest>,
+) -> Result<Json<CreatedApiTokenResponse>, ApiError> {
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("invalid_name", "The token needs a name"));
+    }
+
+    let scopes = parse_scopes(&payload.scopes)?;
+    if scopes.contains(&Scope::UsersAdmin) && !caller.is_admin() {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "insufficient_role",
+            "Only administrators can create tokens with the 'users:admin' scope",
+        ));
+    }
+
+    let package_pattern = payload
+        .package_pattern
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty());
+
+    let expires_at = match payload.expires_in_days {
+        Some(days) if days <= 0 => {
+            return Err(ApiError::bad_request(
+                "invalid_expiry",
+                "expires_in_days must be positive",
+            ));
+        }
+        Some(days) => Some(Utc::now() + Duration::days(days)),
+        None => None,
+    };
+
+    let created = create_api_token(
+        &state.client,
+        &caller.user_id,
+        name,
+        &scopes,
+        package_pattern,
+        expires_at,
+    )
+    .await
+    .map_err(ApiError::internal)?;
+
+    Ok(Json(CreatedApiTokenResponse {
+        token: created.secret,
+        details: token_response(created.token),
+    }))
+}
+
+async fn revoke_token(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let revoked = revoke_api_token(&state.client, &caller.user_id, &id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    if !revoked {
+        return Err(ApiError::not_found(format!("Token {} does not exist", id)));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn token_response(token: api_token::Data) -> ApiTokenResponse {
+    ApiTokenResponse {
+        id: token.id,
+        name: token.name,
+        prefix: token.prefix,
+        scopes: token.scopes,
+        package_pattern: token.package_pattern,
+        expires_at: token.expires_at.map(Into::into),
+        last_used_at: token.last_used_at.map(Into::into),
+        created_at: token.created_at.into(),
+    }
+}
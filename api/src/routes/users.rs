@@ -1,17 +1,28 @@
 use axum::{
-    extract::{Path, State},
+    extract::{OriginalUri, Path, Query, State},
     http::StatusCode,
     response::Json,
     routing::{get, post, put, delete},
-    Router,
+    Extension, Router,
 };
+use prisma_client_rust::Direction;
 use crate::{
+    config::AppState,
     db::PrismaClient,
-    models::{UserRequest, UserResponse},
-    prisma::{user, user_role},
+    models::{PackageResponse, UserRequest, UserResponse},
+    prisma::{package, package_version, user, user_role, UserRole},
+    errors::ApiError,
+    services::{
+        auth_services::{hash_password, revoke_user_sessions, verify_password, Caller, Credential, PasswordCheck},
+        packages_services::{list_packages, PACKAGE_SORTS},
+        user_services::user_write_error,
+    },
+    utils::pagination::{PageQuery, Paginated, SortKey},
 };
 
-pub fn user_routes() -> Router<PrismaClient> {
+const USER_SORTS: &[SortKey] = &[("name", Direction::Asc), ("created", Direction::Desc)];
+
+pub fn user_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(get_users).post(create_user))
         .route("/:id", get(get_user).put(update_user).delete(delete_user))
@@ -19,68 +30,94 @@ pub fn user_routes() -> Router<PrismaClient> {
 }
 
 async fn get_users(
-    State(client): State<PrismaClient>,
-) -> Result<Json<Vec<UserResponse>>, StatusCode> {
-    let users = client
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(page): Query<PageQuery>,
+) -> Result<Paginated<UserResponse>, ApiError> {
+    let page = page.resolve(USER_SORTS, state.settings.max_page_size)?;
+
+    let total = state
+        .client
         .user()
-        .find_many()
+        .count(vec![])
         .exec()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(ApiError::internal)?;
+
+    let direction = page.direction;
+    let order = match page.sort {
+        "created" => user::created_at::order(direction),
+        _ => user::username::order(direction),
+    };
 
-    let user_responses: Vec<UserResponse> = users
-        .into_iter()
+    let mut query = state
+        .client
+        .user()
+        .find_many(vec![])
+        .order_by(order)
+        .order_by(user::id::order(direction))
+        .take(page.take());
+    if let Some(id) = page.cursor_id() {
+        query = query.cursor(user::id::equals(id)).skip(1);
+    }
+    let users = query.exec().await.map_err(ApiError::internal)?;
+
+    Ok(page
+        .finish(users, total, &uri, |u| u.id.clone())
         .map(|u| UserResponse {
             id: u.id,
             username: u.username,
             email: u.email,
             role: format!("{:?}", u.role),
             created_at: u.created_at.into(),
-        })
-        .collect();
-
-    Ok(Json(user_responses))
+        }))
 }
 
 async fn get_user(
     State(client): State<PrismaClient>,
+    Extension(caller): Extension<Caller>,
     Path(id): Path<String>,
-) -> Result<Json<UserResponse>, StatusCode> {
+) -> Result<Json<UserResponse>, ApiError> {
+    caller.require_self_or_admin(&id)?;
+
     let user = client
         .user()
-        .find_unique(user::id::equals(id))
+        .find_unique(user::id::equals(id.clone()))
         .exec()
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("User {} does not exist", id)))?;
 
-    match user {
-        Some(u) => Ok(Json(UserResponse {
-            id: u.id,
-            username: u.username,
-            email: u.email,
-            role: format!("{:?}", u.role),
-            created_at: u.created_at.into(),
-        })),
-        None => Err(StatusCode::NOT_FOUND),
-    }
+    Ok(Json(UserResponse {
+        id: user.id,
+        username: user.username,
+        email: user.email,
+        role: format!("{:?}", user.role),
+        created_at: user.created_at.into(),
+    }))
 }
 
 async fn create_user(
-    State(client): State<PrismaClient>,
+    State(state): State<AppState>,
     Json(payload): Json<UserRequest>,
-) -> Result<Json<UserResponse>, StatusCode> {
-    let user = client
+) -> Result<Json<UserResponse>, ApiError> {
+    let password = hash_password(&state.settings.passwords, payload.password)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let user = state
+        .client
         .user()
         .create(
             payload.username,
             payload.email,
-            payload.password,
-            user_role::USER,
+            password,
+            parse_role(payload.role.as_deref().unwrap_or("USER"))?,
             vec![],
         )
         .exec()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(user_write_error)?;
 
     Ok(Json(UserResponse {
         id: user.id,
@@ -92,23 +129,79 @@ async fn create_user(
 }
 
 async fn update_user(
-    State(client): State<PrismaClient>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
     Path(id): Path<String>,
     Json(payload): Json<UserRequest>,
-) -> Result<Json<UserResponse>, StatusCode> {
-    let user = client
+) -> Result<Json<UserResponse>, ApiError> {
+    caller.require_self_or_admin(&id)?;
+
+    let role = match payload.role.as_deref() {
+        Some(_) if !caller.acts_as_admin() => {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "role_change_forbidden",
+                "Only administrators can change roles",
+            ));
+        }
+        Some(role) => Some(user::role::set(parse_role(role)?)),
+        None => None,
+    };
+
+    let existing = state
+        .client
         .user()
-        .update(
-            user::id::equals(id),
-            vec![
-                user::username::set(payload.username),
-                user::email::set(payload.email),
-                user::password::set(payload.password),
-            ],
-        )
+        .find_unique(user::id::equals(id.clone()))
         .exec()
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("User {} does not exist", id)))?;
+
+    // The password is sent with every update; only a new one signs the
+    // user out elsewhere.
+    let password_changed = matches!(
+        verify_password(&state.settings.passwords, payload.password.clone(), existing.password)
+            .await
+            .map_err(ApiError::internal)?,
+        PasswordCheck::Invalid
+    );
+    // Users changing their own password stay signed in where they did it.
+    let keep = match &caller.credential {
+        Credential::Session { session_id } if caller.user_id == id => session_id.clone(),
+        _ => None,
+    };
+
+    let password = hash_password(&state.settings.passwords, payload.password)
+        .await
+        .map_err(ApiError::internal)?;
+
+    let mut updates = vec![
+        user::username::set(payload.username),
+        user::email::set(payload.email),
+        user::password::set(password),
+    ];
+    updates.extend(role);
+
+    let user = state
+        .client
+        ._transaction()
+        .run(|tx| async move {
+            let user = tx
+                .user()
+                .update(user::id::equals(id), updates)
+                .exec()
+                .await
+                .map_err(user_write_error)?;
+
+            if password_changed {
+                revoke_user_sessions(&tx, &user.id, keep.as_deref())
+                    .await
+                    .map_err(ApiError::internal)?;
+            }
+
+            Ok::<_, ApiError>(user)
+        })
+        .await?;
 
     Ok(Json(UserResponse {
         id: user.id,
@@ -121,47 +214,59 @@ async fn update_user(
 
 async fn delete_user(
     State(client): State<PrismaClient>,
+    Extension(caller): Extension<Caller>,
     Path(id): Path<String>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, ApiError> {
+    caller.require_self_or_admin(&id)?;
+
+    // Packages and versions keep whoever published them.
+    let packages = client
+        .package()
+        .count(vec![package::author_id::equals(id.clone())])
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    let versions = client
+        .package_version()
+        .count(vec![package_version::publisher_id::equals(id.clone())])
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if packages > 0 || versions > 0 {
+        return Err(ApiError::conflict(
+            "user_has_packages",
+            "The user has published packages; delete them before the user",
+        ));
+    }
+
     client
         .user()
         .delete(user::id::equals(id))
         .exec()
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+        .map_err(user_write_error)?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn get_user_packages(
-    State(client): State<PrismaClient>,
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
     Path(id): Path<String>,
-) -> Result<Json<Vec<crate::models::PackageResponse>>, StatusCode> {
-    let packages = client
-        .package()
-        .find_many(vec![crate::prisma::package::author_id::equals(id)])
-        .exec()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let package_responses: Vec<crate::models::PackageResponse> = packages
-        .into_iter()
-        .map(|p| crate::models::PackageResponse {
-            id: p.id,
-            name: p.name,
-            version: p.version,
-            description: p.description,
-            maintainer: p.maintainer,
-            architecture: p.architecture,
-            size: p.size,
-            checksum: p.checksum,
-            created_at: p.created_at.into(),
-            updated_at: p.updated_at.into(),
-            author: p.author_id,
-            dependencies: vec![],
-            tags: vec![],
-        })
-        .collect();
+    Query(page): Query<PageQuery>,
+) -> Result<Paginated<PackageResponse>, ApiError> {
+    let page = page.resolve(PACKAGE_SORTS, state.settings.max_page_size)?;
 
-    Ok(Json(package_responses))
-}
\ No newline at end of file
+    list_packages(&state.client, vec![package::author_id::equals(id)], page, &uri).await
+}
+
+fn parse_role(role: &str) -> Result<UserRole, ApiError> {
+    match role.to_ascii_uppercase().as_str() {
+        "ADMIN" => Ok(user_role::ADMIN),
+        "USER" => Ok(user_role::USER),
+        _ => Err(ApiError::bad_request(
+            "invalid_role",
+            format!("Unknown role '{}'", role),
+        )),
+    }
+}
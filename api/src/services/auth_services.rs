@@ -0,0 +1,540 @@
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Algorithm, Argon2, Params, Version,
+};
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{self as jwt, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{JwtAlgorithm, JwtSettings, PasswordSettings},
+    db::PrismaClient,
+    errors::ApiError,
+    prisma::{refresh_token, session, user},
+    utils::glob_match,
+};
+
+/// HMAC secrets shorter than the SHA-256 output weaken HS256.
+const MIN_SECRET_LEN: usize = 32;
+
+/// The outcome of checking a login password against the stored value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Invalid,
+    Valid,
+    /// Correct, but hashed with outdated parameters.
+    NeedsRehash,
+    /// Correct, but stored in plaintext from before passwords were hashed.
+    Legacy,
+}
+
+/// Hashes a password with Argon2id into a PHC string. Hashing is
+/// deliberately slow, so it runs on the blocking pool.
+pub async fn hash_password(settings: &PasswordSettings, password: String) -> Result<String> {
+    let settings = settings.clone();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        argon2(&settings)?
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| anyhow!("failed to hash password: {}", err))
+    })
+    .await?
+}
+
+pub async fn verify_password(
+    settings: &PasswordSettings,
+    password: String,
+    stored: String,
+) -> Result<PasswordCheck> {
+    let settings = settings.clone();
+    tokio::task::spawn_blocking(move || check_password(&settings, &password, &stored)).await?
+}
+
+fn check_password(settings: &PasswordSettings, password: &str, stored: &str) -> Result<PasswordCheck> {
+    if !stored.starts_with("$argon2") {
+        return Ok(if constant_time_eq(password.as_bytes(), stored.as_bytes()) {
+            PasswordCheck::Legacy
+        } else {
+            PasswordCheck::Invalid
+        });
+    }
+
+    let hash = PasswordHash::new(stored).map_err(|err| anyhow!("malformed password hash: {}", err))?;
+
+    // Verification uses the algorithm and parameters recorded in the hash.
+    if Argon2::default().verify_password(password.as_bytes(), &hash).is_err() {
+        return Ok(PasswordCheck::Invalid);
+    }
+
+    let current = hash.algorithm == Algorithm::Argon2id.ident()
+        && hash.version == Some(Version::V0x13.into())
+        && Params::try_from(&hash).is_ok_and(|params| {
+            params.m_cost() == settings.memory_kib
+                && params.t_cost() == settings.iterations
+                && params.p_cost() == settings.parallelism
+        });
+
+    Ok(if current {
+        PasswordCheck::Valid
+    } else {
+        PasswordCheck::NeedsRehash
+    })
+}
+
+fn argon2(settings: &PasswordSettings) -> Result<Argon2<'static>> {
+    let params = Params::new(settings.memory_kib, settings.iterations, settings.parallelism, None)
+        .map_err(|err| anyhow!("invalid Argon2 parameters: {}", err))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// Compares two byte strings in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The claims of an access token. `auth_middleware` inserts them into the
+/// request extensions once the token is verified.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user id.
+    pub sub: String,
+    pub role: String,
+    pub iss: String,
+    pub iat: i64,
+    pub exp: i64,
+    /// A unique id for this token.
+    pub jti: String,
+    /// The login session the token was issued for. Revoking the session
+    /// invalidates the token before it expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+/// A permission that can be granted to an API token. Session tokens carry
+/// every scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    PackagesPublish,
+    PackagesDelete,
+    UsersAdmin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [
+        Scope::PackagesPublish,
+        Scope::PackagesDelete,
+        Scope::UsersAdmin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::PackagesPublish => "packages:publish",
+            Scope::PackagesDelete => "packages:delete",
+            Scope::UsersAdmin => "users:admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+}
+
+/// How the caller of a request authenticated.
+#[derive(Debug, Clone)]
+pub enum Credential {
+    Session {
+        session_id: Option<String>,
+    },
+    ApiToken {
+        id: String,
+        scopes: Vec<Scope>,
+        package_pattern: Option<String>,
+    },
+}
+
+/// The authenticated caller of a request. `auth_middleware` inserts it into
+/// the request extensions.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub user_id: String,
+    pub role: String,
+    pub credential: Credential,
+}
+
+impl Caller {
+    pub fn from_claims(claims: Claims) -> Self {
+        Self {
+            user_id: claims.sub,
+            role: claims.role,
+            credential: Credential::Session {
+                session_id: claims.sid,
+            },
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role.eq_ignore_ascii_case("admin")
+    }
+
+    /// Administrators only get their extra rights through credentials that
+    /// grant `users:admin`, so an admin's narrowly scoped CI token stays
+    /// narrow.
+    pub fn acts_as_admin(&self) -> bool {
+        self.is_admin() && self.has_scope(Scope::UsersAdmin)
+    }
+
+    /// Fails with 403 unless the caller is `user_id` or acts as admin.
+    pub fn require_self_or_admin(&self, user_id: &str) -> Result<(), ApiError> {
+        if self.user_id == user_id || self.acts_as_admin() {
+            return Ok(());
+        }
+        Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "not_account_owner",
+            "You can only manage your own account",
+        ))
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        match &self.credential {
+            Credential::Session { .. } => true,
+            Credential::ApiToken { scopes, .. } => scopes.contains(&scope),
+        }
+    }
+
+    /// Fails with 403 unless the caller's credential grants `scope`.
+    pub fn require_scope(&self, scope: Scope) -> Result<(), ApiError> {
+        if self.has_scope(scope) {
+            return Ok(());
+        }
+        Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "insufficient_scope",
+            format!("This token does not grant the '{}' scope", scope.as_str()),
+        ))
+    }
+
+    /// Fails with 403 if the caller's API token is restricted to package
+    /// names that do not include `name`.
+    pub fn require_package(&self, name: &str) -> Result<(), ApiError> {
+        match &self.credential {
+            Credential::ApiToken {
+                package_pattern: Some(pattern),
+                ..
+            } if !glob_match(pattern, name) => Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "package_not_allowed",
+                format!("This token is restricted to packages matching '{}'", pattern),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Why an access token was refused, reported to the client in the
+/// `WWW-Authenticate` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    Expired,
+    BadSignature,
+    Malformed,
+    Revoked,
+    UnknownApiToken,
+}
+
+impl TokenError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            TokenError::Expired => "The access token expired",
+            TokenError::BadSignature => "The access token signature is invalid",
+            TokenError::Malformed => "The access token is malformed",
+            TokenError::Revoked => "The token or its session has been revoked",
+            TokenError::UnknownApiToken => "The API token is not valid",
+        }
+    }
+}
+
+/// Signs and verifies access tokens.
+pub struct TokenKeys {
+    algorithm: jwt::Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    issuer: String,
+    access_ttl: i64,
+    refresh_ttl: i64,
+}
+
+impl TokenKeys {
+    pub fn from_settings(settings: &JwtSettings) -> Result<Self> {
+        let (algorithm, encoding, decoding) = match settings.algorithm {
+            JwtAlgorithm::Hs256 => {
+                let secret = settings
+                    .secret
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("JWT_SECRET must be set when JWT_ALGORITHM is HS256"))?;
+                if secret.len() < MIN_SECRET_LEN {
+                    bail!("JWT_SECRET must be at least {} bytes long", MIN_SECRET_LEN);
+                }
+                (
+                    jwt::Algorithm::HS256,
+                    EncodingKey::from_secret(secret.as_bytes()),
+                    DecodingKey::from_secret(secret.as_bytes()),
+                )
+            }
+            JwtAlgorithm::EdDsa => {
+                let read = |path: Option<&std::path::PathBuf>, var: &str| -> Result<Vec<u8>> {
+                    let path = path.ok_or_else(|| anyhow!("{} must be set when JWT_ALGORITHM is EdDSA", var))?;
+                    std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+                };
+                let private_key = read(settings.private_key_path.as_ref(), "JWT_PRIVATE_KEY_PATH")?;
+                let public_key = read(settings.public_key_path.as_ref(), "JWT_PUBLIC_KEY_PATH")?;
+                (
+                    jwt::Algorithm::EdDSA,
+                    EncodingKey::from_ed_pem(&private_key).context("invalid Ed25519 private key")?,
+                    DecodingKey::from_ed_pem(&public_key).context("invalid Ed25519 public key")?,
+                )
+            }
+        };
+
+        Ok(Self {
+            algorithm,
+            encoding,
+            decoding,
+            issuer: settings.issuer.clone(),
+            access_ttl: settings.access_ttl,
+            refresh_ttl: settings.refresh_ttl,
+        })
+    }
+
+    pub fn issue(&self, user_id: &str, role: &str, session_id: Option<&str>) -> Result<String> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            role: role.to_string(),
+            iss: self.issuer.clone(),
+            iat: now,
+            exp: now + self.access_ttl,
+            jti: uuid::Uuid::new_v4().to_string(),
+            sid: session_id.map(str::to_string),
+        };
+
+        jwt::encode(&Header::new(self.algorithm), &claims, &self.encoding)
+            .context("failed to sign access token")
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Claims, TokenError> {
+        let mut validation = Validation::new(self.algorithm);
+        validation.leeway = 30;
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_required_spec_claims(&["exp", "iat", "iss", "sub"]);
+
+        jwt::decode::<Claims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|err| match err.kind() {
+                ErrorKind::ExpiredSignature => TokenError::Expired,
+                ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => TokenError::BadSignature,
+                _ => TokenError::Malformed,
+            })
+    }
+}
+
+/// The tokens handed to a client when a session starts or is refreshed.
+pub struct IssuedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+/// Starts a login session for `user` and issues its first token pair.
+pub async fn start_session(
+    client: &PrismaClient,
+    tokens: &TokenKeys,
+    user: &user::Data,
+) -> Result<IssuedTokens> {
+    let session = client
+        .session()
+        .create(user::id::equals(user.id.clone()), vec![])
+        .exec()
+        .await?;
+
+    issue_tokens(client, tokens, user, &session.id).await
+}
+
+/// Exchanges a refresh token for a new token pair. Each refresh token is
+/// single-use; presenting one a second time means it was copied, so the
+/// whole session is revoked.
+pub async fn refresh_session(
+    client: &PrismaClient,
+    tokens: &TokenKeys,
+    presented: &str,
+) -> Result<(IssuedTokens, user::Data), ApiError> {
+    let token = client
+        .refresh_token()
+        .find_unique(refresh_token::token_hash::equals(hash_token(presented)))
+        .with(refresh_token::session::fetch().with(session::user::fetch()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| invalid_refresh_token("The refresh token is not valid"))?;
+
+    let session = token.session().map_err(ApiError::internal)?;
+    if session.revoked_at.is_some() {
+        return Err(invalid_refresh_token("The session has been revoked"));
+    }
+
+    let now = Utc::now();
+    if token.used_at.is_some() {
+        return Err(revoke_reused(client, &session.id).await);
+    }
+    if token.expires_at < now {
+        return Err(invalid_refresh_token("The refresh token expired"));
+    }
+
+    // Only one request can claim the token, even if two arrive together.
+    let claimed = client
+        .refresh_token()
+        .update_many(
+            vec![
+                refresh_token::id::equals(token.id.clone()),
+                refresh_token::used_at::equals(None),
+            ],
+            vec![refresh_token::used_at::set(Some(now.into()))],
+        )
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if claimed == 0 {
+        return Err(revoke_reused(client, &session.id).await);
+    }
+
+    let user = session.user().map_err(ApiError::internal)?.clone();
+    let issued = issue_tokens(client, tokens, &user, &session.id)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok((issued, user))
+}
+
+/// Revokes the session a refresh token belongs to. Unknown tokens are
+/// ignored so that logging out twice is harmless.
+pub async fn end_session(client: &PrismaClient, presented: &str) -> Result<()> {
+    let token = client
+        .refresh_token()
+        .find_unique(refresh_token::token_hash::equals(hash_token(presented)))
+        .exec()
+        .await?;
+
+    if let Some(token) = token {
+        revoke_session(client, &token.session_id).await?;
+    }
+    Ok(())
+}
+
+pub async fn revoke_session(client: &PrismaClient, session_id: &str) -> Result<()> {
+    client
+        .session()
+        .update_many(
+            vec![
+                session::id::equals(session_id.to_string()),
+                session::revoked_at::equals(None),
+            ],
+            vec![session::revoked_at::set(Some(Utc::now().into()))],
+        )
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// Revokes every session of `user_id` but `keep`, as after a password
+/// change.
+pub async fn revoke_user_sessions(client: &PrismaClient, user_id: &str, keep: Option<&str>) -> Result<()> {
+    let mut filters = vec![
+        session::user_id::equals(user_id.to_string()),
+        session::revoked_at::equals(None),
+    ];
+    if let Some(keep) = keep {
+        filters.push(session::id::not(keep.to_string()));
+    }
+
+    client
+        .session()
+        .update_many(filters, vec![session::revoked_at::set(Some(Utc::now().into()))])
+        .exec()
+        .await?;
+    Ok(())
+}
+
+pub async fn session_is_active(client: &PrismaClient, session_id: &str) -> Result<bool> {
+    let session = client
+        .session()
+        .find_unique(session::id::equals(session_id.to_string()))
+        .exec()
+        .await?;
+
+    Ok(session.is_some_and(|session| session.revoked_at.is_none()))
+}
+
+async fn issue_tokens(
+    client: &PrismaClient,
+    tokens: &TokenKeys,
+    user: &user::Data,
+    session_id: &str,
+) -> Result<IssuedTokens> {
+    let refresh_token = random_token();
+
+    client
+        .refresh_token()
+        .create(
+            hash_token(&refresh_token),
+            session::id::equals(session_id.to_string()),
+            (Utc::now() + Duration::seconds(tokens.refresh_ttl)).into(),
+            vec![],
+        )
+        .exec()
+        .await?;
+
+    let access_token = tokens.issue(&user.id, &format!("{:?}", user.role), Some(session_id))?;
+
+    Ok(IssuedTokens {
+        access_token,
+        refresh_token,
+        expires_in: tokens.access_ttl,
+    })
+}
+
+async fn revoke_reused(client: &PrismaClient, session_id: &str) -> ApiError {
+    tracing::warn!("refresh token reused, revoking session {}", session_id);
+    if let Err(err) = revoke_session(client, session_id).await {
+        return ApiError::internal(err);
+    }
+    invalid_refresh_token("The refresh token has already been used")
+}
+
+/// A random 256-bit secret, URL-safe so it can be pasted anywhere.
+pub fn random_token() -> String {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    URL_SAFE_NO_PAD.encode(secret)
+}
+
+/// Stored form of a token made by `random_token`. The secret is already
+/// high-entropy, so a plain digest keeps it useless to someone reading the
+/// database without the cost of a password hash.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn invalid_refresh_token(message: &str) -> ApiError {
+    ApiError::new(StatusCode::UNAUTHORIZED, "invalid_refresh_token", message)
+}
@@ -0,0 +1,524 @@
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::GzDecoder;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+use xz2::read::XzDecoder;
+
+/// Upper bound on the size of the `control` member, which in practice is a
+/// few kilobytes.
+const MAX_CONTROL_SIZE: u64 = 1024 * 1024;
+
+/// A single Debian control paragraph. Field names are matched
+/// case-insensitively and the original order is preserved.
+#[derive(Debug, Clone)]
+pub struct ControlFile {
+    fields: Vec<(String, String)>,
+}
+
+impl ControlFile {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut fields: Vec<(String, String)> = Vec::new();
+
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                if fields.is_empty() {
+                    continue;
+                }
+                break;
+            }
+
+            if line.starts_with(' ') || line.starts_with('\t') {
+                let (_, value) = fields
+                    .last_mut()
+                    .ok_or_else(|| anyhow!("continuation line without a field: '{}'", line))?;
+                value.push('\n');
+                value.push_str(line);
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("malformed control line: '{}'", line))?;
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                bail!("malformed control field name: '{}'", key);
+            }
+            if fields.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                bail!("duplicate control field '{}'", key);
+            }
+
+            fields.push((key.to_string(), value.trim().to_string()));
+        }
+
+        if fields.is_empty() {
+            bail!("control file is empty");
+        }
+
+        Ok(Self { fields })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn require(&self, key: &str) -> Result<&str> {
+        match self.get(key) {
+            Some(value) if !value.is_empty() => Ok(value),
+            _ => bail!("control file is missing the required '{}' field", key),
+        }
+    }
+}
+
+impl std::fmt::Display for ControlFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in &self.fields {
+            writeln!(f, "{}: {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// One package named in a relationship field, e.g. `libc6 (>= 2.34)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub name: String,
+    pub constraint: Option<(String, String)>,
+}
+
+impl Relation {
+    /// The constraint in the `<op> <version>` form stored alongside
+    /// dependencies, or an empty string when any version satisfies it.
+    pub fn constraint_string(&self) -> String {
+        match &self.constraint {
+            Some((op, version)) => format!("{} {}", op, version),
+            None => String::new(),
+        }
+    }
+}
+
+/// Parses a relationship field into its comma-separated groups, each a list
+/// of `|`-separated alternatives. Architecture qualifiers, architecture
+/// restrictions and build profiles are dropped.
+pub fn parse_relations(value: &str) -> Result<Vec<Vec<Relation>>> {
+    let mut groups = Vec::new();
+
+    for group in value.split(',') {
+        let group = group.trim();
+        if group.is_empty() {
+            continue;
+        }
+
+        let mut alternatives = Vec::new();
+        for alternative in group.split('|') {
+            alternatives.push(parse_relation(alternative.trim())?);
+        }
+        groups.push(alternatives);
+    }
+
+    Ok(groups)
+}
+
+fn parse_relation(text: &str) -> Result<Relation> {
+    let text = strip_delimited(text, '[', ']');
+    let text = strip_delimited(&text, '<', '>');
+    let text = text.trim();
+
+    let (name_part, constraint) = match text.find('(') {
+        Some(start) => {
+            let end = text
+                .find(')')
+                .filter(|end| *end > start)
+                .ok_or_else(|| anyhow!("unterminated version constraint in '{}'", text))?;
+            (&text[..start], Some(parse_constraint(&text[start + 1..end])?))
+        }
+        None => (text, None),
+    };
+
+    let name = name_part.trim();
+    let name = name.split(':').next().unwrap_or(name);
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        bail!("malformed package relation '{}'", text);
+    }
+
+    Ok(Relation {
+        name: name.to_string(),
+        constraint,
+    })
+}
+
+fn parse_constraint(text: &str) -> Result<(String, String)> {
+    let text = text.trim();
+    let op_len = text
+        .find(|c: char| !matches!(c, '<' | '>' | '='))
+        .unwrap_or(text.len());
+    let (op, version) = text.split_at(op_len);
+    let version = version.trim();
+
+    let op = match op {
+        "<<" | "<=" | "=" | ">=" | ">>" => op,
+        // Obsolete forms still accepted by dpkg.
+        "<" => "<=",
+        ">" => ">=",
+        _ => bail!("unknown version operator '{}'", op),
+    };
+    if version.is_empty() || version.contains(char::is_whitespace) {
+        bail!("malformed version constraint '{}'", text);
+    }
+
+    Ok((op.to_string(), version.to_string()))
+}
+
+/// Drops `[...]` or `<...>` sections that appear outside the parenthesised
+/// version constraint, whose operators also use angle brackets.
+fn strip_delimited(text: &str, open: char, close: char) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut depth = 0;
+    let mut in_constraint = false;
+    for c in text.chars() {
+        if in_constraint {
+            in_constraint = c != ')';
+            result.push(c);
+        } else if c == open {
+            depth += 1;
+        } else if c == close && depth > 0 {
+            depth -= 1;
+        } else if depth == 0 {
+            in_constraint = c == '(';
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Metadata taken from the control file of a binary package.
+#[derive(Debug, Clone)]
+pub struct DebMetadata {
+    pub name: String,
+    pub version: String,
+    pub architecture: String,
+    pub maintainer: String,
+    pub description: String,
+    pub section: Option<String>,
+    pub priority: Option<String>,
+    pub installed_size: Option<i32>,
+    pub depends: Vec<Vec<Relation>>,
+    pub recommends: Vec<Vec<Relation>>,
+    pub suggests: Vec<Vec<Relation>>,
+    pub conflicts: Vec<Vec<Relation>>,
+    pub control: ControlFile,
+}
+
+impl DebMetadata {
+    pub fn from_control(control: ControlFile) -> Result<Self> {
+        let relations = |key: &str| -> Result<Vec<Vec<Relation>>> {
+            match control.get(key) {
+                Some(value) => parse_relations(value).with_context(|| format!("invalid '{}' field", key)),
+                None => Ok(Vec::new()),
+            }
+        };
+
+        // Pre-Depends is a stronger form of Depends and is treated the same.
+        let mut depends = relations("Pre-Depends")?;
+        depends.extend(relations("Depends")?);
+
+        let installed_size = match control.get("Installed-Size") {
+            Some(value) => Some(
+                value
+                    .parse::<i32>()
+                    .map_err(|_| anyhow!("invalid 'Installed-Size' field '{}'", value))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            name: control.require("Package")?.to_string(),
+            version: control.require("Version")?.to_string(),
+            architecture: control.require("Architecture")?.to_string(),
+            maintainer: control.require("Maintainer")?.to_string(),
+            description: unfold_description(control.require("Description")?),
+            section: control.get("Section").map(str::to_string),
+            priority: control.get("Priority").map(str::to_string),
+            installed_size,
+            depends,
+            recommends: relations("Recommends")?,
+            suggests: relations("Suggests")?,
+            conflicts: relations("Conflicts")?,
+            control,
+        })
+    }
+}
+
+/// Turns a folded `Description` field into plain text: continuation lines
+/// lose their leading space and ` .` lines become blank lines.
+fn unfold_description(value: &str) -> String {
+    value
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                return line.trim();
+            }
+            let line = line.strip_prefix(' ').unwrap_or(line);
+            if line.trim() == "." { "" } else { line }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads the metadata of a binary package from the `control` member of its
+/// `control.tar{,.gz,.xz,.zst}`.
+pub fn read_deb_metadata(path: &Path) -> Result<DebMetadata> {
+    let control = read_control_file(path)?;
+    DebMetadata::from_control(control)
+}
+
+pub fn read_control_file(path: &Path) -> Result<ControlFile> {
+    let file = File::open(path).context("failed to open package")?;
+    let mut archive = ar::Archive::new(BufReader::new(file));
+    let mut saw_format = false;
+
+    while let Some(entry) = archive.next_entry() {
+        let mut entry = entry.context("not a valid .deb archive")?;
+        let identifier = String::from_utf8_lossy(entry.header().identifier())
+            .trim_end_matches('/')
+            .to_string();
+
+        match identifier.as_str() {
+            "debian-binary" => {
+                let mut format = String::new();
+                entry.read_to_string(&mut format)?;
+                if !format.trim().starts_with("2.") {
+                    bail!("unsupported .deb format version '{}'", format.trim());
+                }
+                saw_format = true;
+            }
+            member if member.starts_with("control.tar") => {
+                if !saw_format {
+                    bail!("not a valid .deb archive: missing debian-binary member");
+                }
+                let reader: Box<dyn Read + '_> = match member {
+                    "control.tar" => Box::new(entry),
+                    "control.tar.gz" => Box::new(GzDecoder::new(entry)),
+                    "control.tar.xz" => Box::new(XzDecoder::new(entry)),
+                    "control.tar.zst" => Box::new(zstd::stream::read::Decoder::new(entry)?),
+                    other => bail!("unsupported control archive '{}'", other),
+                };
+                return find_control_member(reader);
+            }
+            _ => {}
+        }
+    }
+
+    bail!("not a valid .deb archive: missing control.tar member")
+}
+
+fn find_control_member(reader: impl Read) -> Result<ControlFile> {
+    let mut tar = tar::Archive::new(reader);
+
+    for entry in tar.entries().context("failed to read control archive")? {
+        let entry = entry.context("failed to read control archive")?;
+        let path = entry.path()?.into_owned();
+        if path.components().map(|c| c.as_os_str()).filter(|c| *c != ".").eq(["control"]) {
+            let mut text = String::new();
+            entry
+                .take(MAX_CONTROL_SIZE)
+                .read_to_string(&mut text)
+                .context("control file is not valid UTF-8")?;
+            return ControlFile::parse(&text);
+        }
+    }
+
+    bail!("control archive does not contain a control file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const CONTROL: &str = "\
+Package: hello
+Version: 1:2.10-3
+Architecture: amd64
+Maintainer: Jane Doe <jane@example.com>
+Installed-Size: 280
+Pre-Depends: dpkg (>= 1.19)
+Depends: libc6 (>= 2.34), default-mta | mail-transport-agent
+Suggests: hello-doc
+Section: devel
+Priority: optional
+Description: example package based on GNU hello
+ The GNU hello program produces a familiar, friendly greeting.
+ .
+ It is for testing.
+";
+
+    fn relations(value: &str) -> Vec<Vec<(String, String)>> {
+        parse_relations(value)
+            .unwrap()
+            .into_iter()
+            .map(|group| group.into_iter().map(|r| (r.name.clone(), r.constraint_string())).collect())
+            .collect()
+    }
+
+    fn relation(name: &str, constraint: &str) -> (String, String) {
+        (name.to_string(), constraint.to_string())
+    }
+
+    /// Writes a .deb whose control archive holds `control` as `member`.
+    fn deb(name: &str, member: &str, control: &str) -> std::path::PathBuf {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(control.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, member, control.as_bytes()).unwrap();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&builder.into_inner().unwrap()).unwrap();
+        let control_tar = gz.finish().unwrap();
+
+        let path = std::env::temp_dir().join(format!("debian-services-{}-{}.deb", name, std::process::id()));
+        let mut archive = ar::Builder::new(File::create(&path).unwrap());
+        archive
+            .append(&ar::Header::new(b"debian-binary".to_vec(), 4), &b"2.0\n"[..])
+            .unwrap();
+        archive
+            .append(&ar::Header::new(b"control.tar.gz".to_vec(), control_tar.len() as u64), &control_tar[..])
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_fields_case_insensitively() {
+        let control = ControlFile::parse(CONTROL).unwrap();
+        assert_eq!(control.get("package"), Some("hello"));
+        assert_eq!(control.get("INSTALLED-SIZE"), Some("280"));
+        assert_eq!(control.get("Homepage"), None);
+    }
+
+    #[test]
+    fn keeps_continuation_lines_and_the_first_paragraph_only() {
+        let control = ControlFile::parse("\n\nPackage: hello\nDescription: short\n more\n\tmore still\n\nPackage: other\n").unwrap();
+        assert_eq!(control.get("Package"), Some("hello"));
+        assert_eq!(control.get("Description"), Some("short\n more\n\tmore still"));
+        assert_eq!(control.to_string(), "Package: hello\nDescription: short\n more\n\tmore still\n");
+    }
+
+    #[test]
+    fn rejects_malformed_control_files() {
+        for text in ["", "\n\n", " continuation first\n", "no colon\n", "Bad Name: x\n", ": empty\n", "Package: a\npackage: b\n"] {
+            assert!(ControlFile::parse(text).is_err(), "{:?} parsed", text);
+        }
+    }
+
+    #[test]
+    fn parses_relations_with_alternatives() {
+        assert_eq!(
+            relations("libc6 (>= 2.34), default-mta | mail-transport-agent,, python3:any (<< 4)"),
+            [
+                vec![relation("libc6", ">= 2.34")],
+                vec![relation("default-mta", ""), relation("mail-transport-agent", "")],
+                vec![relation("python3", "<< 4")],
+            ]
+        );
+        assert!(parse_relations("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn drops_architecture_restrictions_and_build_profiles() {
+        assert_eq!(
+            relations("libfoo (>= 1.0) [amd64 arm64] <!nocheck>, libbar [!i386] | libbaz <stage1>"),
+            [
+                vec![relation("libfoo", ">= 1.0")],
+                vec![relation("libbar", ""), relation("libbaz", "")],
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_obsolete_operators_and_tight_spacing() {
+        assert_eq!(
+            relations("a (<1.0), b (>1.0), c(=1.0), d (  >>  2:1.0-1 )"),
+            [
+                vec![relation("a", "<= 1.0")],
+                vec![relation("b", ">= 1.0")],
+                vec![relation("c", "= 1.0")],
+                vec![relation("d", ">> 2:1.0-1")],
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_relations() {
+        for value in ["libc6 (>= 2.34", "libc6 (~> 2.34)", "libc6 (>=)", "libc6 (>= 2 3)", "two words", "a | , b", "(>= 1.0)"] {
+            assert!(parse_relations(value).is_err(), "{:?} parsed", value);
+        }
+    }
+
+    #[test]
+    fn reads_metadata_from_the_control_file() {
+        let metadata = DebMetadata::from_control(ControlFile::parse(CONTROL).unwrap()).unwrap();
+        assert_eq!(metadata.name, "hello");
+        assert_eq!(metadata.version, "1:2.10-3");
+        assert_eq!(metadata.architecture, "amd64");
+        assert_eq!(metadata.maintainer, "Jane Doe <jane@example.com>");
+        assert_eq!(
+            metadata.description,
+            "example package based on GNU hello\nThe GNU hello program produces a familiar, friendly greeting.\n\nIt is for testing."
+        );
+        assert_eq!(metadata.section.as_deref(), Some("devel"));
+        assert_eq!(metadata.priority.as_deref(), Some("optional"));
+        assert_eq!(metadata.installed_size, Some(280));
+        // Pre-Depends come first, as dpkg has to satisfy them first.
+        let depends: Vec<_> = metadata.depends.iter().map(|group| group[0].name.as_str()).collect();
+        assert_eq!(depends, ["dpkg", "libc6", "default-mta"]);
+        assert_eq!(metadata.suggests.len(), 1);
+        assert!(metadata.recommends.is_empty() && metadata.conflicts.is_empty());
+    }
+
+    #[test]
+    fn requires_the_identifying_fields() {
+        for field in ["Package", "Version", "Architecture", "Maintainer", "Description"] {
+            let text: String = CONTROL
+                .lines()
+                .filter(|line| !line.starts_with(&format!("{}:", field)))
+                .map(|line| format!("{}\n", line))
+                .collect();
+            let err = DebMetadata::from_control(ControlFile::parse(&text).unwrap()).unwrap_err();
+            assert!(err.to_string().contains(field), "{}", err);
+        }
+
+        let text = CONTROL.replace("Installed-Size: 280", "Installed-Size: lots");
+        assert!(DebMetadata::from_control(ControlFile::parse(&text).unwrap()).is_err());
+        let text = CONTROL.replace("Depends: libc6 (>= 2.34)", "Depends: libc6 (>= 2.34");
+        let err = DebMetadata::from_control(ControlFile::parse(&text).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("'Depends'"), "{}", err);
+    }
+
+    #[test]
+    fn reads_the_control_member_of_a_deb() {
+        let path = deb("plain", "./control", CONTROL);
+        let metadata = read_deb_metadata(&path).unwrap();
+        assert_eq!((metadata.name.as_str(), metadata.version.as_str()), ("hello", "1:2.10-3"));
+        std::fs::remove_file(path).unwrap();
+
+        let path = deb("no-control", "./postinst", "#!/bin/sh\n");
+        assert!(read_control_file(&path).unwrap_err().to_string().contains("does not contain a control file"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_debs() {
+        let path = std::env::temp_dir().join(format!("debian-services-text-{}.deb", std::process::id()));
+        std::fs::write(&path, "not an archive").unwrap();
+        assert!(read_control_file(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
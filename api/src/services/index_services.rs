@@ -0,0 +1,227 @@
+use axum::http::StatusCode;
+use prisma_client_rust::{Direction, PrismaValue, Raw};
+use serde::Deserialize;
+use std::{collections::BTreeSet, time::Duration};
+
+use crate::{
+    db::PrismaClient,
+    errors::ApiError,
+    models::{IndexChangesResponse, IndexPackageResponse},
+    prisma::package,
+    services::packages_services::{latest_version_fetch, sort_newest_first, tags_fetch, version_response},
+};
+
+/// How many transactions' changes one page of the changes feed covers.
+pub const CHANGES_PAGE_SIZE: usize = 1000;
+
+/// How often changes past the retention period are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Where the change log stands. Only changes below `bound`, the oldest
+/// transaction still running, are final: transactions commit in any order,
+/// but none can log a change below it any more.
+pub struct LogState {
+    pub bound: i64,
+    /// The transaction of the newest final change, None if there is none.
+    pub latest: Option<i64>,
+    /// The transaction of the oldest change kept. Changes below it have
+    /// been pruned, so no cursor below it can be resumed from.
+    pub oldest: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct LogStateRow {
+    bound: String,
+    latest: Option<String>,
+    oldest: Option<String>,
+    logged: bool,
+}
+
+const LOG_STATE_QUERY: &str = r#"
+SELECT
+    pg_snapshot_xmin(pg_current_snapshot())::text AS bound,
+    (
+        SELECT c."txid"::text
+        FROM "index_changes" c
+        WHERE c."txid" < pg_snapshot_xmin(pg_current_snapshot())
+        ORDER BY c."txid" DESC
+        LIMIT 1
+    ) AS latest,
+    (SELECT c."txid"::text FROM "index_changes" c ORDER BY c."txid" LIMIT 1) AS oldest,
+    EXISTS (SELECT 1 FROM pg_trigger WHERE tgname = 'package_versions_index') AS logged
+"#;
+
+#[derive(Deserialize)]
+struct ChangeRow {
+    txid: String,
+    #[serde(rename = "packageName")]
+    package_name: String,
+}
+
+/// The changes of the first transactions at or after the cursor and below
+/// the bound, one more transaction than a page holds to tell whether more
+/// follow.
+const CHANGES_QUERY: &str = r#"
+WITH txids AS (
+    SELECT DISTINCT c."txid"
+    FROM "index_changes" c
+    WHERE c."txid" >= {}::text::xid8 AND c."txid" < {}::text::xid8
+    ORDER BY c."txid"
+    LIMIT {}
+)
+SELECT c."txid"::text AS txid, c."packageName"
+FROM "index_changes" c JOIN txids ON txids."txid" = c."txid"
+ORDER BY c."txid", c."id"
+"#;
+
+/// Deletes the changes of transactions older than the newest one with a
+/// change past the retention period. Its own changes are kept, so the
+/// oldest change left marks where cursors stop being resumable.
+const PRUNE_QUERY: &str = r#"
+DELETE FROM "index_changes" WHERE "txid" < (
+    SELECT c."txid"
+    FROM "index_changes" c
+    WHERE c."changedAt" < now() - make_interval(days => {}::int)
+    ORDER BY c."txid" DESC
+    LIMIT 1
+)
+"#;
+
+fn parse_txid(txid: &str) -> Result<i64, ApiError> {
+    txid.parse().map_err(ApiError::internal)
+}
+
+/// The state of the change log. Fails when the triggers that fill it are
+/// missing, as on a database created with `prisma db push`, rather than
+/// serving a feed that never changes.
+pub async fn log_state(client: &PrismaClient) -> Result<LogState, ApiError> {
+    let rows: Vec<LogStateRow> = client
+        ._query_raw(Raw::new(LOG_STATE_QUERY, vec![]))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    let row = rows
+        .into_iter()
+        .next()
+        .ok_or_else(|| ApiError::internal("the change log state query returned nothing"))?;
+
+    if !row.logged {
+        tracing::error!("the index change log has no triggers; run the index_changes migration");
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "index_unavailable",
+            "The package index change log is not set up; run prisma/migrations/20261018040000_index_changes/migration.sql",
+        ));
+    }
+
+    Ok(LogState {
+        bound: parse_txid(&row.bound)?,
+        latest: row.latest.as_deref().map(parse_txid).transpose()?,
+        oldest: row.oldest.as_deref().map(parse_txid).transpose()?,
+    })
+}
+
+/// Prunes the change log every hour for as long as the server runs.
+/// Clients whose cursor falls below what is left fetch the whole index
+/// again.
+pub fn schedule_pruning(client: PrismaClient, retention_days: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match client
+                ._execute_raw(Raw::new(PRUNE_QUERY, vec![PrismaValue::Int(retention_days)]))
+                .exec()
+                .await
+            {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!("pruned {} index changes", pruned),
+                Err(err) => tracing::error!("failed to prune the index change log: {}", err),
+            }
+        }
+    });
+}
+
+/// The packages matching `filters` with all their versions, by name.
+pub async fn index_packages(
+    client: &PrismaClient,
+    filters: Vec<package::WhereParam>,
+) -> Result<Vec<IndexPackageResponse>, ApiError> {
+    let packages = client
+        .package()
+        .find_many(filters)
+        .with(latest_version_fetch())
+        .with(tags_fetch())
+        .order_by(package::name::order(Direction::Asc))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(packages
+        .into_iter()
+        .map(|mut package| {
+            let mut versions = package.versions.take().unwrap_or_default();
+            sort_newest_first(&mut versions);
+            IndexPackageResponse {
+                name: package.name.clone(),
+                versions: versions
+                    .into_iter()
+                    .map(|version| version_response(&package, version))
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+/// The packages changed from the cursor `since` up to the log's bound, a
+/// page of transactions at a time. Packages are read as they are now
+/// rather than as of the page's cursor, which at worst sends a later change
+/// twice.
+pub async fn changes_since(
+    client: &PrismaClient,
+    state: &LogState,
+    since: i64,
+) -> Result<IndexChangesResponse, ApiError> {
+    let rows: Vec<ChangeRow> = client
+        ._query_raw(Raw::new(
+            CHANGES_QUERY,
+            vec![
+                PrismaValue::String(since.to_string()),
+                PrismaValue::String(state.bound.to_string()),
+                PrismaValue::Int(CHANGES_PAGE_SIZE as i64 + 1),
+            ],
+        ))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    // The transaction past the page is where the next page starts.
+    let mut txids = BTreeSet::new();
+    let mut names = BTreeSet::new();
+    let mut next = None;
+    for row in rows {
+        let txid = parse_txid(&row.txid)?;
+        txids.insert(txid);
+        if txids.len() > CHANGES_PAGE_SIZE {
+            next = Some(txid);
+            break;
+        }
+        names.insert(row.package_name);
+    }
+    let more = next.is_some();
+    let cursor = next.unwrap_or(state.bound);
+
+    if names.is_empty() {
+        return Ok(IndexChangesResponse { cursor, more, packages: vec![] });
+    }
+
+    let mut packages = index_packages(client, vec![package::name::in_vec(names.iter().cloned().collect())]).await?;
+    for name in names {
+        if !packages.iter().any(|package| package.name == name) {
+            packages.push(IndexPackageResponse { name, versions: vec![] });
+        }
+    }
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(IndexChangesResponse { cursor, more, packages })
+}
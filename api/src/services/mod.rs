@@ -0,0 +1,12 @@
+pub mod auth_services;
+pub mod debian_services;
+pub mod index_services;
+pub mod org_services;
+pub mod ownership_services;
+pub mod packages_services;
+pub mod repository_services;
+pub mod search_services;
+pub mod signing_services;
+pub mod tag_services;
+pub mod token_services;
+pub mod user_services;
@@ -0,0 +1,300 @@
+use axum::http::StatusCode;
+
+use crate::{
+    db::PrismaClient,
+    errors::ApiError,
+    prisma::{org_role, organization, organization_member, team, team_member, user, OrgRole},
+    services::auth_services::Caller,
+};
+
+/// Splits a scoped package name such as `@infra/agent` into the
+/// organization and the Debian package name. Personal packages have no
+/// scope.
+pub fn split_scope(name: &str) -> (Option<&str>, &str) {
+    match name.strip_prefix('@').and_then(|rest| rest.split_once('/')) {
+        Some((scope, name)) => (Some(scope), name),
+        None => (None, name),
+    }
+}
+
+/// Organization names double as apt component names, so they follow the
+/// same rules and may not shadow the component of personal packages.
+pub fn validate_org_name(name: &str, reserved_component: &str) -> Result<(), ApiError> {
+    let valid = (2..=39).contains(&name.len())
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(ApiError::bad_request(
+            "invalid_organization_name",
+            format!(
+                "'{}' is not a valid organization name; use 2 to 39 lowercase letters, digits and '-'",
+                name
+            ),
+        ));
+    }
+    if name == reserved_component {
+        return Err(ApiError::conflict(
+            "organization_exists",
+            format!("The name '{}' is reserved", name),
+        ));
+    }
+    Ok(())
+}
+
+fn rank(role: &OrgRole) -> u8 {
+    if *role == org_role::OWNER {
+        3
+    } else if *role == org_role::ADMIN {
+        2
+    } else {
+        1
+    }
+}
+
+pub fn parse_org_role(value: &str) -> Result<OrgRole, ApiError> {
+    match value.to_ascii_uppercase().as_str() {
+        "OWNER" => Ok(org_role::OWNER),
+        "ADMIN" => Ok(org_role::ADMIN),
+        "MEMBER" => Ok(org_role::MEMBER),
+        _ => Err(ApiError::bad_request(
+            "invalid_role",
+            format!("'{}' is not one of owner, admin or member", value),
+        )),
+    }
+}
+
+pub async fn find_org(client: &PrismaClient, name: &str) -> Result<organization::Data, ApiError> {
+    client
+        .organization()
+        .find_unique(organization::name::equals(name.to_string()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("Organization {} does not exist", name)))
+}
+
+pub async fn find_team(client: &PrismaClient, org: &organization::Data, name: &str) -> Result<team::Data, ApiError> {
+    client
+        .team()
+        .find_unique(team::organization_id_name(org.id.clone(), name.to_string()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("Team {} does not exist in {}", name, org.name)))
+}
+
+/// Creates an organization with `owner_id` as its first owner.
+pub async fn create_org(
+    client: &PrismaClient,
+    name: String,
+    display_name: Option<String>,
+    owner_id: &str,
+) -> Result<organization::Data, ApiError> {
+    let existing = client
+        .organization()
+        .find_unique(organization::name::equals(name.clone()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if existing.is_some() {
+        return Err(ApiError::conflict(
+            "organization_exists",
+            format!("Organization {} already exists", name),
+        ));
+    }
+
+    let owner_id = owner_id.to_string();
+    client
+        ._transaction()
+        .run(|tx| async move {
+            let org = tx
+                .organization()
+                .create(name, vec![organization::display_name::set(display_name)])
+                .exec()
+                .await?;
+
+            tx.organization_member()
+                .create(
+                    organization::id::equals(org.id.clone()),
+                    user::id::equals(owner_id),
+                    vec![organization_member::role::set(org_role::OWNER)],
+                )
+                .exec()
+                .await?;
+
+            Ok::<_, prisma_client_rust::QueryError>(org)
+        })
+        .await
+        .map_err(ApiError::internal)
+}
+
+/// The caller's role in `org`, if they are a member.
+pub async fn org_role_of(
+    client: &PrismaClient,
+    org: &organization::Data,
+    user_id: &str,
+) -> Result<Option<OrgRole>, ApiError> {
+    Ok(client
+        .organization_member()
+        .find_unique(organization_member::organization_id_user_id(
+            org.id.clone(),
+            user_id.to_string(),
+        ))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .map(|member| member.role))
+}
+
+/// Fails with 403 unless the caller holds at least `min` in `org`, or acts
+/// as admin.
+pub async fn require_org_role(
+    client: &PrismaClient,
+    caller: &Caller,
+    org: &organization::Data,
+    min: OrgRole,
+) -> Result<(), ApiError> {
+    if caller.acts_as_admin() {
+        return Ok(());
+    }
+    match org_role_of(client, org, &caller.user_id).await? {
+        Some(role) if rank(&role) >= rank(&min) => Ok(()),
+        _ => Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "insufficient_org_role",
+            format!(
+                "This requires the {} role in {}",
+                format!("{:?}", min).to_lowercase(),
+                org.name
+            ),
+        )),
+    }
+}
+
+/// Whether the caller may manage packages in the `@scope/` namespace:
+/// organization owners and admins may, as may members of a team with
+/// publish permission.
+pub async fn can_manage_scope(client: &PrismaClient, caller: &Caller, scope: &str) -> Result<bool, ApiError> {
+    let org = find_org(client, scope).await?;
+    if caller.acts_as_admin() {
+        return Ok(true);
+    }
+    if let Some(role) = org_role_of(client, &org, &caller.user_id).await? {
+        if rank(&role) >= rank(&org_role::ADMIN) {
+            return Ok(true);
+        }
+    }
+
+    let publishing_team = client
+        .team_member()
+        .find_first(vec![
+            team_member::user_id::equals(caller.user_id.clone()),
+            team_member::team::is(vec![
+                team::organization_id::equals(org.id),
+                team::can_publish::equals(true),
+            ]),
+        ])
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(publishing_team.is_some())
+}
+
+/// Sets the role of a member, adding them if needed. Only owners may grant
+/// or take away ownership.
+pub async fn set_member(
+    client: &PrismaClient,
+    caller: &Caller,
+    org: &organization::Data,
+    user_id: &str,
+    role: OrgRole,
+) -> Result<organization_member::Data, ApiError> {
+    let current = org_role_of(client, org, user_id).await?;
+    let touches_owner = role == org_role::OWNER || current.as_ref() == Some(&org_role::OWNER);
+    let min = if touches_owner { org_role::OWNER } else { org_role::ADMIN };
+    require_org_role(client, caller, org, min).await?;
+
+    if current.as_ref() == Some(&org_role::OWNER) && role != org_role::OWNER {
+        ensure_other_owner(client, org, user_id).await?;
+    }
+
+    client
+        .organization_member()
+        .upsert(
+            organization_member::organization_id_user_id(org.id.clone(), user_id.to_string()),
+            organization_member::create(
+                organization::id::equals(org.id.clone()),
+                user::id::equals(user_id.to_string()),
+                vec![organization_member::role::set(role.clone())],
+            ),
+            vec![organization_member::role::set(role)],
+        )
+        .with(organization_member::user::fetch())
+        .exec()
+        .await
+        .map_err(ApiError::internal)
+}
+
+/// Removes a member from the organization and its teams. Members may leave
+/// on their own; the last owner cannot.
+pub async fn remove_member(
+    client: &PrismaClient,
+    caller: &Caller,
+    org: &organization::Data,
+    user_id: &str,
+) -> Result<(), ApiError> {
+    let Some(current) = org_role_of(client, org, user_id).await? else {
+        return Err(ApiError::not_found(format!("The user is not a member of {}", org.name)));
+    };
+    if caller.user_id != user_id {
+        let min = if current == org_role::OWNER { org_role::OWNER } else { org_role::ADMIN };
+        require_org_role(client, caller, org, min).await?;
+    }
+    if current == org_role::OWNER {
+        ensure_other_owner(client, org, user_id).await?;
+    }
+
+    let org_id = org.id.clone();
+    let user_id = user_id.to_string();
+    client
+        ._transaction()
+        .run(|tx| async move {
+            tx.team_member()
+                .delete_many(vec![
+                    team_member::user_id::equals(user_id.clone()),
+                    team_member::team::is(vec![team::organization_id::equals(org_id.clone())]),
+                ])
+                .exec()
+                .await?;
+
+            tx.organization_member()
+                .delete(organization_member::organization_id_user_id(org_id, user_id))
+                .exec()
+                .await
+        })
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(())
+}
+
+async fn ensure_other_owner(client: &PrismaClient, org: &organization::Data, user_id: &str) -> Result<(), ApiError> {
+    let others = client
+        .organization_member()
+        .count(vec![
+            organization_member::organization_id::equals(org.id.clone()),
+            organization_member::role::equals(org_role::OWNER),
+            organization_member::user_id::not(user_id.to_string()),
+        ])
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if others == 0 {
+        return Err(ApiError::conflict(
+            "last_owner",
+            format!("{} needs at least one owner", org.name),
+        ));
+    }
+    Ok(())
+}
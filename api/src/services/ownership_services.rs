@@ -0,0 +1,400 @@
+use anyhow::Result;
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use prisma_client_rust::operator::or;
+
+use crate::{
+    db::PrismaClient,
+    errors::ApiError,
+    prisma::{ownership_transfer, owner_role, package_owner, user},
+    services::{
+        auth_services::Caller,
+        org_services::{can_manage_scope, split_scope},
+        packages_services::lock_package_name,
+    },
+};
+
+/// Pending transfers lapse after this many days so a forgotten offer cannot
+/// be accepted long after the owner changed their mind.
+const TRANSFER_TTL_DAYS: i64 = 14;
+
+/// Whether a publish of a package name is allowed because the caller
+/// already maintains it, or because nobody does yet and the caller is about
+/// to claim it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishRight {
+    Maintainer,
+    Claim,
+}
+
+/// The owners and maintainers of `name`, owners first.
+pub async fn list_owners(client: &PrismaClient, name: &str) -> Result<Vec<package_owner::Data>> {
+    let mut owners = client
+        .package_owner()
+        .find_many(vec![package_owner::package_name::equals(name.to_string())])
+        .with(package_owner::user::fetch())
+        .order_by(package_owner::created_at::order(prisma_client_rust::Direction::Asc))
+        .exec()
+        .await?;
+
+    owners.sort_by_key(|owner| owner.role != owner_role::OWNER);
+    Ok(owners)
+}
+
+/// Checks that the caller may publish a version of `name`. Scoped names
+/// belong to their organization and are never claimed by a user; the
+/// organization's permissions apply in addition to any maintainers.
+pub async fn authorize_publish(
+    client: &PrismaClient,
+    caller: &Caller,
+    name: &str,
+) -> Result<PublishRight, ApiError> {
+    let (scope, _) = split_scope(name);
+    if let Some(scope) = scope {
+        if can_manage_scope(client, caller, scope).await? {
+            return Ok(PublishRight::Maintainer);
+        }
+    }
+
+    let owners = list_owners(client, name).await.map_err(ApiError::internal)?;
+    if owners.is_empty() && scope.is_none() {
+        return Ok(PublishRight::Claim);
+    }
+    if caller.acts_as_admin() || owners.iter().any(|owner| owner.user_id == caller.user_id) {
+        return Ok(PublishRight::Maintainer);
+    }
+    Err(not_maintainer(name))
+}
+
+/// Records `user_id` as the owner of `name` unless somebody owns it by now,
+/// and returns whether the name is the user's. Meant to run in the
+/// transaction that creates the first version: the lock it takes on the
+/// name until then keeps concurrent first publishes from both claiming it.
+pub async fn claim_name(client: &PrismaClient, name: &str, user_id: &str) -> prisma_client_rust::Result<bool> {
+    lock_package_name(client, name).await?;
+
+    let owners = client
+        .package_owner()
+        .find_many(vec![package_owner::package_name::equals(name.to_string())])
+        .exec()
+        .await?;
+    if !owners.is_empty() {
+        return Ok(owners.iter().any(|owner| owner.user_id == user_id));
+    }
+
+    client
+        .package_owner()
+        .create(
+            name.to_string(),
+            user::id::equals(user_id.to_string()),
+            vec![package_owner::role::set(owner_role::OWNER)],
+        )
+        .exec()
+        .await?;
+    Ok(true)
+}
+
+/// Fails with 403 unless the caller owns or maintains `name`, may manage
+/// its organization's namespace, or acts as admin.
+pub async fn require_maintainer(client: &PrismaClient, caller: &Caller, name: &str) -> Result<(), ApiError> {
+    if caller.acts_as_admin() {
+        return Ok(());
+    }
+    if let (Some(scope), _) = split_scope(name) {
+        if can_manage_scope(client, caller, scope).await? {
+            return Ok(());
+        }
+    }
+    let owners = list_owners(client, name).await.map_err(ApiError::internal)?;
+    if owners.iter().any(|owner| owner.user_id == caller.user_id) {
+        return Ok(());
+    }
+    Err(not_maintainer(name))
+}
+
+/// Fails with 403 unless the caller is an owner of `name`, or acts as admin.
+/// Whoever may manage an organization's namespace owns its packages.
+pub async fn require_package_owner(client: &PrismaClient, caller: &Caller, name: &str) -> Result<(), ApiError> {
+    if caller.acts_as_admin() {
+        return Ok(());
+    }
+    if let (Some(scope), _) = split_scope(name) {
+        if can_manage_scope(client, caller, scope).await? {
+            return Ok(());
+        }
+    }
+    let owners = list_owners(client, name).await.map_err(ApiError::internal)?;
+    let is_owner = owners
+        .iter()
+        .any(|owner| owner.user_id == caller.user_id && owner.role == owner_role::OWNER);
+    if is_owner {
+        return Ok(());
+    }
+    Err(ApiError::new(
+        StatusCode::FORBIDDEN,
+        "not_package_owner",
+        format!("Only owners of '{}' can do this", name),
+    ))
+}
+
+pub async fn add_maintainer(client: &PrismaClient, name: &str, user_id: &str) -> Result<package_owner::Data, ApiError> {
+    let existing = client
+        .package_owner()
+        .find_unique(package_owner::package_name_user_id(name.to_string(), user_id.to_string()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if existing.is_some() {
+        return Err(ApiError::conflict(
+            "already_maintainer",
+            format!("The user already maintains '{}'", name),
+        ));
+    }
+
+    client
+        .package_owner()
+        .create(
+            name.to_string(),
+            user::id::equals(user_id.to_string()),
+            vec![package_owner::role::set(owner_role::MAINTAINER)],
+        )
+        .with(package_owner::user::fetch())
+        .exec()
+        .await
+        .map_err(ApiError::internal)
+}
+
+/// Removes `user_id` from the owners of `name`. Owners may remove anyone,
+/// maintainers only themselves, and the last owner cannot be removed;
+/// ownership has to be transferred instead.
+pub async fn remove_owner(
+    client: &PrismaClient,
+    caller: &Caller,
+    name: &str,
+    user_id: &str,
+) -> Result<(), ApiError> {
+    if caller.user_id != user_id {
+        require_package_owner(client, caller, name).await?;
+    }
+
+    let owners = list_owners(client, name).await.map_err(ApiError::internal)?;
+    let Some(target) = owners.iter().find(|owner| owner.user_id == user_id) else {
+        return Err(ApiError::not_found(format!("The user does not maintain '{}'", name)));
+    };
+
+    let owner_count = owners
+        .iter()
+        .filter(|owner| owner.role == owner_role::OWNER)
+        .count();
+    if target.role == owner_role::OWNER && owner_count == 1 {
+        return Err(ApiError::conflict(
+            "last_owner",
+            format!("'{}' needs an owner; transfer ownership before leaving", name),
+        ));
+    }
+
+    client
+        .package_owner()
+        .delete(package_owner::id::equals(target.id.clone()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(())
+}
+
+pub fn transfer_expires_at(transfer: &ownership_transfer::Data) -> DateTime<Utc> {
+    DateTime::<Utc>::from(transfer.created_at) + Duration::days(TRANSFER_TTL_DAYS)
+}
+
+fn transfer_is_pending(transfer: &ownership_transfer::Data, now: DateTime<Utc>) -> bool {
+    transfer.accepted_at.is_none() && transfer.cancelled_at.is_none() && transfer_expires_at(transfer) > now
+}
+
+/// Offers ownership of `name` to `to_user_id`. Any earlier offer for the
+/// same name is withdrawn, so at most one transfer is pending per name.
+pub async fn request_transfer(
+    client: &PrismaClient,
+    caller: &Caller,
+    name: &str,
+    to_user_id: &str,
+) -> Result<ownership_transfer::Data, ApiError> {
+    require_package_owner(client, caller, name).await?;
+    if caller.user_id == to_user_id {
+        return Err(ApiError::bad_request(
+            "invalid_recipient",
+            "Ownership cannot be transferred to yourself",
+        ));
+    }
+
+    let now = Utc::now();
+    client
+        .ownership_transfer()
+        .update_many(
+            vec![
+                ownership_transfer::package_name::equals(name.to_string()),
+                ownership_transfer::accepted_at::equals(None),
+                ownership_transfer::cancelled_at::equals(None),
+            ],
+            vec![ownership_transfer::cancelled_at::set(Some(now.into()))],
+        )
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    client
+        .ownership_transfer()
+        .create(
+            name.to_string(),
+            user::id::equals(caller.user_id.clone()),
+            user::id::equals(to_user_id.to_string()),
+            vec![],
+        )
+        .with(ownership_transfer::from::fetch())
+        .with(ownership_transfer::to::fetch())
+        .exec()
+        .await
+        .map_err(ApiError::internal)
+}
+
+/// Pending transfers offered to or by `user_id`, newest first.
+pub async fn list_transfers(client: &PrismaClient, user_id: &str) -> Result<Vec<ownership_transfer::Data>> {
+    let now = Utc::now();
+    let transfers = client
+        .ownership_transfer()
+        .find_many(vec![
+            or(vec![
+                ownership_transfer::from_user_id::equals(user_id.to_string()),
+                ownership_transfer::to_user_id::equals(user_id.to_string()),
+            ]),
+            ownership_transfer::accepted_at::equals(None),
+            ownership_transfer::cancelled_at::equals(None),
+        ])
+        .with(ownership_transfer::from::fetch())
+        .with(ownership_transfer::to::fetch())
+        .order_by(ownership_transfer::created_at::order(prisma_client_rust::Direction::Desc))
+        .exec()
+        .await?;
+
+    Ok(transfers
+        .into_iter()
+        .filter(|transfer| transfer_is_pending(transfer, now))
+        .collect())
+}
+
+async fn find_pending_transfer(client: &PrismaClient, id: &str) -> Result<ownership_transfer::Data, ApiError> {
+    client
+        .ownership_transfer()
+        .find_unique(ownership_transfer::id::equals(id.to_string()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .filter(|transfer| transfer_is_pending(transfer, Utc::now()))
+        .ok_or_else(|| ApiError::not_found(format!("No pending transfer {}", id)))
+}
+
+/// Accepts a transfer offered to the caller: the recipient becomes an owner
+/// and the previous owner stays on as a maintainer, free to leave with
+/// `remove_owner` afterwards.
+pub async fn accept_transfer(client: &PrismaClient, caller: &Caller, id: &str) -> Result<String, ApiError> {
+    let transfer = find_pending_transfer(client, id).await?;
+    if transfer.to_user_id != caller.user_id {
+        return Err(ApiError::not_found(format!("No pending transfer {}", id)));
+    }
+
+    let sender = client
+        .package_owner()
+        .find_unique(package_owner::package_name_user_id(
+            transfer.package_name.clone(),
+            transfer.from_user_id.clone(),
+        ))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    if sender.map(|owner| owner.role) != Some(owner_role::OWNER) {
+        return Err(ApiError::conflict(
+            "transfer_stale",
+            "The user who offered this transfer no longer owns the package",
+        ));
+    }
+
+    let name = transfer.package_name.clone();
+    client
+        ._transaction()
+        .run(|tx| async move {
+            let claimed = tx
+                .ownership_transfer()
+                .update_many(
+                    vec![
+                        ownership_transfer::id::equals(transfer.id.clone()),
+                        ownership_transfer::accepted_at::equals(None),
+                        ownership_transfer::cancelled_at::equals(None),
+                    ],
+                    vec![ownership_transfer::accepted_at::set(Some(Utc::now().into()))],
+                )
+                .exec()
+                .await?;
+            if claimed == 0 {
+                return Ok(false);
+            }
+
+            tx.package_owner()
+                .upsert(
+                    package_owner::package_name_user_id(
+                        transfer.package_name.clone(),
+                        transfer.to_user_id.clone(),
+                    ),
+                    package_owner::create(
+                        transfer.package_name.clone(),
+                        user::id::equals(transfer.to_user_id.clone()),
+                        vec![package_owner::role::set(owner_role::OWNER)],
+                    ),
+                    vec![package_owner::role::set(owner_role::OWNER)],
+                )
+                .exec()
+                .await?;
+
+            tx.package_owner()
+                .update(
+                    package_owner::package_name_user_id(
+                        transfer.package_name.clone(),
+                        transfer.from_user_id.clone(),
+                    ),
+                    vec![package_owner::role::set(owner_role::MAINTAINER)],
+                )
+                .exec()
+                .await?;
+
+            Ok::<_, prisma_client_rust::QueryError>(true)
+        })
+        .await
+        .map_err(ApiError::internal)?
+        .then_some(name)
+        .ok_or_else(|| ApiError::not_found(format!("No pending transfer {}", id)))
+}
+
+/// Withdraws (by the sender) or declines (by the recipient) a transfer.
+pub async fn cancel_transfer(client: &PrismaClient, caller: &Caller, id: &str) -> Result<(), ApiError> {
+    let transfer = find_pending_transfer(client, id).await?;
+    if transfer.from_user_id != caller.user_id && transfer.to_user_id != caller.user_id {
+        return Err(ApiError::not_found(format!("No pending transfer {}", id)));
+    }
+
+    client
+        .ownership_transfer()
+        .update(
+            ownership_transfer::id::equals(transfer.id),
+            vec![ownership_transfer::cancelled_at::set(Some(Utc::now().into()))],
+        )
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(())
+}
+
+pub fn not_maintainer(name: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::FORBIDDEN,
+        "not_package_maintainer",
+        format!("Only owners and maintainers of '{}' can publish it", name),
+    )
+}
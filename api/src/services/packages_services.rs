@@ -0,0 +1,564 @@
+use axum::{
+    body::Body,
+    extract::multipart::Field,
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use common::{Constraint, Version};
+use prisma_client_rust::{Direction, PrismaValue, QueryError, Raw};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    config::{AppState, Settings},
+    errors::ApiError,
+    models::{DependencyRequest, DependencyResponse, PackageResponse, TagResponse},
+    db::PrismaClient,
+    prisma::{package, package_dependency, package_tag, package_version, DependencyType},
+    services::{
+        debian_services::DebMetadata,
+        org_services::{split_scope, validate_org_name},
+        repository_services::strip_epoch,
+    },
+    utils::{
+        if_none_match,
+        pagination::{Page, Paginated, SortKey},
+        parse_range, RangeRequest,
+    },
+};
+
+/// An uploaded artifact written to the staging area of the storage root,
+/// with its size and SHA-256 computed while streaming.
+pub struct StagedArtifact {
+    pub temp_path: PathBuf,
+    pub size: i64,
+    pub checksum: String,
+}
+
+/// Streams a multipart `file` field to disk without buffering it in memory.
+pub async fn stage_upload(settings: &Settings, mut field: Field<'_>) -> Result<StagedArtifact, ApiError> {
+    let staging_dir = settings.storage_path.join("tmp");
+    fs::create_dir_all(&staging_dir).await.map_err(ApiError::internal)?;
+
+    let temp_path = staging_dir.join(format!("{}.upload", uuid::Uuid::new_v4()));
+    let mut file = fs::File::create(&temp_path).await.map_err(ApiError::internal)?;
+
+    let mut hasher = Sha256::new();
+    let mut size: i64 = 0;
+
+    loop {
+        let chunk = match field.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(err) => {
+                let _ = fs::remove_file(&temp_path).await;
+                return Err(ApiError::bad_request("invalid_upload", err.to_string()));
+            }
+        };
+
+        hasher.update(&chunk);
+        size += chunk.len() as i64;
+
+        if let Err(err) = file.write_all(&chunk).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(ApiError::internal(err));
+        }
+    }
+
+    file.flush().await.map_err(ApiError::internal)?;
+
+    if size == 0 {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(ApiError::bad_request("empty_upload", "The uploaded file is empty"));
+    }
+
+    Ok(StagedArtifact {
+        temp_path,
+        size,
+        checksum: hex::encode(hasher.finalize()),
+    })
+}
+
+/// Takes a lock on the blob behind `checksum` until the end of the
+/// transaction. Publishing under it and releasing under it cannot overlap,
+/// so a release cannot delete a blob a version is about to refer to.
+pub async fn lock_blob(client: &PrismaClient, checksum: &str) -> Result<(), QueryError> {
+    // The first 64 bits of the checksum make the lock's key.
+    let _: Vec<serde_json::Value> = client
+        ._query_raw(Raw::new(
+            r#"SELECT 1 AS "locked" FROM (SELECT pg_advisory_xact_lock(('x' || substr({}::text, 1, 16))::bit(64)::bigint)) AS l"#,
+            vec![PrismaValue::String(checksum.to_string())],
+        ))
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// Takes a lock on the package name until the end of the transaction, so
+/// that publishes of the same name check what is already there one at a
+/// time.
+pub async fn lock_package_name(client: &PrismaClient, name: &str) -> Result<(), QueryError> {
+    let _: Vec<serde_json::Value> = client
+        ._query_raw(Raw::new(
+            r#"SELECT 1 AS "locked" FROM (SELECT pg_advisory_xact_lock(hashtext('packages'), hashtext({}))) AS l"#,
+            vec![PrismaValue::String(name.to_string())],
+        ))
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// Deletes the blob behind `checksum` once no version refers to it any
+/// more. Identical files published under several records share a blob.
+pub async fn release_blob(state: &AppState, checksum: &str) -> Result<(), ApiError> {
+    let storage = state.storage.clone();
+    let checksum = checksum.to_string();
+
+    state
+        .client
+        ._transaction()
+        .run(|tx| async move {
+            lock_blob(&tx, &checksum).await?;
+
+            let references = tx
+                .package_version()
+                .count(vec![package_version::checksum::equals(checksum.clone())])
+                .exec()
+                .await?;
+
+            if references == 0 {
+                storage.delete(&checksum).await.map_err(ApiError::internal)?;
+            }
+
+            Ok::<_, ApiError>(())
+        })
+        .await
+}
+
+pub async fn discard_artifact(path: &Path) {
+    match fs::remove_file(path).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => tracing::warn!("failed to remove artifact {}: {}", path.display(), err),
+    }
+}
+
+/// Rejects identifiers that are not valid Debian package names, versions or
+/// architectures, which also keeps them safe to use as path components.
+/// Scoped names must also have a valid organization as their scope.
+pub fn validate_identity(name: &str, version: &str, architecture: &str) -> Result<(), ApiError> {
+    let (scope, debian_name) = split_scope(name);
+    let valid_name = scope.map_or(true, |scope| validate_org_name(scope, "").is_ok())
+        && debian_name.len() >= 2
+        && debian_name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && debian_name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c));
+    if !valid_name {
+        return Err(ApiError::bad_request(
+            "invalid_package_name",
+            format!("'{}' is not a valid package name", name),
+        ));
+    }
+
+    if let Err(err) = version.parse::<Version>() {
+        return Err(ApiError::bad_request("invalid_version", err.to_string()));
+    }
+
+    let valid_architecture = !architecture.is_empty()
+        && architecture
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_architecture {
+        return Err(ApiError::bad_request(
+            "invalid_architecture",
+            format!("'{}' is not a valid architecture", architecture),
+        ));
+    }
+
+    Ok(())
+}
+
+/// A dependency row derived from a control file relationship field or
+/// submitted with a `PackageRequest`.
+pub struct DependencySpec {
+    pub name: String,
+    pub version: String,
+    pub kind: DependencyType,
+    pub group: i32,
+}
+
+/// Flattens the relationship fields of a control file into dependency rows.
+/// Alternatives (`a | b`) share the same group number.
+pub fn dependency_specs(metadata: &DebMetadata) -> Vec<DependencySpec> {
+    let fields = [
+        (&metadata.depends, DependencyType::Requires),
+        (&metadata.recommends, DependencyType::Recommends),
+        (&metadata.suggests, DependencyType::Suggests),
+        (&metadata.conflicts, DependencyType::Conflicts),
+    ];
+
+    let mut specs = Vec::new();
+    let mut group = 0;
+
+    for (relations, kind) in fields {
+        for alternatives in relations {
+            for relation in alternatives {
+                specs.push(DependencySpec {
+                    name: relation.name.clone(),
+                    version: relation.constraint_string(),
+                    kind,
+                    group,
+                });
+            }
+            group += 1;
+        }
+    }
+
+    specs
+}
+
+/// Validates dependencies submitted with a `PackageRequest`. Entries that
+/// share an `alternative_group` are alternatives of each other; the others
+/// each form a group of their own.
+pub fn request_dependency_specs(requests: &[DependencyRequest]) -> Result<Vec<DependencySpec>, ApiError> {
+    let mut groups: Vec<i32> = Vec::new();
+    let mut specs = Vec::new();
+
+    for request in requests {
+        let name = request.name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(ApiError::bad_request(
+                "invalid_dependency",
+                format!("'{}' is not a valid dependency name", request.name),
+            ));
+        }
+
+        let version = request.version.trim();
+        if !version.is_empty() {
+            version.parse::<Constraint>().map_err(|err| {
+                ApiError::bad_request("invalid_dependency", format!("{}: {}", name, err))
+            })?;
+        }
+
+        let kind = match request.dependency_type.to_ascii_uppercase().as_str() {
+            "REQUIRES" => DependencyType::Requires,
+            "RECOMMENDS" => DependencyType::Recommends,
+            "SUGGESTS" => DependencyType::Suggests,
+            "CONFLICTS" => DependencyType::Conflicts,
+            _ => {
+                return Err(ApiError::bad_request(
+                    "invalid_dependency",
+                    format!("Unknown dependency type '{}'", request.dependency_type),
+                ))
+            }
+        };
+
+        // Renumber so groups are dense and never shared with ungrouped entries.
+        let group = match request.alternative_group {
+            Some(requested) => match groups.iter().position(|g| *g == requested) {
+                Some(index) => index,
+                None => {
+                    groups.push(requested);
+                    groups.len() - 1
+                }
+            },
+            None => {
+                groups.push(i32::MIN);
+                groups.len() - 1
+            }
+        };
+
+        specs.push(DependencySpec {
+            name: name.to_string(),
+            version: version.to_string(),
+            kind,
+            group: group as i32,
+        });
+    }
+
+    Ok(specs)
+}
+
+/// Replaces the dependency rows of a version. Meant to run inside a
+/// transaction so readers never see a version without its dependencies.
+pub async fn replace_dependencies(
+    client: &PrismaClient,
+    version_id: &str,
+    specs: Vec<DependencySpec>,
+) -> prisma_client_rust::Result<()> {
+    client
+        .package_dependency()
+        .delete_many(vec![package_dependency::version_id::equals(version_id.to_string())])
+        .exec()
+        .await?;
+
+    client
+        .package_dependency()
+        .create_many(
+            specs
+                .into_iter()
+                .map(|dep| {
+                    package_dependency::create_unchecked(
+                        version_id.to_string(),
+                        dep.name,
+                        dep.version,
+                        vec![
+                            package_dependency::r#type::set(dep.kind),
+                            package_dependency::alternative_group::set(dep.group),
+                        ],
+                    )
+                })
+                .collect(),
+        )
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Fetches the tags of a package for `tag_responses`.
+pub fn tags_fetch() -> package::tags::Fetch {
+    package::tags::fetch(vec![]).with(package_tag::tag::fetch())
+}
+
+/// The tags of a package fetched with `tags_fetch`, sorted by name.
+pub fn tag_responses(package: &package::Data) -> Vec<TagResponse> {
+    let mut tags: Vec<TagResponse> = package
+        .tags
+        .iter()
+        .flatten()
+        .filter_map(|package_tag| package_tag.tag().ok())
+        .map(|tag| TagResponse {
+            name: tag.name.clone(),
+            color: tag.color.clone(),
+        })
+        .collect();
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    tags
+}
+
+/// The response for one version, with its dependencies and its package's
+/// tags when they were fetched.
+pub fn version_response(package: &package::Data, version: package_version::Data) -> PackageResponse {
+    let dependencies = version
+        .dependencies
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dep| DependencyResponse {
+            name: dep.dep_name,
+            version: dep.dep_version,
+            dependency_type: format!("{:?}", dep.r#type),
+            alternative_group: dep.alternative_group,
+        })
+        .collect();
+
+    PackageResponse {
+        id: version.id,
+        name: package.name.clone(),
+        version: version.version,
+        description: version.description,
+        maintainer: version.maintainer,
+        architecture: version.architecture,
+        size: version.size,
+        checksum: version.checksum,
+        signed: version.signature.is_some(),
+        created_at: version.created_at.into(),
+        updated_at: version.updated_at.into(),
+        author: version.publisher_id,
+        dependencies,
+        tags: tag_responses(package),
+    }
+}
+
+/// The response for the newest version of a package fetched with
+/// `latest_version_fetch` (and `tags_fetch`), or None if it has no versions.
+pub fn latest_response(mut package: package::Data) -> Option<PackageResponse> {
+    let mut versions = package.versions.take()?;
+    sort_newest_first(&mut versions);
+    let version = versions.into_iter().next()?;
+    Some(version_response(&package, version))
+}
+
+/// The sort keys package listings accept.
+pub const PACKAGE_SORTS: &[SortKey] = &[
+    ("name", Direction::Asc),
+    ("updated", Direction::Desc),
+    ("downloads", Direction::Desc),
+    ("created", Direction::Desc),
+];
+
+/// One page of the packages matching `filters`, each with its newest version.
+pub async fn list_packages(
+    client: &PrismaClient,
+    mut filters: Vec<package::WhereParam>,
+    page: Page,
+    uri: &Uri,
+) -> Result<Paginated<PackageResponse>, ApiError> {
+    // Packages without versions have nothing to list. Leaving them out in
+    // the query keeps pages full and the total in step with them.
+    filters.push(package::versions::some(vec![]));
+
+    let total = client
+        .package()
+        .count(filters.clone())
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    let direction = page.direction;
+    let order = match page.sort {
+        "updated" => package::updated_at::order(direction),
+        "downloads" => package::downloads::order(direction),
+        "created" => package::created_at::order(direction),
+        _ => package::name::order(direction),
+    };
+
+    let mut query = client
+        .package()
+        .find_many(filters)
+        .with(latest_version_fetch())
+        .with(tags_fetch())
+        .order_by(order)
+        .order_by(package::id::order(direction))
+        .take(page.take());
+    if let Some(id) = page.cursor_id() {
+        query = query.cursor(package::id::equals(id)).skip(1);
+    }
+    let packages = query.exec().await.map_err(ApiError::internal)?;
+
+    Ok(page
+        .finish(packages, total, uri, |package| package.id.clone())
+        .filter_map(latest_response))
+}
+
+/// Fetches the versions of a package for `latest_response`. The newest is
+/// picked by Debian version ordering, which the database cannot do.
+pub fn latest_version_fetch() -> package::versions::Fetch {
+    package::versions::fetch(vec![]).with(package_version::dependencies::fetch(vec![]))
+}
+
+/// Sorts versions newest first by Debian version ordering. Versions that
+/// predate validation and do not parse go last, newest published first.
+pub fn sort_newest_first(versions: &mut [package_version::Data]) {
+    versions.sort_by(|a, b| {
+        match (a.version.parse::<Version>(), b.version.parse::<Version>()) {
+            (Ok(a), Ok(b)) => b.cmp(&a),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => b.created_at.cmp(&a.created_at),
+        }
+    });
+}
+
+/// Builds the response for a package artifact download, streaming it from
+/// storage with support for conditional and range requests. `name` is the
+/// Debian name the file is offered under.
+pub async fn serve_artifact(
+    state: &AppState,
+    name: &str,
+    package: &package_version::Data,
+    headers: &HeaderMap,
+) -> Result<Response, ApiError> {
+    let etag = format!("\"{}\"", package.checksum);
+    let checksum = hex::decode(&package.checksum).map_err(ApiError::internal)?;
+    let file_name = format!(
+        "{}_{}_{}.deb",
+        name,
+        strip_epoch(&package.version),
+        package.architecture
+    );
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, header_value(&etag)?);
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(
+        "digest",
+        header_value(&format!("sha-256={}", BASE64.encode(&checksum)))?,
+    );
+    response_headers.insert("x-checksum-sha256", header_value(&package.checksum)?);
+
+    if if_none_match(headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let size = state
+        .storage
+        .stat(&package.checksum)
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| {
+            ApiError::not_found(format!("The artifact for package {} is missing", package.id))
+        })?
+        .size;
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/vnd.debian.binary-package"),
+    );
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        header_value(&format!("attachment; filename=\"{}\"", file_name))?,
+    );
+
+    let (status, range) = match parse_range(headers, size, &etag) {
+        RangeRequest::Full => (StatusCode::OK, None),
+        RangeRequest::Partial(range) => {
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                header_value(&format!("bytes {}-{}/{}", range.start, range.end, size))?,
+            );
+            (StatusCode::PARTIAL_CONTENT, Some(range))
+        }
+        RangeRequest::Unsatisfiable => {
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                header_value(&format!("bytes */{}", size))?,
+            );
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        }
+    };
+
+    // Only complete downloads count; resumed ones were counted when they
+    // started.
+    if range.is_none() {
+        record_download(state, &package.package_id).await;
+    }
+
+    let length = range.map(|r| r.length()).unwrap_or(size);
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+
+    let stream = state
+        .storage
+        .stream(&package.checksum, range)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok((status, response_headers, Body::from_stream(stream)).into_response())
+}
+
+async fn record_download(state: &AppState, package_id: &str) {
+    let result = state
+        .client
+        .package()
+        .update(
+            package::id::equals(package_id.to_string()),
+            vec![package::downloads::increment(1)],
+        )
+        .exec()
+        .await;
+
+    if let Err(err) = result {
+        tracing::warn!("failed to count download of {}: {}", package_id, err);
+    }
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, ApiError> {
+    HeaderValue::from_str(value).map_err(ApiError::internal)
+}
+
@@ -0,0 +1,580 @@
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+use xz2::write::XzEncoder;
+
+use crate::{
+    config::{AppState, AptSettings},
+    db::PrismaClient,
+    prisma::{package, package_dependency, package_version, DependencyType},
+    services::{org_services::split_scope, signing_services::Signer},
+};
+
+pub struct IndexFile {
+    pub data: Bytes,
+    pub content_type: &'static str,
+}
+
+/// A generated snapshot of the `dists/` tree, keyed by the path below
+/// `dists/`, e.g. `stable/main/binary-amd64/Packages.gz`.
+#[derive(Default)]
+pub struct RepositoryIndex {
+    files: HashMap<String, IndexFile>,
+}
+
+impl RepositoryIndex {
+    pub fn get(&self, path: &str) -> Option<&IndexFile> {
+        self.files.get(path)
+    }
+
+    /// Adds `InRelease` and `Release.gpg` next to each suite's `Release`.
+    async fn sign(
+        &mut self,
+        signer: &Signer,
+        settings: &AptSettings,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let release = match self.files.get(&format!("{}/Release", settings.suite)) {
+            Some(file) => file.data.clone(),
+            None => return Ok(()),
+        };
+
+        let detached = signer.detach_sign(&release, now).await?;
+        let clearsigned = signer.clearsign(&release, now).await?;
+
+        self.files.insert(
+            format!("{}/Release.gpg", settings.suite),
+            IndexFile {
+                data: Bytes::from(detached),
+                content_type: "application/pgp-signature",
+            },
+        );
+        self.files.insert(
+            format!("{}/InRelease", settings.suite),
+            IndexFile {
+                data: Bytes::from(clearsigned),
+                content_type: "text/plain; charset=utf-8",
+            },
+        );
+
+        Ok(())
+    }
+}
+
+/// Holds the current repository index and rebuilds it in the background
+/// whenever packages change.
+pub struct Repository {
+    index: RwLock<Arc<RepositoryIndex>>,
+    pending: AtomicBool,
+    rebuild_lock: tokio::sync::Mutex<()>,
+}
+
+impl Default for Repository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repository {
+    pub fn new() -> Self {
+        Self {
+            index: RwLock::new(Arc::new(RepositoryIndex::default())),
+            pending: AtomicBool::new(false),
+            rebuild_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn current(&self) -> Arc<RepositoryIndex> {
+        self.index.read().unwrap().clone()
+    }
+
+    /// Queues a rebuild of the index. Requests made while a rebuild is
+    /// already queued are coalesced into it, since it will read the latest
+    /// state of the database when it starts.
+    pub fn schedule_rebuild(self: &Arc<Self>, state: &AppState) {
+        if self.pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let repository = self.clone();
+        let client = state.client.clone();
+        let settings = state.settings.clone();
+        let signer = state.signer.clone();
+
+        tokio::spawn(async move {
+            let _guard = repository.rebuild_lock.lock().await;
+            repository.pending.store(false, Ordering::SeqCst);
+
+            // On failure the previous index keeps being served rather than
+            // replacing a signed index with an unsigned or partial one.
+            match build_index(&client, &settings.apt, signer.as_deref()).await {
+                Ok(index) => {
+                    *repository.index.write().unwrap() = Arc::new(index);
+                    tracing::info!("apt repository index rebuilt");
+                }
+                Err(err) => tracing::error!("failed to rebuild apt repository index: {:#}", err),
+            }
+        });
+    }
+}
+
+pub async fn build_index(
+    client: &PrismaClient,
+    settings: &AptSettings,
+    signer: Option<&Signer>,
+) -> anyhow::Result<RepositoryIndex> {
+    let packages = client
+        .package()
+        .find_many(vec![])
+        .with(package::versions::fetch(vec![]).with(package_version::dependencies::fetch(vec![])))
+        .order_by(package::name::order(prisma_client_rust::Direction::Asc))
+        .exec()
+        .await?;
+
+    let now = Utc::now();
+    let mut index = render_index(settings, &packages, now);
+    if let Some(signer) = signer {
+        index.sign(signer, settings, now).await?;
+    }
+
+    Ok(index)
+}
+
+pub fn render_index(
+    settings: &AptSettings,
+    packages: &[package::Data],
+    now: DateTime<Utc>,
+) -> RepositoryIndex {
+    let versions: Vec<(&str, &package_version::Data)> = packages
+        .iter()
+        .flat_map(|p| p.versions.iter().flatten().map(move |v| (p.name.as_str(), v)))
+        .collect();
+
+    let mut architectures: BTreeSet<String> = settings.architectures.iter().cloned().collect();
+    architectures.extend(
+        versions
+            .iter()
+            .filter(|(_, v)| v.architecture != "all")
+            .map(|(_, v)| v.architecture.clone()),
+    );
+
+    // Personal packages go in the configured component and each
+    // organization's packages in a component of its own, so scoped packages
+    // with the same Debian name do not collide.
+    let mut components = vec![settings.component.as_str()];
+    for package in packages {
+        let (component, _) = component_of(settings, &package.name);
+        if !components.contains(&component) {
+            components.push(component);
+        }
+    }
+    components[1..].sort_unstable();
+
+    let mut files = HashMap::new();
+    let mut checksums = Vec::new();
+
+    for (component, architecture) in components
+        .iter()
+        .flat_map(|component| architectures.iter().map(move |architecture| (*component, architecture)))
+    {
+        let stanzas: Vec<String> = versions
+            .iter()
+            .filter(|(_, v)| &v.architecture == architecture || v.architecture == "all")
+            .filter(|(name, _)| component_of(settings, name).0 == component)
+            .map(|(name, v)| render_stanza(settings, name, v))
+            .collect();
+        let packages_file = stanzas.join("\n");
+
+        let directory = format!("{}/binary-{}", component, architecture);
+        let variants = [
+            ("Packages", Bytes::from(packages_file.clone()), "text/plain; charset=utf-8"),
+            ("Packages.gz", Bytes::from(gzip(packages_file.as_bytes())), "application/gzip"),
+            ("Packages.xz", Bytes::from(xz(packages_file.as_bytes())), "application/x-xz"),
+        ];
+
+        for (name, data, content_type) in variants {
+            let relative = format!("{}/{}", directory, name);
+            checksums.push((relative.clone(), data.clone()));
+            files.insert(
+                format!("{}/{}", settings.suite, relative),
+                IndexFile { data, content_type },
+            );
+        }
+    }
+
+    let release = render_release(settings, &components, &architectures, &checksums, now);
+    files.insert(
+        format!("{}/Release", settings.suite),
+        IndexFile {
+            data: Bytes::from(release),
+            content_type: "text/plain; charset=utf-8",
+        },
+    );
+
+    RepositoryIndex { files }
+}
+
+fn render_release(
+    settings: &AptSettings,
+    components: &[&str],
+    architectures: &BTreeSet<String>,
+    files: &[(String, Bytes)],
+    now: DateTime<Utc>,
+) -> String {
+    let mut release = String::new();
+    release.push_str(&format!("Origin: {}\n", settings.origin));
+    release.push_str(&format!("Label: {}\n", settings.label));
+    release.push_str(&format!("Suite: {}\n", settings.suite));
+    release.push_str(&format!("Codename: {}\n", settings.suite));
+    release.push_str(&format!("Date: {}\n", now.format("%a, %d %b %Y %H:%M:%S UTC")));
+    release.push_str(&format!(
+        "Architectures: {}\n",
+        architectures.iter().cloned().collect::<Vec<_>>().join(" ")
+    ));
+    release.push_str(&format!("Components: {}\n", components.join(" ")));
+
+    release.push_str("MD5Sum:\n");
+    for (path, data) in files {
+        release.push_str(&format!(" {} {} {}\n", hex::encode(Md5::digest(data)), data.len(), path));
+    }
+
+    release.push_str("SHA256:\n");
+    for (path, data) in files {
+        release.push_str(&format!(" {} {} {}\n", hex::encode(Sha256::digest(data)), data.len(), path));
+    }
+
+    release
+}
+
+fn render_stanza(settings: &AptSettings, name: &str, package: &package_version::Data) -> String {
+    let mut stanza = String::new();
+    let mut field = |key: &str, value: &str| {
+        if !value.is_empty() {
+            stanza.push_str(&format!("{}: {}\n", key, value));
+        }
+    };
+
+    let (component, name) = component_of(settings, name);
+    field("Package", name);
+    field("Version", &package.version);
+    field("Architecture", &package.architecture);
+    field("Maintainer", &package.maintainer);
+    if let Some(installed_size) = package.installed_size {
+        field("Installed-Size", &installed_size.to_string());
+    }
+
+    let dependencies = package.dependencies.as_deref().unwrap_or_default();
+    field("Depends", &render_relations(dependencies, DependencyType::Requires));
+    field("Recommends", &render_relations(dependencies, DependencyType::Recommends));
+    field("Suggests", &render_relations(dependencies, DependencyType::Suggests));
+    field("Conflicts", &render_relations(dependencies, DependencyType::Conflicts));
+
+    field("Section", package.section.as_deref().unwrap_or_default());
+    field("Priority", package.priority.as_deref().unwrap_or_default());
+    field(
+        "Filename",
+        &pool_path(component, name, &package.version, &package.architecture),
+    );
+    field("Size", &package.size.to_string());
+    field("SHA256", &package.checksum);
+    field(
+        "Description",
+        &fold_description(package.description.as_deref().unwrap_or(name)),
+    );
+
+    stanza
+}
+
+/// Renders the dependency rows of one type as a relationship field,
+/// joining rows that share an alternative group with `|`.
+fn render_relations(dependencies: &[package_dependency::Data], kind: DependencyType) -> String {
+    let mut groups: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+
+    for dependency in dependencies.iter().filter(|d| d.r#type == kind) {
+        let relation = if dependency.dep_version.is_empty() {
+            dependency.dep_name.clone()
+        } else {
+            format!("{} ({})", dependency.dep_name, dependency.dep_version)
+        };
+        groups
+            .entry(dependency.alternative_group)
+            .or_default()
+            .push(relation);
+    }
+
+    groups
+        .into_values()
+        .map(|alternatives| alternatives.join(" | "))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The inverse of the unfolding done on upload: continuation lines are
+/// indented and blank lines become ` .`.
+fn fold_description(description: &str) -> String {
+    let mut lines = description.lines();
+    let mut folded = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        folded.push('\n');
+        if line.trim().is_empty() {
+            folded.push_str(" .");
+        } else {
+            folded.push(' ');
+            folded.push_str(line);
+        }
+    }
+    folded
+}
+
+/// The apt component and Debian name a registry package is published under.
+pub fn component_of<'a>(settings: &'a AptSettings, name: &'a str) -> (&'a str, &'a str) {
+    match split_scope(name) {
+        (Some(scope), name) => (scope, name),
+        (None, name) => (settings.component.as_str(), name),
+    }
+}
+
+/// The path of a package below the repository root, following the usual
+/// `pool/<component>/<prefix>/<name>/<file>` layout.
+pub fn pool_path(component: &str, name: &str, version: &str, architecture: &str) -> String {
+    let prefix = if name.starts_with("lib") && name.len() > 3 {
+        &name[..4]
+    } else {
+        &name[..1]
+    };
+
+    format!(
+        "pool/{}/{}/{}/{}_{}_{}.deb",
+        component,
+        prefix,
+        name,
+        name,
+        strip_epoch(version),
+        architecture
+    )
+}
+
+/// Debian file names leave out the epoch of a version.
+pub fn strip_epoch(version: &str) -> &str {
+    match version.split_once(':') {
+        Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => version,
+    }
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+fn xz(data: &[u8]) -> Vec<u8> {
+    let mut encoder = XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(data).expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use flate2::read::GzDecoder;
+    use serde_json::{json, Value};
+    use std::io::Read;
+
+    fn settings() -> AptSettings {
+        AptSettings {
+            suite: "stable".to_string(),
+            component: "main".to_string(),
+            origin: "Test".to_string(),
+            label: "Test Packages".to_string(),
+            architectures: vec!["amd64".to_string()],
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap()
+    }
+
+    fn dependency(name: &str, version: &str, kind: &str, group: i32) -> Value {
+        json!({
+            "id": format!("dep-{}", name),
+            "versionId": "ver",
+            "depName": name,
+            "depVersion": version,
+            "type": kind,
+            "alternativeGroup": group,
+        })
+    }
+
+    fn version(version: &str, architecture: &str, dependencies: Vec<Value>) -> Value {
+        json!({
+            "id": format!("{}-{}", version, architecture),
+            "packageId": "pkg",
+            "version": version,
+            "description": "Says hello\nto everyone.\n\nReally.",
+            "maintainer": "Jane Doe <jane@example.com>",
+            "architecture": architecture,
+            "size": 1024,
+            "checksum": "ab".repeat(32),
+            "filePath": "blob",
+            "section": "misc",
+            "installedSize": 8,
+            "createdAt": "2026-10-17T12:00:00+00:00",
+            "updatedAt": "2026-10-17T12:00:00+00:00",
+            "publisherId": "user",
+            "dependencies": dependencies,
+        })
+    }
+
+    /// A package as `build_index` reads it, with its versions and their
+    /// dependencies.
+    fn package(name: &str, versions: Vec<Value>) -> package::Data {
+        serde_json::from_value(json!({
+            "id": name,
+            "name": name,
+            "strictVersioning": false,
+            "downloads": 0,
+            "createdAt": "2026-10-17T12:00:00+00:00",
+            "updatedAt": "2026-10-17T12:00:00+00:00",
+            "authorId": "user",
+            "versions": versions,
+        }))
+        .unwrap()
+    }
+
+    fn text(index: &RepositoryIndex, path: &str) -> String {
+        String::from_utf8(index.get(path).unwrap().data.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn renders_a_stanza_per_package() {
+        let packages = [package(
+            "libhello",
+            vec![version(
+                "1:2.0-1",
+                "amd64",
+                vec![
+                    dependency("libc6", ">= 2.34", "REQUIRES", 0),
+                    dependency("libfoo", "", "REQUIRES", 1),
+                    dependency("libbar", "<< 2.0", "REQUIRES", 1),
+                    dependency("hello-doc", "", "SUGGESTS", 0),
+                ],
+            )],
+        )];
+        let index = render_index(&settings(), &packages, now());
+
+        assert_eq!(
+            text(&index, "stable/main/binary-amd64/Packages"),
+            format!(
+                "Package: libhello\n\
+                 Version: 1:2.0-1\n\
+                 Architecture: amd64\n\
+                 Maintainer: Jane Doe <jane@example.com>\n\
+                 Installed-Size: 8\n\
+                 Depends: libc6 (>= 2.34), libfoo | libbar (<< 2.0)\n\
+                 Suggests: hello-doc\n\
+                 Section: misc\n\
+                 Filename: pool/main/libh/libhello/libhello_2.0-1_amd64.deb\n\
+                 Size: 1024\n\
+                 SHA256: {}\n\
+                 Description: Says hello\n to everyone.\n .\n Really.\n",
+                "ab".repeat(32)
+            )
+        );
+    }
+
+    #[test]
+    fn lists_architecture_all_packages_under_every_architecture() {
+        let packages = [
+            package("hello", vec![version("1.0", "amd64", vec![])]),
+            package("hello-doc", vec![version("1.0", "all", vec![])]),
+            package("tool", vec![version("1.0", "arm64", vec![])]),
+        ];
+        let index = render_index(&settings(), &packages, now());
+
+        let amd64 = text(&index, "stable/main/binary-amd64/Packages");
+        assert!(amd64.contains("Package: hello\n") && amd64.contains("Package: hello-doc\n"));
+        assert!(!amd64.contains("Package: tool\n"));
+        let arm64 = text(&index, "stable/main/binary-arm64/Packages");
+        assert!(arm64.contains("Package: tool\n") && arm64.contains("Package: hello-doc\n"));
+        assert!(!arm64.contains("Package: hello\n"));
+        assert!(index.get("stable/main/binary-all/Packages").is_none());
+        assert!(text(&index, "stable/Release").contains("Architectures: amd64 arm64\n"));
+    }
+
+    #[test]
+    fn lists_every_version_of_a_package() {
+        let packages = [package(
+            "hello",
+            vec![version("2.0", "amd64", vec![]), version("1.0", "amd64", vec![])],
+        )];
+        let index = render_index(&settings(), &packages, now());
+
+        let amd64 = text(&index, "stable/main/binary-amd64/Packages");
+        assert!(amd64.contains("Version: 2.0\n") && amd64.contains("Version: 1.0\n"));
+        assert!(amd64.contains("Filename: pool/main/h/hello/hello_1.0_amd64.deb\n"));
+    }
+
+    #[test]
+    fn renders_empty_indices_for_configured_architectures() {
+        let index = render_index(&settings(), &[], now());
+        assert_eq!(text(&index, "stable/main/binary-amd64/Packages"), "");
+        assert!(index.get("stable/main/binary-amd64/Packages.xz").is_some());
+    }
+
+    #[test]
+    fn release_lists_every_index_file_with_its_checksums() {
+        let index = render_index(&settings(), &[package("hello", vec![version("1.0", "amd64", vec![])])], now());
+        let release = text(&index, "stable/Release");
+
+        assert!(release.starts_with(
+            "Origin: Test\nLabel: Test Packages\nSuite: stable\nCodename: stable\nDate: Sat, 17 Oct 2026 12:00:00 UTC\n"
+        ));
+        for name in ["Packages", "Packages.gz", "Packages.xz"] {
+            let path = format!("main/binary-amd64/{}", name);
+            let data = &index.get(&format!("stable/{}", path)).unwrap().data;
+            for digest in [hex::encode(Md5::digest(data)), hex::encode(Sha256::digest(data))] {
+                let line = format!(" {} {} {}\n", digest, data.len(), path);
+                assert!(release.contains(&line), "{:?} is not in\n{}", line, release);
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_indices_hold_the_same_stanzas() {
+        let index = render_index(&settings(), &[package("hello", vec![version("1.0", "amd64", vec![])])], now());
+        let plain = text(&index, "stable/main/binary-amd64/Packages");
+
+        let mut gunzipped = String::new();
+        GzDecoder::new(&index.get("stable/main/binary-amd64/Packages.gz").unwrap().data[..])
+            .read_to_string(&mut gunzipped)
+            .unwrap();
+        assert_eq!(gunzipped, plain);
+
+        let mut unxzed = String::new();
+        xz2::read::XzDecoder::new(&index.get("stable/main/binary-amd64/Packages.xz").unwrap().data[..])
+            .read_to_string(&mut unxzed)
+            .unwrap();
+        assert_eq!(unxzed, plain);
+    }
+
+    #[test]
+    fn pool_paths_leave_out_the_epoch() {
+        assert_eq!(pool_path("main", "hello", "1:2.0-1", "amd64"), "pool/main/h/hello/hello_2.0-1_amd64.deb");
+        assert_eq!(pool_path("main", "libhello", "2.0", "all"), "pool/main/libh/libhello/libhello_2.0_all.deb");
+        assert_eq!(pool_path("main", "lib", "1.0", "all"), "pool/main/l/lib/lib_1.0_all.deb");
+        assert_eq!(strip_epoch("10:1.0"), "1.0");
+        assert_eq!(strip_epoch("1.0-1:2"), "1.0-1:2");
+    }
+}
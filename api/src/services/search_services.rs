@@ -0,0 +1,184 @@
+use axum::http::Uri;
+use prisma_client_rust::{Direction, PrismaValue, Raw};
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::{
+    db::PrismaClient,
+    errors::ApiError,
+    models::SearchResultResponse,
+    prisma::package,
+    services::packages_services::{latest_response, latest_version_fetch, tags_fetch},
+    utils::pagination::{Page, Paginated, SortKey},
+};
+
+/// The sort keys search accepts; results are ranked by default.
+pub const SEARCH_SORTS: &[SortKey] = &[
+    ("relevance", Direction::Desc),
+    ("name", Direction::Asc),
+    ("updated", Direction::Desc),
+    ("downloads", Direction::Desc),
+    ("created", Direction::Desc),
+];
+
+#[derive(Debug, Default)]
+pub struct SearchFilters {
+    pub architecture: Option<String>,
+    pub maintainer: Option<String>,
+    /// Only packages with a version that depends on this package.
+    pub depends: Option<String>,
+    pub tags: Vec<String>,
+    /// Keep packages with any of `tags` rather than all of them.
+    pub any_tag: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchHit {
+    /// None on the single row returned when nothing matches, which only
+    /// carries the total.
+    id: Option<String>,
+    relevance: Option<f64>,
+    snippet: Option<String>,
+    total: i32,
+}
+
+/// Matches `text` against the weighted `searchVector` of each package
+/// (name, tags, description, maintainers; see the `package_search`
+/// migration) or, for typos, the trigram similarity of the name. Relevance
+/// adds the text rank to the name similarity, and an exact name match
+/// outranks everything else.
+///
+/// Pages by keyset on the sort key and id: the cursor row's key is looked
+/// up again, so cursors stay valid while the ranking is computed per query.
+const SEARCH_QUERY: &str = r#"
+WITH query AS (
+    SELECT
+        params.*,
+        websearch_to_tsquery('simple', params.text) || websearch_to_tsquery('english', params.text) AS ts
+    FROM (
+        SELECT
+            {}::text AS text,
+            {}::text AS architecture,
+            {}::text AS maintainer,
+            {}::text AS depends,
+            string_to_array({}::text, ',') AS tags,
+            {}::boolean AS any_tag,
+            {}::text AS cursor
+    ) params
+),
+matches AS (
+    SELECT
+        p."id",
+        p."name",
+        p."description",
+        p."updatedAt",
+        p."createdAt",
+        p."downloads",
+        (ts_rank_cd(p."searchVector", query.ts, 32)
+            + similarity(p."name", query.text)
+            + CASE WHEN lower(p."name") = lower(query.text) THEN 10 ELSE 0 END)::float8 AS relevance
+    FROM "packages" p, query
+    WHERE (p."searchVector" @@ query.ts OR p."name" % query.text)
+      AND (query.architecture IS NULL OR EXISTS (
+          SELECT 1 FROM "package_versions" v
+          WHERE v."packageId" = p."id" AND v."architecture" IN (query.architecture, 'all')))
+      AND (query.maintainer IS NULL OR EXISTS (
+          SELECT 1 FROM "package_versions" v
+          WHERE v."packageId" = p."id" AND v."maintainer" = query.maintainer))
+      AND (query.depends IS NULL OR EXISTS (
+          SELECT 1 FROM "package_versions" v JOIN "package_dependencies" d ON d."versionId" = v."id"
+          WHERE v."packageId" = p."id" AND d."depName" = query.depends))
+      AND (query.tags IS NULL OR (
+          SELECT count(*) FROM "package_tags" pt JOIN "tags" t ON t."id" = pt."tagId"
+          WHERE pt."packageId" = p."id" AND t."name" = ANY(query.tags)
+      ) >= CASE WHEN query.any_tag THEN 1 ELSE cardinality(query.tags) END)
+),
+page AS (
+    SELECT m.*
+    FROM matches m, query
+    WHERE query.cursor IS NULL
+       OR (m.$KEY, m."id") $COMPARE (SELECT c.$KEY, c."id" FROM matches c WHERE c."id" = query.cursor)
+    ORDER BY m.$KEY $DIRECTION, m."id" $DIRECTION
+    LIMIT $LIMIT
+)
+SELECT
+    page."id",
+    page.relevance,
+    ts_headline('english', coalesce(page."description", ''), query.ts,
+        'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=24, MinWords=8') AS snippet,
+    (SELECT count(*) FROM matches)::int AS total
+FROM query LEFT JOIN page ON true
+ORDER BY page.$KEY $DIRECTION, page."id" $DIRECTION
+"#;
+
+/// One page of packages matching `text`, each with its newest version, its
+/// relevance and a snippet of its description with the matches marked.
+pub async fn search_packages(
+    client: &PrismaClient,
+    text: String,
+    filters: SearchFilters,
+    page: Page,
+    uri: &Uri,
+) -> Result<Paginated<SearchResultResponse>, ApiError> {
+    let key = match page.sort {
+        "name" => r#""name""#,
+        "updated" => r#""updatedAt""#,
+        "downloads" => r#""downloads""#,
+        "created" => r#""createdAt""#,
+        _ => "relevance",
+    };
+    // A `prev` page is read walking back from the cursor, then reversed.
+    let descending = matches!(page.direction, Direction::Desc) != page.is_backward();
+    let (compare, direction) = if descending { ("<", "DESC") } else { (">", "ASC") };
+
+    let sql = SEARCH_QUERY
+        .replace("$KEY", key)
+        .replace("$COMPARE", compare)
+        .replace("$DIRECTION", direction)
+        .replace("$LIMIT", &page.take().abs().to_string());
+    let optional = |value: Option<String>| value.map_or(PrismaValue::Null, PrismaValue::String);
+    let params = vec![
+        PrismaValue::String(text),
+        optional(filters.architecture),
+        optional(filters.maintainer),
+        optional(filters.depends),
+        optional((!filters.tags.is_empty()).then(|| filters.tags.join(","))),
+        PrismaValue::Boolean(filters.any_tag),
+        optional(page.cursor_id()),
+    ];
+
+    let mut hits: Vec<SearchHit> = client
+        ._query_raw(Raw::new(&sql, params))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?;
+
+    let total = hits.first().map_or(0, |hit| i64::from(hit.total));
+    hits.retain(|hit| hit.id.is_some());
+    if page.is_backward() {
+        hits.reverse();
+    }
+
+    let hits = page.finish(hits, total, uri, |hit| hit.id.clone().unwrap_or_default());
+
+    let ids = hits.items.iter().filter_map(|hit| hit.id.clone()).collect();
+    let mut packages: HashMap<String, package::Data> = client
+        .package()
+        .find_many(vec![package::id::in_vec(ids)])
+        .with(latest_version_fetch())
+        .with(tags_fetch())
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .into_iter()
+        .map(|package| (package.id.clone(), package))
+        .collect();
+
+    Ok(hits.filter_map(|hit| {
+        let package = packages.remove(hit.id.as_deref()?)?;
+        Some(SearchResultResponse {
+            package: latest_response(package)?,
+            relevance: hit.relevance.unwrap_or_default(),
+            snippet: hit.snippet.filter(|snippet| !snippet.is_empty()),
+        })
+    }))
+}
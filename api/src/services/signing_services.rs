@@ -0,0 +1,205 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::{fs, io::AsyncWriteExt, process::Command};
+
+use crate::config::SigningSettings;
+
+/// A key imported into the signer's keyring. Keys with a `retire_after`
+/// time stop signing once it has passed, which lets a previous key keep
+/// signing alongside its replacement while clients pick up the new one.
+#[derive(Debug, Clone)]
+pub struct SigningKey {
+    pub fingerprint: String,
+    pub retire_after: Option<DateTime<Utc>>,
+}
+
+impl SigningKey {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.retire_after.is_none_or(|until| now < until)
+    }
+}
+
+/// Signs repository metadata with OpenPGP keys by driving `gpg` against a
+/// keyring private to the registry.
+pub struct Signer {
+    homedir: PathBuf,
+    passphrase_file: Option<PathBuf>,
+    keys: Vec<SigningKey>,
+}
+
+impl Signer {
+    /// Imports the configured keys into a keyring under `homedir`. Returns
+    /// `None` when no signing key is configured.
+    pub async fn load(settings: &SigningSettings, homedir: PathBuf) -> Result<Option<Self>> {
+        let Some(key_path) = &settings.key_path else {
+            return Ok(None);
+        };
+
+        fs::create_dir_all(&homedir).await?;
+        fs::set_permissions(&homedir, std::fs::Permissions::from_mode(0o700)).await?;
+
+        let mut signer = Self {
+            homedir,
+            passphrase_file: settings.passphrase_file.clone(),
+            keys: Vec::new(),
+        };
+
+        let fingerprint = signer.import(key_path).await?;
+        signer.keys.push(SigningKey {
+            fingerprint,
+            retire_after: None,
+        });
+
+        if let Some(previous_path) = &settings.previous_key_path {
+            let fingerprint = signer.import(previous_path).await?;
+            signer.keys.push(SigningKey {
+                fingerprint,
+                retire_after: settings.previous_key_until,
+            });
+        }
+
+        for key in &signer.keys {
+            tracing::info!(
+                "loaded repository signing key {}{}",
+                key.fingerprint,
+                key.retire_after
+                    .map(|until| format!(" (signing until {})", until.to_rfc3339()))
+                    .unwrap_or_default()
+            );
+        }
+
+        Ok(Some(signer))
+    }
+
+    pub fn active_keys(&self, now: DateTime<Utc>) -> Vec<&SigningKey> {
+        self.keys.iter().filter(|key| key.is_active(now)).collect()
+    }
+
+    /// The armored public keys of every currently active signing key.
+    pub async fn public_keys(&self, now: DateTime<Utc>) -> Result<Vec<u8>> {
+        let mut args = vec!["--armor", "--export"];
+        let active = self.active_keys(now);
+        args.extend(active.iter().map(|key| key.fingerprint.as_str()));
+        self.gpg(&args, None).await
+    }
+
+    /// A detached, armored signature over `data`, as used for `Release.gpg`.
+    pub async fn detach_sign(&self, data: &[u8], now: DateTime<Utc>) -> Result<Vec<u8>> {
+        self.sign(&["--armor", "--detach-sign"], Input::Data(data), now).await
+    }
+
+    /// A detached, armored signature over the file at `path`, which gpg
+    /// reads itself so that large artifacts are never held in memory.
+    pub async fn detach_sign_file(&self, path: &Path, now: DateTime<Utc>) -> Result<Vec<u8>> {
+        self.sign(&["--armor", "--detach-sign"], Input::File(path), now).await
+    }
+
+    /// A clearsigned copy of `data`, as used for `InRelease`.
+    pub async fn clearsign(&self, data: &[u8], now: DateTime<Utc>) -> Result<Vec<u8>> {
+        self.sign(&["--clearsign"], Input::Data(data), now).await
+    }
+
+    async fn sign(&self, mode: &[&str], input: Input<'_>, now: DateTime<Utc>) -> Result<Vec<u8>> {
+        let active = self.active_keys(now);
+        if active.is_empty() {
+            bail!("no active signing key");
+        }
+
+        let passphrase_file = self
+            .passphrase_file
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned());
+
+        let mut args = vec!["--pinentry-mode", "loopback", "--digest-algo", "SHA512"];
+        if let Some(passphrase_file) = &passphrase_file {
+            args.extend(["--passphrase-file", passphrase_file.as_str()]);
+        }
+        for key in &active {
+            args.extend(["--local-user", key.fingerprint.as_str()]);
+        }
+        args.extend(mode);
+        args.extend(["--output", "-"]);
+
+        match input {
+            Input::Data(data) => self.gpg(&args, Some(data)).await,
+            Input::File(path) => {
+                let path = path.to_string_lossy();
+                args.extend(["--", path.as_ref()]);
+                self.gpg(&args, None).await
+            }
+        }
+    }
+
+    /// Imports an armored secret key and returns its primary fingerprint.
+    async fn import(&self, path: &Path) -> Result<String> {
+        let path_arg = path.to_string_lossy();
+        let listing = self
+            .gpg(
+                &["--with-colons", "--import-options", "show-only", "--import", &path_arg],
+                None,
+            )
+            .await
+            .with_context(|| format!("failed to read signing key {}", path.display()))?;
+
+        let listing = String::from_utf8_lossy(&listing);
+        let fingerprint = listing
+            .lines()
+            .find(|line| line.starts_with("fpr:"))
+            .and_then(|line| line.split(':').nth(9))
+            .filter(|fpr| !fpr.is_empty())
+            .ok_or_else(|| anyhow!("{} does not contain an OpenPGP key", path.display()))?
+            .to_string();
+
+        if !listing.lines().any(|line| line.starts_with("sec:")) {
+            bail!("{} does not contain a secret key", path.display());
+        }
+
+        self.gpg(&["--import", &path_arg], None)
+            .await
+            .with_context(|| format!("failed to import signing key {}", path.display()))?;
+
+        Ok(fingerprint)
+    }
+
+    async fn gpg(&self, args: &[&str], input: Option<&[u8]>) -> Result<Vec<u8>> {
+        let mut child = Command::new("gpg")
+            .arg("--homedir")
+            .arg(&self.homedir)
+            .args(["--batch", "--yes", "--no-tty"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("failed to run gpg")?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = input.map(<[u8]>::to_vec);
+        let writer = tokio::spawn(async move {
+            if let Some(input) = input {
+                stdin.write_all(&input).await?;
+            }
+            stdin.shutdown().await
+        });
+
+        let output = child.wait_with_output().await?;
+        writer.await??;
+
+        if !output.status.success() {
+            bail!("gpg failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+/// What `Signer::sign` signs.
+enum Input<'a> {
+    Data(&'a [u8]),
+    File(&'a Path),
+}
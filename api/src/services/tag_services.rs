@@ -0,0 +1,183 @@
+use crate::{
+    db::PrismaClient,
+    errors::ApiError,
+    prisma::{package_tag, tag},
+};
+
+/// Tag names are lowercase letters, digits and hyphens so that the same
+/// tag is not created twice with different spellings.
+pub fn normalize_tag(name: &str) -> Result<String, ApiError> {
+    let normalized = name.trim().to_ascii_lowercase();
+    let valid = (1..=32).contains(&normalized.len())
+        && normalized.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && normalized.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid {
+        return Err(ApiError::bad_request(
+            "invalid_tag",
+            format!("'{}' is not a valid tag; use up to 32 letters, digits and '-'", name),
+        ));
+    }
+    Ok(normalized)
+}
+
+/// Normalizes submitted tag names and drops duplicates.
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = normalize_tag(tag)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Ok(normalized)
+}
+
+/// Colors are `#rrggbb`, as the web UI renders them directly.
+pub fn validate_color(color: &str) -> Result<(), ApiError> {
+    let valid = color
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+    if !valid {
+        return Err(ApiError::bad_request(
+            "invalid_color",
+            format!("'{}' is not a color of the form #rrggbb", color),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn find_tag(client: &PrismaClient, name: &str) -> Result<tag::Data, ApiError> {
+    client
+        .tag()
+        .find_unique(tag::name::equals(name.to_ascii_lowercase()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("Tag {} does not exist", name)))
+}
+
+/// Replaces the tags of a package, creating tags that do not exist yet.
+pub async fn replace_tags(
+    client: &PrismaClient,
+    package_id: &str,
+    names: &[String],
+) -> prisma_client_rust::Result<()> {
+    let mut tag_ids = Vec::with_capacity(names.len());
+    for name in names {
+        let tag = client
+            .tag()
+            .upsert(
+                tag::name::equals(name.clone()),
+                tag::create(name.clone(), vec![]),
+                vec![],
+            )
+            .exec()
+            .await?;
+        tag_ids.push(tag.id);
+    }
+
+    client
+        .package_tag()
+        .delete_many(vec![package_tag::package_id::equals(package_id.to_string())])
+        .exec()
+        .await?;
+
+    client
+        .package_tag()
+        .create_many(
+            tag_ids
+                .into_iter()
+                .map(|tag_id| package_tag::create_unchecked(package_id.to_string(), tag_id, vec![]))
+                .collect(),
+        )
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Tags a package, creating the tag if it does not exist yet.
+pub async fn add_tag(
+    client: &PrismaClient,
+    package_id: &str,
+    name: &str,
+) -> prisma_client_rust::Result<()> {
+    let tag = client
+        .tag()
+        .upsert(
+            tag::name::equals(name.to_string()),
+            tag::create(name.to_string(), vec![]),
+            vec![],
+        )
+        .exec()
+        .await?;
+
+    client
+        .package_tag()
+        .upsert(
+            package_tag::package_id_tag_id(package_id.to_string(), tag.id.clone()),
+            package_tag::create_unchecked(package_id.to_string(), tag.id, vec![]),
+            vec![],
+        )
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn remove_tag(
+    client: &PrismaClient,
+    package_id: &str,
+    tag_id: &str,
+) -> prisma_client_rust::Result<()> {
+    client
+        .package_tag()
+        .delete_many(vec![
+            package_tag::package_id::equals(package_id.to_string()),
+            package_tag::tag_id::equals(tag_id.to_string()),
+        ])
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Moves every package tagged `from` over to `into` and deletes `from`.
+/// Packages that already carry both keep a single `into` tag.
+pub async fn merge_tags(
+    client: &PrismaClient,
+    from: tag::Data,
+    into: tag::Data,
+) -> prisma_client_rust::Result<()> {
+    client
+        ._transaction()
+        .run(|tx| async move {
+            let tagged = tx
+                .package_tag()
+                .find_many(vec![package_tag::tag_id::equals(into.id.clone())])
+                .exec()
+                .await?;
+            let already_tagged: Vec<String> = tagged.into_iter().map(|t| t.package_id).collect();
+
+            tx.package_tag()
+                .update_many(
+                    vec![
+                        package_tag::tag_id::equals(from.id.clone()),
+                        package_tag::package_id::not_in_vec(already_tagged),
+                    ],
+                    vec![package_tag::tag_id::set(into.id)],
+                )
+                .exec()
+                .await?;
+
+            // Whatever still points at `from` duplicates an `into` tag and
+            // goes with it.
+            tx.tag()
+                .delete(tag::id::equals(from.id))
+                .exec()
+                .await?;
+
+            Ok::<_, prisma_client_rust::QueryError>(())
+        })
+        .await
+}
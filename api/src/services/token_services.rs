@@ -0,0 +1,156 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    db::PrismaClient,
+    errors::ApiError,
+    prisma::{api_token, user},
+    services::auth_services::{hash_token, random_token, Caller, Credential, Scope, TokenError},
+};
+
+/// API token secrets start with this, which tells them apart from JWTs and
+/// makes leaked tokens easy to spot.
+pub const TOKEN_PREFIX: &str = "cpk_";
+
+/// Number of leading secret characters kept in clear so tokens can be told
+/// apart in listings.
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// `last_used_at` is only written when it is older than this, so a busy CI
+/// job does not turn every request into a write.
+const LAST_USED_RESOLUTION: i64 = 60;
+
+pub struct CreatedApiToken {
+    pub token: api_token::Data,
+    pub secret: String,
+}
+
+pub fn parse_scopes(values: &[String]) -> Result<Vec<Scope>, ApiError> {
+    if values.is_empty() {
+        return Err(ApiError::bad_request(
+            "invalid_scope",
+            "At least one scope is required",
+        ));
+    }
+
+    let mut scopes = Vec::new();
+    for value in values {
+        let scope = Scope::parse(value).ok_or_else(|| {
+            ApiError::bad_request("invalid_scope", format!("Unknown scope '{}'", value))
+        })?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    Ok(scopes)
+}
+
+pub async fn create_api_token(
+    client: &PrismaClient,
+    owner_id: &str,
+    name: String,
+    scopes: &[Scope],
+    package_pattern: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<CreatedApiToken> {
+    let secret = format!("{}{}", TOKEN_PREFIX, random_token());
+
+    let token = client
+        .api_token()
+        .create(
+            name,
+            hash_token(&secret),
+            secret[..DISPLAY_PREFIX_LEN].to_string(),
+            user::id::equals(owner_id.to_string()),
+            vec![
+                api_token::scopes::set(scopes.iter().map(|s| s.as_str().to_string()).collect()),
+                api_token::package_pattern::set(package_pattern),
+                api_token::expires_at::set(expires_at.map(Into::into)),
+            ],
+        )
+        .exec()
+        .await?;
+
+    Ok(CreatedApiToken { token, secret })
+}
+
+/// The tokens of `owner_id` that have not been revoked, newest first.
+pub async fn list_api_tokens(client: &PrismaClient, owner_id: &str) -> Result<Vec<api_token::Data>> {
+    Ok(client
+        .api_token()
+        .find_many(vec![
+            api_token::owner_id::equals(owner_id.to_string()),
+            api_token::revoked_at::equals(None),
+        ])
+        .order_by(api_token::created_at::order(prisma_client_rust::Direction::Desc))
+        .exec()
+        .await?)
+}
+
+/// Revokes a token of `owner_id`. Returns false if there was no such token.
+pub async fn revoke_api_token(client: &PrismaClient, owner_id: &str, id: &str) -> Result<bool> {
+    let revoked = client
+        .api_token()
+        .update_many(
+            vec![
+                api_token::id::equals(id.to_string()),
+                api_token::owner_id::equals(owner_id.to_string()),
+                api_token::revoked_at::equals(None),
+            ],
+            vec![api_token::revoked_at::set(Some(Utc::now().into()))],
+        )
+        .exec()
+        .await?;
+
+    Ok(revoked > 0)
+}
+
+/// Resolves an API token secret to the caller it authenticates.
+pub async fn authenticate_api_token(
+    client: &PrismaClient,
+    secret: &str,
+) -> Result<Result<Caller, TokenError>> {
+    let token = client
+        .api_token()
+        .find_unique(api_token::token_hash::equals(hash_token(secret)))
+        .with(api_token::owner::fetch())
+        .exec()
+        .await?;
+
+    let Some(token) = token else {
+        return Ok(Err(TokenError::UnknownApiToken));
+    };
+    if token.revoked_at.is_some() {
+        return Ok(Err(TokenError::Revoked));
+    }
+
+    let now = Utc::now();
+    if token.expires_at.is_some_and(|expires_at| expires_at < now) {
+        return Ok(Err(TokenError::Expired));
+    }
+
+    let stale = token
+        .last_used_at
+        .is_none_or(|used| now.signed_duration_since(used) > Duration::seconds(LAST_USED_RESOLUTION));
+    if stale {
+        client
+            .api_token()
+            .update(
+                api_token::id::equals(token.id.clone()),
+                vec![api_token::last_used_at::set(Some(now.into()))],
+            )
+            .exec()
+            .await?;
+    }
+
+    let owner = token.owner()?;
+    Ok(Ok(Caller {
+        user_id: token.owner_id.clone(),
+        role: format!("{:?}", owner.role),
+        credential: Credential::ApiToken {
+            id: token.id.clone(),
+            scopes: token.scopes.iter().filter_map(|s| Scope::parse(s)).collect(),
+            package_pattern: token.package_pattern.clone(),
+        },
+    }))
+}
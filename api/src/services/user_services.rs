@@ -0,0 +1,27 @@
+use prisma_client_rust::{
+    prisma_errors::query_engine::{RecordNotFound, UniqueKeyViolation},
+    QueryError,
+};
+use crate::{db::PrismaClient, errors::ApiError, prisma::user};
+
+pub async fn find_user_by_username(client: &PrismaClient, username: &str) -> Result<user::Data, ApiError> {
+    client
+        .user()
+        .find_unique(user::username::equals(username.to_string()))
+        .exec()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(format!("User {} does not exist", username)))
+}
+
+/// The error for a failed create, update or delete of a user: a taken
+/// username or email is a conflict, a missing user is not found.
+pub fn user_write_error(err: QueryError) -> ApiError {
+    if err.is_prisma_error::<UniqueKeyViolation>() {
+        ApiError::conflict("user_exists", "The username or email is already taken")
+    } else if err.is_prisma_error::<RecordNotFound>() {
+        ApiError::not_found("The user does not exist")
+    } else {
+        ApiError::internal(err)
+    }
+}
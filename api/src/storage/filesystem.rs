@@ -0,0 +1,104 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::{
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+use super::{blob_key, BlobStat, BlobStorage, BlobStream, StorageError, StorageResult};
+use crate::utils::ByteRange;
+
+pub struct FilesystemStorage {
+    root: PathBuf,
+}
+
+impl FilesystemStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, digest: &str) -> StorageResult<PathBuf> {
+        Ok(self.root.join(blob_key(digest)?))
+    }
+}
+
+fn not_found(digest: &str, err: io::Error) -> StorageError {
+    match err.kind() {
+        io::ErrorKind::NotFound => StorageError::NotFound(digest.to_string()),
+        _ => StorageError::Io(err),
+    }
+}
+
+#[async_trait]
+impl BlobStorage for FilesystemStorage {
+    async fn put(&self, digest: &str, source: &Path) -> StorageResult<()> {
+        let destination = self.path(digest)?;
+
+        if fs::try_exists(&destination).await? {
+            fs::remove_file(source).await?;
+            return Ok(());
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // Renaming fails across filesystems, in which case copy to a
+        // temporary name next to the destination and rename that instead.
+        if fs::rename(source, &destination).await.is_err() {
+            let partial = destination.with_extension("partial");
+            fs::copy(source, &partial).await?;
+            fs::rename(&partial, &destination).await?;
+            fs::remove_file(source).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, digest: &str) -> StorageResult<Bytes> {
+        let path = self.path(digest)?;
+        let data = fs::read(&path).await.map_err(|err| not_found(digest, err))?;
+        Ok(Bytes::from(data))
+    }
+
+    async fn stream(&self, digest: &str, range: Option<ByteRange>) -> StorageResult<BlobStream> {
+        let path = self.path(digest)?;
+        let mut file = fs::File::open(&path)
+            .await
+            .map_err(|err| not_found(digest, err))?;
+
+        match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(range.start)).await?;
+                Ok(ReaderStream::new(file.take(range.length())).boxed())
+            }
+            None => Ok(ReaderStream::new(file).boxed()),
+        }
+    }
+
+    async fn delete(&self, digest: &str) -> StorageResult<()> {
+        let path = self.path(digest)?;
+        match fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn stat(&self, digest: &str) -> StorageResult<Option<BlobStat>> {
+        let path = self.path(digest)?;
+        match fs::metadata(&path).await {
+            Ok(metadata) => Ok(Some(BlobStat {
+                size: metadata.len(),
+            })),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+mod filesystem;
+mod s3;
+
+pub use filesystem::FilesystemStorage;
+pub use s3::S3Storage;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use std::{fmt, io, path::Path, sync::Arc};
+
+use crate::{
+    config::{Settings, StorageBackend},
+    utils::ByteRange,
+};
+
+pub type BlobStream = BoxStream<'static, io::Result<Bytes>>;
+
+#[derive(Debug)]
+pub enum StorageError {
+    NotFound(String),
+    InvalidDigest(String),
+    Io(io::Error),
+    Backend(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NotFound(digest) => write!(f, "blob {} not found", digest),
+            StorageError::InvalidDigest(digest) => write!(f, "'{}' is not a SHA-256 digest", digest),
+            StorageError::Io(err) => write!(f, "storage I/O error: {}", err),
+            StorageError::Backend(message) => write!(f, "storage backend error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlobStat {
+    pub size: u64,
+}
+
+/// Content-addressed storage for package artifacts. Blobs are keyed by the
+/// lowercase hex SHA-256 of their contents, so publishing the same file
+/// twice stores it once.
+#[async_trait]
+pub trait BlobStorage: Send + Sync {
+    /// Moves the local file at `source` into storage under `digest`. The
+    /// source is removed in both the stored and already-present cases.
+    async fn put(&self, digest: &str, source: &Path) -> StorageResult<()>;
+
+    /// Reads a whole blob into memory. Only meant for small blobs.
+    async fn get(&self, digest: &str) -> StorageResult<Bytes>;
+
+    /// Streams a blob, or the given inclusive byte range of it.
+    async fn stream(&self, digest: &str, range: Option<ByteRange>) -> StorageResult<BlobStream>;
+
+    /// Deletes a blob. Deleting a missing blob is not an error.
+    async fn delete(&self, digest: &str) -> StorageResult<()>;
+
+    async fn stat(&self, digest: &str) -> StorageResult<Option<BlobStat>>;
+
+    async fn exists(&self, digest: &str) -> StorageResult<bool> {
+        Ok(self.stat(digest).await?.is_some())
+    }
+}
+
+/// The storage key of a blob: `sha256/ab/cd/abcd…`.
+pub fn blob_key(digest: &str) -> StorageResult<String> {
+    let valid = digest.len() == 64
+        && digest
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+    if !valid {
+        return Err(StorageError::InvalidDigest(digest.to_string()));
+    }
+
+    Ok(format!("sha256/{}/{}/{}", &digest[..2], &digest[2..4], digest))
+}
+
+pub fn from_settings(settings: &Settings) -> StorageResult<Arc<dyn BlobStorage>> {
+    match settings.storage_backend {
+        StorageBackend::Filesystem => Ok(Arc::new(FilesystemStorage::new(
+            settings.storage_path.join("blobs"),
+        ))),
+        StorageBackend::S3 => {
+            let s3 = settings.s3.as_ref().ok_or_else(|| {
+                StorageError::Backend("STORAGE_BACKEND=s3 requires S3_BUCKET to be set".to_string())
+            })?;
+            Ok(Arc::new(S3Storage::new(s3)?))
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use object_store::{
+    aws::{AmazonS3, AmazonS3Builder},
+    path::Path as ObjectPath,
+    GetOptions, GetRange, ObjectStore, WriteMultipart,
+};
+use std::{io, path::Path};
+use tokio::{fs, io::AsyncReadExt};
+
+use super::{blob_key, BlobStat, BlobStorage, BlobStream, StorageError, StorageResult};
+use crate::{config::S3Settings, utils::ByteRange};
+
+/// Size of the parts sent during a multipart upload.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Number of parts allowed in flight at once while uploading.
+const MAX_CONCURRENT_PARTS: usize = 4;
+
+/// Stores blobs in an S3-compatible object store such as AWS S3 or MinIO.
+pub struct S3Storage {
+    store: AmazonS3,
+}
+
+impl S3Storage {
+    pub fn new(settings: &S3Settings) -> StorageResult<Self> {
+        let mut builder = AmazonS3Builder::new()
+            .with_bucket_name(&settings.bucket)
+            .with_region(&settings.region)
+            .with_allow_http(settings.allow_http);
+
+        if let Some(endpoint) = &settings.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(access_key_id) = &settings.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &settings.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+
+        let store = builder.build().map_err(backend_error)?;
+        Ok(Self { store })
+    }
+
+    fn location(&self, digest: &str) -> StorageResult<ObjectPath> {
+        Ok(ObjectPath::from(blob_key(digest)?))
+    }
+}
+
+fn backend_error(err: object_store::Error) -> StorageError {
+    StorageError::Backend(err.to_string())
+}
+
+fn map_error(digest: &str, err: object_store::Error) -> StorageError {
+    match err {
+        object_store::Error::NotFound { .. } => StorageError::NotFound(digest.to_string()),
+        err => backend_error(err),
+    }
+}
+
+#[async_trait]
+impl BlobStorage for S3Storage {
+    async fn put(&self, digest: &str, source: &Path) -> StorageResult<()> {
+        if self.exists(digest).await? {
+            fs::remove_file(source).await?;
+            return Ok(());
+        }
+
+        let location = self.location(digest)?;
+        let upload = self
+            .store
+            .put_multipart(&location)
+            .await
+            .map_err(backend_error)?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, PART_SIZE);
+
+        let mut file = fs::File::open(source).await?;
+        let mut buffer = vec![0u8; PART_SIZE];
+        loop {
+            let read = match file.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) => {
+                    let _ = writer.abort().await;
+                    return Err(err.into());
+                }
+            };
+            if let Err(err) = writer.wait_for_capacity(MAX_CONCURRENT_PARTS).await {
+                let _ = writer.abort().await;
+                return Err(backend_error(err));
+            }
+            writer.write(&buffer[..read]);
+        }
+
+        writer.finish().await.map_err(backend_error)?;
+        fs::remove_file(source).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, digest: &str) -> StorageResult<Bytes> {
+        let location = self.location(digest)?;
+        let result = self
+            .store
+            .get(&location)
+            .await
+            .map_err(|err| map_error(digest, err))?;
+        result.bytes().await.map_err(|err| map_error(digest, err))
+    }
+
+    async fn stream(&self, digest: &str, range: Option<ByteRange>) -> StorageResult<BlobStream> {
+        let location = self.location(digest)?;
+        let options = GetOptions {
+            range: range.map(|r| GetRange::Bounded(r.start as usize..r.end as usize + 1)),
+            ..Default::default()
+        };
+
+        let result = self
+            .store
+            .get_opts(&location, options)
+            .await
+            .map_err(|err| map_error(digest, err))?;
+
+        Ok(result.into_stream().map_err(io::Error::from).boxed())
+    }
+
+    async fn delete(&self, digest: &str) -> StorageResult<()> {
+        let location = self.location(digest)?;
+        match self.store.delete(&location).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(backend_error(err)),
+        }
+    }
+
+    async fn stat(&self, digest: &str) -> StorageResult<Option<BlobStat>> {
+        let location = self.location(digest)?;
+        match self.store.head(&location).await {
+            Ok(meta) => Ok(Some(BlobStat {
+                size: meta.size as u64,
+            })),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(err) => Err(backend_error(err)),
+        }
+    }
+}
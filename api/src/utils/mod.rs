@@ -0,0 +1,263 @@
+pub mod pagination;
+
+use axum::http::{header, HeaderMap};
+
+/// A satisfiable byte range, inclusive of both ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// No usable `Range` header; serve the whole representation.
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
+
+/// Interprets the `Range` and `If-Range` headers of a request for a
+/// representation of `size` bytes. Only single `bytes` ranges are honoured;
+/// anything else falls back to a full response as RFC 9110 allows.
+pub fn parse_range(headers: &HeaderMap, size: u64, etag: &str) -> RangeRequest {
+    let Some(range) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+        return RangeRequest::Full;
+    };
+
+    if let Some(if_range) = headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+        if if_range.trim() != etag {
+            return RangeRequest::Full;
+        }
+    }
+
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return RangeRequest::Full,
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(suffix) if size > 0 => ByteRange {
+                start: size.saturating_sub(suffix),
+                end: size - 1,
+            },
+            Ok(_) => return RangeRequest::Unsatisfiable,
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+            let end = match end {
+                "" => size.saturating_sub(1),
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(size.saturating_sub(1)),
+                    _ => return RangeRequest::Full,
+                },
+            };
+            if start >= size {
+                return RangeRequest::Unsatisfiable;
+            }
+            ByteRange { start, end }
+        }
+    };
+
+    RangeRequest::Partial(range)
+}
+
+/// Whether an `If-None-Match` header matches the given strong entity tag,
+/// using the weak comparison the header calls for.
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// Matches `text` against a shell-style pattern where `*` stands for any
+/// run of characters and `?` for exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` absorb one more character and retry.
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const ETAG: &str = "\"abc123\"";
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn range(value: &str, size: u64) -> RangeRequest {
+        parse_range(&headers(&[(header::RANGE, value)]), size, ETAG)
+    }
+
+    fn partial(start: u64, end: u64) -> RangeRequest {
+        RangeRequest::Partial(ByteRange { start, end })
+    }
+
+    #[test]
+    fn serves_everything_without_a_range() {
+        assert_eq!(parse_range(&HeaderMap::new(), 100, ETAG), RangeRequest::Full);
+    }
+
+    #[test]
+    fn parses_bounded_and_open_ranges() {
+        assert_eq!(range("bytes=0-9", 100), partial(0, 9));
+        assert_eq!(range("bytes=90-", 100), partial(90, 99));
+        assert_eq!(range("bytes= 10 - 19 ", 100), partial(10, 19));
+        assert_eq!(range("bytes=99-99", 100), partial(99, 99));
+        assert_eq!(ByteRange { start: 10, end: 19 }.length(), 10);
+    }
+
+    #[test]
+    fn clamps_the_end_to_the_last_byte() {
+        assert_eq!(range("bytes=50-1000", 100), partial(50, 99));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(range("bytes=-10", 100), partial(90, 99));
+        assert_eq!(range("bytes=-1000", 100), partial(0, 99));
+        assert_eq!(range("bytes=-0", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(range("bytes=-10", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn refuses_ranges_starting_past_the_end() {
+        assert_eq!(range("bytes=100-", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(range("bytes=100-200", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn ignores_ranges_it_does_not_serve() {
+        for value in ["items=0-9", "bytes=0-9,20-29", "bytes=-", "bytes=9-0", "bytes=a-9", "bytes=0-b", "bytes=-x", "bytes=5"] {
+            assert_eq!(range(value, 100), RangeRequest::Full, "{}", value);
+        }
+    }
+
+    #[test]
+    fn honours_if_range_only_for_the_current_entity_tag() {
+        let request = |if_range: &str| {
+            parse_range(&headers(&[(header::RANGE, "bytes=0-9"), (header::IF_RANGE, if_range)]), 100, ETAG)
+        };
+        assert_eq!(request(ETAG), partial(0, 9));
+        assert_eq!(request("\"stale\""), RangeRequest::Full);
+        // If-Range needs a strong match, so a weak tag never matches.
+        assert_eq!(request("W/\"abc123\""), RangeRequest::Full);
+    }
+
+    #[test]
+    fn matches_if_none_match_weakly() {
+        let matches = |value: &str| if_none_match(&headers(&[(header::IF_NONE_MATCH, value)]), ETAG);
+        assert!(!if_none_match(&HeaderMap::new(), ETAG));
+        assert!(matches(ETAG));
+        assert!(matches("W/\"abc123\""));
+        assert!(matches("*"));
+        assert!(matches("\"other\", W/\"abc123\""));
+        assert!(!matches("\"other\""));
+        assert!(!matches("\"abc\""));
+        assert!(!matches("abc123"));
+    }
+
+    #[test]
+    fn glob_matches_literal_names() {
+        assert!(glob_match("hello", "hello"));
+        assert!(!glob_match("hello", "hello-doc"));
+        assert!(!glob_match("hello-doc", "hello"));
+    }
+
+    #[test]
+    fn glob_star_matches_any_run() {
+        assert!(glob_match("*", "hello"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("hello*", "hello"));
+        assert!(glob_match("hello*", "hello-doc"));
+        assert!(glob_match("*-doc", "hello-doc"));
+        assert!(glob_match("lib*-dev", "libssl-dev"));
+        assert!(glob_match("lib*-dev", "lib-dev"));
+        assert!(glob_match("lib*-dev", "libfoo-dev-dev"));
+        assert!(!glob_match("lib*-dev", "libssl-dev1"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXcYb"));
+        assert!(glob_match("**", "hello"));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_one_character() {
+        assert!(glob_match("python3.1?", "python3.12"));
+        assert!(!glob_match("python3.1?", "python3.1"));
+        assert!(!glob_match("python3.1?", "python3.123"));
+    }
+
+    #[test]
+    fn glob_empty_pattern_only_matches_empty_text() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "hello"));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn glob_matches_scoped_names() {
+        assert!(glob_match("@acme/*", "@acme/tool"));
+        assert!(glob_match("@acme/*", "@acme/"));
+        assert!(!glob_match("@acme/*", "@acme-labs/tool"));
+        assert!(!glob_match("@acme/*", "tool"));
+        // `*` is not limited to one path segment.
+        assert!(glob_match("@*/tool", "@acme/tool"));
+        assert!(glob_match("*", "@acme/tool"));
+        assert!(glob_match("@acme/lib*", "@acme/libfoo/extra"));
+    }
+}
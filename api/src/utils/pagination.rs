@@ -0,0 +1,412 @@
+//! Cursor pagination for listing endpoints.
+//!
+//! A page is requested with `?limit=&cursor=&sort=&order=`. The response
+//! body stays a plain JSON array; the total number of matching rows goes in
+//! `X-Total-Count` and the neighbouring pages in an RFC 8288 `Link` header.
+//! Cursors are opaque to clients and only valid for the sort they were
+//! issued under.
+
+use axum::{
+    http::{header, HeaderName, HeaderValue, Uri},
+    response::{IntoResponse, Json, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use prisma_client_rust::Direction;
+use serde::{Deserialize, Serialize};
+use crate::errors::ApiError;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+
+pub const TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub sort: Option<String>,
+    /// `asc` or `desc`; each sort key has its own default.
+    pub order: Option<String>,
+}
+
+/// A sort key an endpoint accepts, with the direction it defaults to.
+pub type SortKey = (&'static str, Direction);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    id: String,
+    sort: String,
+    desc: bool,
+    /// Set on `prev` cursors, which page towards the start of the list.
+    back: bool,
+}
+
+pub struct Page {
+    pub sort: &'static str,
+    pub direction: Direction,
+    limit: i64,
+    cursor: Option<Cursor>,
+}
+
+impl PageQuery {
+    /// Validates the query against the sort keys an endpoint supports; the
+    /// first one is the default. `limit` is clamped to `max_limit`.
+    pub fn resolve(&self, sorts: &[SortKey], max_limit: i64) -> Result<Page, ApiError> {
+        let &(sort, default_direction) = match self.sort.as_deref() {
+            None => &sorts[0],
+            Some(requested) => sorts
+                .iter()
+                .find(|(key, _)| *key == requested)
+                .ok_or_else(|| {
+                    let keys: Vec<&str> = sorts.iter().map(|(key, _)| *key).collect();
+                    ApiError::bad_request(
+                        "invalid_sort",
+                        format!("'{}' is not one of {}", requested, keys.join(", ")),
+                    )
+                })?,
+        };
+
+        let direction = match self.order.as_deref() {
+            None => default_direction,
+            Some("asc") => Direction::Asc,
+            Some("desc") => Direction::Desc,
+            Some(other) => {
+                return Err(ApiError::bad_request(
+                    "invalid_order",
+                    format!("'{}' is not one of asc or desc", other),
+                ))
+            }
+        };
+
+        let limit = match self.limit {
+            None => DEFAULT_PAGE_SIZE.min(max_limit),
+            Some(limit) if limit < 1 => {
+                return Err(ApiError::bad_request(
+                    "invalid_limit",
+                    "limit must be at least 1",
+                ))
+            }
+            Some(limit) => limit.min(max_limit),
+        };
+
+        let cursor = self
+            .cursor
+            .as_deref()
+            .map(decode_cursor)
+            .transpose()?
+            .map(|cursor| {
+                if cursor.sort != sort || cursor.desc != matches!(direction, Direction::Desc) {
+                    return Err(ApiError::bad_request(
+                        "invalid_cursor",
+                        "The cursor was issued for a different sort order",
+                    ));
+                }
+                Ok(cursor)
+            })
+            .transpose()?;
+
+        Ok(Page {
+            sort,
+            direction,
+            limit,
+            cursor,
+        })
+    }
+}
+
+impl Page {
+    /// The id of the row to continue from, which the query should pass to
+    /// `.cursor()` and then skip.
+    pub fn cursor_id(&self) -> Option<String> {
+        self.cursor.as_ref().map(|cursor| cursor.id.clone())
+    }
+
+    /// The `take` for the query: one row more than the page holds, to learn
+    /// whether another page follows, and negative when paging backwards.
+    pub fn take(&self) -> i64 {
+        if self.is_backward() {
+            -(self.limit + 1)
+        } else {
+            self.limit + 1
+        }
+    }
+
+    /// Whether this is a `prev` page, read walking back from the cursor.
+    pub fn is_backward(&self) -> bool {
+        self.cursor.as_ref().is_some_and(|cursor| cursor.back)
+    }
+
+    /// Trims the over-fetched row and works out the neighbouring pages.
+    /// `id` must return the same unique key the query orders by last.
+    pub fn finish<T>(
+        self,
+        mut items: Vec<T>,
+        total: i64,
+        uri: &Uri,
+        id: impl Fn(&T) -> String,
+    ) -> Paginated<T> {
+        let more = items.len() as i64 > self.limit;
+        if more {
+            if self.is_backward() {
+                items.remove(0);
+            } else {
+                items.pop();
+            }
+        }
+
+        let (has_prev, has_next) = match (&self.cursor, more) {
+            (None, more) => (false, more),
+            (Some(cursor), more) if cursor.back => (more, true),
+            (Some(_), more) => (true, more),
+        };
+
+        let link = |item: Option<&T>, back: bool| {
+            item.map(|item| {
+                page_uri(
+                    uri,
+                    &encode_cursor(&Cursor {
+                        id: id(item),
+                        sort: self.sort.to_string(),
+                        desc: matches!(self.direction, Direction::Desc),
+                        back,
+                    }),
+                )
+            })
+        };
+        let next = if has_next { link(items.last(), false) } else { None };
+        let prev = if has_prev { link(items.first(), true) } else { None };
+
+        Paginated {
+            items,
+            total,
+            next,
+            prev,
+        }
+    }
+}
+
+/// One page of a listing, rendered as a JSON array with pagination headers.
+#[derive(Debug)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl<T> Paginated<T> {
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Paginated<U> {
+        self.filter_map(|item| Some(f(item)))
+    }
+
+    /// Like `map`, dropping items that map to None. The page keeps its
+    /// links, so it may come out shorter than the limit.
+    pub fn filter_map<U>(self, f: impl FnMut(T) -> Option<U>) -> Paginated<U> {
+        Paginated {
+            items: self.items.into_iter().filter_map(f).collect(),
+            total: self.total,
+            next: self.next,
+            prev: self.prev,
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Paginated<T> {
+    fn into_response(self) -> Response {
+        let mut response = Json(self.items).into_response();
+        let headers = response.headers_mut();
+
+        headers.insert(TOTAL_COUNT, HeaderValue::from(self.total));
+
+        let links: Vec<String> = [(self.next, "next"), (self.prev, "prev")]
+            .into_iter()
+            .filter_map(|(target, rel)| target.map(|target| format!("<{}>; rel=\"{}\"", target, rel)))
+            .collect();
+        if !links.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+                headers.insert(header::LINK, value);
+            }
+        }
+
+        response
+    }
+}
+
+fn encode_cursor(cursor: &Cursor) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+fn decode_cursor(cursor: &str) -> Result<Cursor, ApiError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| ApiError::bad_request("invalid_cursor", "The cursor is not valid"))
+}
+
+/// The request's own path and query with `cursor` replaced, so every other
+/// parameter carries over to the linked page.
+fn page_uri(uri: &Uri, cursor: &str) -> String {
+    let mut params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| !param.is_empty() && param.split('=').next() != Some("cursor"))
+        .collect();
+    let cursor = format!("cursor={}", cursor);
+    params.push(&cursor);
+
+    format!("{}?{}", uri.path(), params.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SORTS: &[SortKey] = &[("created", Direction::Desc), ("name", Direction::Asc)];
+
+    fn query(cursor: Option<String>, limit: Option<i64>, sort: Option<&str>, order: Option<&str>) -> PageQuery {
+        PageQuery {
+            cursor,
+            limit,
+            sort: sort.map(str::to_string),
+            order: order.map(str::to_string),
+        }
+    }
+
+    fn cursor(id: &str, sort: &str, desc: bool, back: bool) -> String {
+        encode_cursor(&Cursor {
+            id: id.to_string(),
+            sort: sort.to_string(),
+            desc,
+            back,
+        })
+    }
+
+    fn error(query: PageQuery) -> &'static str {
+        query.resolve(SORTS, 100).err().unwrap().code
+    }
+
+    fn uri() -> Uri {
+        "/api/packages?tag=cli&cursor=old&limit=2".parse().unwrap()
+    }
+
+    /// The cursor in a `next` or `prev` link.
+    fn link_cursor(link: &str) -> Cursor {
+        decode_cursor(link.rsplit_once("cursor=").unwrap().1).unwrap()
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let decoded = decode_cursor(&cursor("abc", "name", true, false)).unwrap();
+        assert_eq!((decoded.id.as_str(), decoded.sort.as_str(), decoded.desc, decoded.back), ("abc", "name", true, false));
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        let not_a_cursor = URL_SAFE_NO_PAD.encode(b"{\"id\":\"abc\"}");
+        for cursor in ["", "not base64!", "bm90IGpzb24", not_a_cursor.as_str()] {
+            assert_eq!(decode_cursor(cursor).err().unwrap().code, "invalid_cursor", "{}", cursor);
+        }
+    }
+
+    #[test]
+    fn resolves_defaults() {
+        let page = query(None, None, None, None).resolve(SORTS, 100).unwrap();
+        assert_eq!(page.sort, "created");
+        assert!(matches!(page.direction, Direction::Desc));
+        assert_eq!(page.take(), DEFAULT_PAGE_SIZE + 1);
+        assert_eq!(page.cursor_id(), None);
+
+        let page = query(None, None, Some("name"), None).resolve(SORTS, 10).unwrap();
+        assert!(matches!(page.direction, Direction::Asc));
+        assert_eq!(page.take(), 11);
+    }
+
+    #[test]
+    fn clamps_the_limit() {
+        let page = query(None, Some(1000), None, Some("asc")).resolve(SORTS, 100).unwrap();
+        assert!(matches!(page.direction, Direction::Asc));
+        assert_eq!(page.take(), 101);
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        assert_eq!(error(query(None, Some(0), None, None)), "invalid_limit");
+        assert_eq!(error(query(None, None, Some("downloads"), None)), "invalid_sort");
+        assert_eq!(error(query(None, None, None, Some("up"))), "invalid_order");
+        assert_eq!(error(query(Some("garbage".to_string()), None, None, None)), "invalid_cursor");
+    }
+
+    #[test]
+    fn rejects_cursors_from_another_sort() {
+        let other_sort = cursor("abc", "name", true, false);
+        assert_eq!(error(query(Some(other_sort), None, None, None)), "invalid_cursor");
+        let other_order = cursor("abc", "created", false, false);
+        assert_eq!(error(query(Some(other_order), None, None, None)), "invalid_cursor");
+
+        let page = query(Some(cursor("abc", "created", true, true)), Some(3), None, None)
+            .resolve(SORTS, 100)
+            .unwrap();
+        assert_eq!(page.cursor_id().as_deref(), Some("abc"));
+        assert_eq!(page.take(), -4);
+    }
+
+    #[test]
+    fn first_page_links_to_the_next_only() {
+        let page = query(None, Some(2), None, None).resolve(SORTS, 100).unwrap();
+        let paginated = page.finish(vec!["a", "b", "c"], 7, &uri(), |item| item.to_string());
+
+        assert_eq!(paginated.items, ["a", "b"]);
+        assert_eq!(paginated.total, 7);
+        assert!(paginated.prev.is_none());
+        let next = paginated.next.unwrap();
+        assert!(next.starts_with("/api/packages?tag=cli&limit=2&cursor="), "{}", next);
+        let next = link_cursor(&next);
+        assert_eq!((next.id.as_str(), next.sort.as_str(), next.desc, next.back), ("b", "created", true, false));
+    }
+
+    #[test]
+    fn last_page_links_to_the_previous_only() {
+        let page = query(Some(cursor("b", "created", true, false)), Some(2), None, None)
+            .resolve(SORTS, 100)
+            .unwrap();
+        let paginated = page.finish(vec!["c"], 3, &uri(), |item| item.to_string());
+
+        assert_eq!(paginated.items, ["c"]);
+        assert!(paginated.next.is_none());
+        let prev = link_cursor(&paginated.prev.unwrap());
+        assert_eq!((prev.id.as_str(), prev.back), ("c", true));
+    }
+
+    #[test]
+    fn backward_page_drops_the_row_before_it() {
+        let page = query(Some(cursor("d", "created", true, true)), Some(2), None, None)
+            .resolve(SORTS, 100)
+            .unwrap();
+        let paginated = page.finish(vec!["a", "b", "c"], 7, &uri(), |item| item.to_string());
+
+        assert_eq!(paginated.items, ["b", "c"]);
+        assert_eq!(link_cursor(paginated.prev.as_deref().unwrap()).id, "b");
+        assert_eq!(link_cursor(paginated.next.as_deref().unwrap()).id, "c");
+    }
+
+    #[test]
+    fn renders_total_and_links_as_headers() {
+        let paginated = Paginated {
+            items: vec![1, 2],
+            total: 5,
+            next: Some("/api/packages?cursor=n".to_string()),
+            prev: Some("/api/packages?cursor=p".to_string()),
+        };
+        let response = paginated.into_response();
+
+        assert_eq!(response.headers()[TOTAL_COUNT], "5");
+        assert_eq!(
+            response.headers()[header::LINK],
+            "</api/packages?cursor=n>; rel=\"next\", </api/packages?cursor=p>; rel=\"prev\""
+        );
+
+        let response = Paginated::<i32> { items: vec![], total: 0, next: None, prev: None }.into_response();
+        assert!(response.headers().get(header::LINK).is_none());
+    }
+}
@@ -3,20 +3,21 @@ use colored::*;
 use dialoguer::Input;
 use serde_json::json;
 use std::path::Path;
+use crate::commands::{auth::send_authorized, encode_name};
 use crate::config::Config;
-use crate::main::AdminAction;
+use crate::AdminAction;
 
 pub async fn execute(action: AdminAction) -> Result<()> {
-    let config = Config::load()?;
+    let mut config = Config::load()?;
     
-    if config.auth_token.is_none() {
+    if config.access_token().is_none() {
         println!("{}", "Error: Admin authentication required".red());
         println!("Please run 'cpkgs auth login' first");
         return Ok(());
     }
 
     match action {
-        AdminAction::Upload { package_file } => {
+        AdminAction::Upload { package_file, scope } => {
             if !Path::new(&package_file).exists() {
                 println!("{}: Package file not found", "Error".red());
                 return Ok(());
@@ -33,17 +34,20 @@ pub async fn execute(action: AdminAction) -> Result<()> {
                 .and_then(|n| n.to_str())
                 .unwrap_or("package.deb");
             
-            let form = reqwest::multipart::Form::new()
-                .part("file", reqwest::multipart::Part::bytes(file_content)
-                    .file_name(file_name.to_string())
-                    .mime_str("application/octet-stream")?);
-            
-            let response = client
-                .post(&url)
-                .header("Authorization", format!("Bearer {}", config.auth_token.unwrap()))
-                .multipart(form)
-                .send()
-                .await?;
+            let response = send_authorized(&client, &mut config, |auth| {
+                let mut form = reqwest::multipart::Form::new().part(
+                    "file",
+                    reqwest::multipart::Part::bytes(file_content.clone())
+                        .file_name(file_name.to_string())
+                        .mime_str("application/octet-stream")
+                        .expect("static MIME type is valid"),
+                );
+                if let Some(scope) = &scope {
+                    form = form.text("scope", scope.clone());
+                }
+                client.post(&url).header("Authorization", auth).multipart(form)
+            })
+            .await?;
 
             if response.status().is_success() {
                 let result: serde_json::Value = response.json().await?;
@@ -57,13 +61,12 @@ pub async fn execute(action: AdminAction) -> Result<()> {
             println!("{} {} ({})", "Removing".red().bold(), name.cyan(), version.yellow());
             
             let client = reqwest::Client::new();
-            let url = format!("{}/api/packages/{}/{}", config.registry_url, name, version);
+            let url = format!("{}/api/packages/{}/{}", config.registry_url, encode_name(&name), version);
             
-            let response = client
-                .delete(&url)
-                .header("Authorization", format!("Bearer {}", config.auth_token.unwrap()))
-                .send()
-                .await?;
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
 
             if response.status().is_success() {
                 println!("{} {} ({})", "✓ Removed".green(), name.cyan(), version.yellow());
@@ -76,11 +79,10 @@ pub async fn execute(action: AdminAction) -> Result<()> {
             let client = reqwest::Client::new();
             let url = format!("{}/api/users", config.registry_url);
             
-            let response = client
-                .get(&url)
-                .header("Authorization", format!("Bearer {}", config.auth_token.unwrap()))
-                .send()
-                .await?;
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.get(&url).header("Authorization", auth)
+            })
+            .await?;
 
             if response.status().is_success() {
                 let users: serde_json::Value = response.json().await?;
@@ -109,16 +111,17 @@ pub async fn execute(action: AdminAction) -> Result<()> {
             let client = reqwest::Client::new();
             let url = format!("{}/api/users", config.registry_url);
             
-            let response = client
-                .post(&url)
-                .header("Authorization", format!("Bearer {}", config.auth_token.unwrap()))
-                .json(&json!({
-                    "username": username,
-                    "email": email,
-                    "password": password
-                }))
-                .send()
-                .await?;
+            let response = send_authorized(&client, &mut config, |auth| {
+                client
+                    .post(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({
+                        "username": username,
+                        "email": email,
+                        "password": password
+                    }))
+            })
+            .await?;
 
             if response.status().is_success() {
                 let user: serde_json::Value = response.json().await?;
@@ -127,6 +130,8 @@ pub async fn execute(action: AdminAction) -> Result<()> {
                 println!("{}: {}", "User creation failed".red(), response.text().await?);
             }
         }
+
+        AdminAction::Tag { action } => crate::commands::tag::execute(&mut config, action).await?,
     }
 
     Ok(())
@@ -1,9 +1,10 @@
 use anyhow::Result;
 use colored::*;
 use dialoguer::{Input, Password};
+use reqwest::{header::WWW_AUTHENTICATE, RequestBuilder, Response, StatusCode};
 use serde_json::json;
 use crate::config::Config;
-use crate::main::AuthAction;
+use crate::{AuthAction, TokenAction};
 
 pub async fn execute(action: AuthAction) -> Result<()> {
     match action {
@@ -32,10 +33,9 @@ pub async fn execute(action: AuthAction) -> Result<()> {
 
             if response.status().is_success() {
                 let auth_response: serde_json::Value = response.json().await?;
-                let token = auth_response["token"].as_str().unwrap_or("");
                 
                 let mut config = config;
-                config.set_auth_token(token.to_string());
+                store_tokens(&mut config, &auth_response);
                 config.save()?;
                 
                 println!("{} {}!", "Welcome back".green().bold(), username.cyan());
@@ -47,6 +47,21 @@ pub async fn execute(action: AuthAction) -> Result<()> {
         
         AuthAction::Logout => {
             let mut config = Config::load()?;
+
+            if let Some(refresh_token) = &config.refresh_token {
+                let url = format!("{}/api/auth/logout", config.registry_url);
+                let revoked = reqwest::Client::new()
+                    .post(&url)
+                    .json(&json!({ "refresh_token": refresh_token }))
+                    .send()
+                    .await
+                    .is_ok_and(|response| response.status().is_success());
+
+                if !revoked {
+                    println!("{}", "Warning: could not revoke the session on the registry".yellow());
+                }
+            }
+
             config.clear_auth_token();
             config.save()?;
             
@@ -84,10 +99,9 @@ pub async fn execute(action: AuthAction) -> Result<()> {
 
             if response.status().is_success() {
                 let auth_response: serde_json::Value = response.json().await?;
-                let token = auth_response["token"].as_str().unwrap_or("");
                 
                 let mut config = config;
-                config.set_auth_token(token.to_string());
+                store_tokens(&mut config, &auth_response);
                 config.save()?;
                 
                 println!("{} {}!", "Welcome".green().bold(), username.cyan());
@@ -100,7 +114,7 @@ pub async fn execute(action: AuthAction) -> Result<()> {
         AuthAction::Status => {
             let config = Config::load()?;
             
-            if let Some(_token) = config.auth_token {
+            if config.access_token().is_some() {
                 println!("{}", "✓ You are logged in".green());
                 println!("Registry: {}", config.registry_url.cyan());
             } else {
@@ -108,7 +122,166 @@ pub async fn execute(action: AuthAction) -> Result<()> {
                 println!("Use 'cpkgs auth login' to authenticate");
             }
         }
+
+        AuthAction::Token { action } => {
+            token(action).await?;
+        }
+    }
+
+    Ok(())
+}
+async fn token(action: TokenAction) -> Result<()> {
+    let mut config = Config::load()?;
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/auth/tokens", config.registry_url);
+
+    match action {
+        TokenAction::Create { name, scopes, package, expires_in_days } => {
+            let response = send_authorized(&client, &mut config, |auth| {
+                client
+                    .post(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({
+                        "name": name,
+                        "scopes": scopes,
+                        "package_pattern": package,
+                        "expires_in_days": expires_in_days
+                    }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let token: serde_json::Value = response.json().await?;
+                println!("{} {}", "✓ Created token".green(), token["name"].as_str().unwrap_or_default().cyan());
+                println!();
+                println!("  {}", token["token"].as_str().unwrap_or_default().bold());
+                println!();
+                println!("{}", "Copy it now, it will not be shown again.".yellow());
+                println!("Use it by setting CPKGS_TOKEN in the environment.");
+            } else {
+                println!("{}: {}", "Token creation failed".red(), response.text().await?);
+            }
+        }
+
+        TokenAction::List => {
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.get(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let tokens: Vec<serde_json::Value> = response.json().await?;
+
+                if tokens.is_empty() {
+                    println!("{}", "No API tokens.".yellow());
+                }
+                for token in &tokens {
+                    let scopes: Vec<&str> = token["scopes"]
+                        .as_array()
+                        .map(|scopes| scopes.iter().filter_map(|s| s.as_str()).collect())
+                        .unwrap_or_default();
+
+                    println!("{} ({})", token["name"].as_str().unwrap_or_default().cyan(), token["id"].as_str().unwrap_or_default());
+                    println!("  Token: {}…", token["prefix"].as_str().unwrap_or_default());
+                    println!("  Scopes: {}", scopes.join(", "));
+                    if let Some(pattern) = token["package_pattern"].as_str() {
+                        println!("  Packages: {}", pattern);
+                    }
+                    println!("  Expires: {}", token["expires_at"].as_str().unwrap_or("never"));
+                    println!("  Last used: {}", token["last_used_at"].as_str().unwrap_or("never"));
+                    println!();
+                }
+            } else {
+                println!("{}: {}", "Error".red(), response.text().await?);
+            }
+        }
+
+        TokenAction::Revoke { id } => {
+            let url = format!("{}/{}", url, id);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}", "✓ Revoked token".green(), id);
+            } else {
+                println!("{}: {}", "Revocation failed".red(), response.text().await?);
+            }
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Sends a request built by `build`, which is given the `Authorization`
+/// header value. If the registry reports the access token as expired, the
+/// session is refreshed and the request is sent once more.
+pub async fn send_authorized<F>(
+    client: &reqwest::Client,
+    config: &mut Config,
+    build: F,
+) -> Result<Response>
+where
+    F: Fn(&str) -> RequestBuilder,
+{
+    let response = build(&bearer(config)).send().await?;
+
+    if !token_expired(&response) || !refresh(client, config).await? {
+        return Ok(response);
+    }
+
+    Ok(build(&bearer(config)).send().await?)
+}
+
+fn bearer(config: &Config) -> String {
+    format!("Bearer {}", config.access_token().unwrap_or_default())
+}
+
+fn token_expired(response: &Response) -> bool {
+    response.status() == StatusCode::UNAUTHORIZED
+        && response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|challenge| challenge.contains("invalid_token") && challenge.contains("expired"))
+}
+
+/// Exchanges the stored refresh token for new tokens. Returns whether the
+/// stored access token can now be used.
+async fn refresh(client: &reqwest::Client, config: &mut Config) -> Result<bool> {
+    let Some(refresh_token) = config.refresh_token.clone() else {
+        return Ok(false);
+    };
+
+    let url = format!("{}/api/auth/refresh", config.registry_url);
+    let response = client
+        .post(&url)
+        .json(&json!({ "refresh_token": refresh_token }))
+        .send()
+        .await?;
+
+    if response.status() == StatusCode::UNAUTHORIZED {
+        // The session was revoked or has run out; the stored tokens are of
+        // no further use.
+        config.clear_auth_token();
+        config.save()?;
+        println!("{}", "Your session has ended, please run 'cpkgs auth login'".yellow());
+        return Ok(false);
+    }
+    if !response.status().is_success() {
+        return Ok(false);
+    }
+
+    let auth_response: serde_json::Value = response.json().await?;
+    store_tokens(config, &auth_response);
+    config.save()?;
+
+    Ok(true)
+}
+
+fn store_tokens(config: &mut Config, auth_response: &serde_json::Value) {
+    let token = auth_response["token"].as_str().unwrap_or("");
+    let refresh_token = auth_response["refresh_token"].as_str().map(str::to_string);
+    config.set_auth_token(token.to_string(), refresh_token);
+}
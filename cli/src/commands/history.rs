@@ -0,0 +1,48 @@
+use anyhow::Result;
+use colored::*;
+use crate::config::Config;
+use crate::transaction::{journals, Journal, State};
+
+pub async fn execute() -> Result<()> {
+    let config = Config::load()?;
+    let journals = journals(&config)?;
+
+    if journals.is_empty() {
+        println!("{}", "No transactions yet.".yellow());
+        return Ok(());
+    }
+
+    println!("{}", "Transactions:".green().bold());
+    println!("{}", "─".repeat(50));
+    for journal in journals.iter().rev() {
+        print_journal(journal);
+    }
+
+    Ok(())
+}
+
+pub fn print_journal(journal: &Journal) {
+    let state = match journal.state {
+        State::Committed if journal.pruned => "committed, backups pruned".normal(),
+        State::Committed => "committed".green(),
+        State::Pending | State::RollingBack => "interrupted".red(),
+        State::RolledBack => "rolled back".yellow(),
+    };
+    println!(
+        "{} {} {} ({})",
+        journal.id.to_string().bold(),
+        journal.started_at.format("%Y-%m-%d %H:%M:%S"),
+        journal.command.cyan(),
+        state
+    );
+
+    for package in &journal.packages {
+        let change = match (&package.from, &package.to) {
+            (None, Some(to)) => format!("installed {}", to.yellow()),
+            (Some(from), Some(to)) => format!("{} → {}", from.yellow(), to.yellow()),
+            (Some(from), None) => format!("removed {}", from.yellow()),
+            (None, None) => continue,
+        };
+        println!("    {} {}", package.name, change);
+    }
+}
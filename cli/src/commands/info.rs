@@ -1,54 +1,153 @@
 use anyhow::Result;
 use colored::*;
+use crate::commands::{auth::send_authorized, encode_name};
 use crate::config::Config;
+use crate::database::{Database, InstalledPackage};
+use crate::index::Index;
+use serde_json::{json, Value};
 
-pub async fn execute(name: String, version: Option<String>) -> Result<()> {
-    let config = Config::load()?;
+pub async fn execute(name: String, version: Option<String>, files: bool) -> Result<()> {
+    let mut config = Config::load()?;
     let client = reqwest::Client::new();
-    
-    let url = if let Some(v) = version {
-        format!("{}/api/packages/{}/{}", config.registry_url, name, v)
-    } else {
-        format!("{}/api/packages/{}", config.registry_url, name)
+    let database = Database::open(&config)?;
+    let installed = database.get(&name);
+
+    let found = match Index::open(&config)? {
+        Some(index) => describe(&index, &config, &name, version.as_deref()),
+        None => {
+            let url = if let Some(v) = version {
+                format!("{}/api/packages/{}/{}", config.registry_url, encode_name(&name), v)
+            } else {
+                format!("{}/api/packages/{}", config.registry_url, encode_name(&name))
+            };
+
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.get(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                Ok(response.json().await?)
+            } else {
+                Err(response.text().await?)
+            }
+        }
     };
+
+    let mut package = match found {
+        Ok(package) => package,
+        // Still describe what is on disk when the registry no longer has it.
+        Err(message) if installed.is_some() => {
+            println!("{}: {}", "Not in the registry".yellow(), message);
+            print_installed(installed, files);
+            return Ok(());
+        }
+        Err(message) => {
+            println!("{}: {}", "Package not found".red(), message);
+            return Ok(());
+        }
+    };
+
+    println!("{}", "Package Information:".green().bold());
+    println!("{}", "─".repeat(50));
+
+    // Without a version the registry describes the package as a whole,
+    // with its newest version under `latest`.
+    if let Some(latest) = package.get_mut("latest").map(Value::take) {
+        if let Some(homepage) = package["homepage"].as_str() {
+            println!("{}: {}", "Homepage".cyan(), homepage);
+        }
+        if let Some(repository) = package["repository"].as_str() {
+            println!("{}: {}", "Repository".cyan(), repository);
+        }
+        if let Some(deprecated) = package["deprecated"].as_str() {
+            println!("{}: {}", "Deprecated".red(), deprecated);
+        }
+        if let Some(downloads) = package.get("downloads") {
+            println!("{}: {}", "Downloads".cyan(), downloads);
+        }
+        let versions: Vec<&str> = package["versions"]
+            .as_array()
+            .map(|versions| versions.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+        println!("{}: {}", "Versions".cyan(), versions.join(", "));
+
+        if latest.is_null() {
+            print_installed(installed, files);
+            return Ok(());
+        }
+        package = latest;
+    }
+
+    println!("{}: {}", "Name".cyan(), package["name"]);
+    println!("{}: {}", "Version".cyan(), package["version"]);
+    println!("{}: {}", "Description".cyan(), package["description"].as_str().unwrap_or("No description"));
+    println!("{}: {}", "Maintainer".cyan(), package["maintainer"]);
+    println!("{}: {}", "Architecture".cyan(), package["architecture"]);
+    println!("{}: {} bytes", "Size".cyan(), package["size"]);
+    println!("{}: {}", "Checksum".cyan(), package["checksum"]);
+    println!("{}: {}", "Created".cyan(), package["created_at"]);
+    println!("{}: {}", "Updated".cyan(), package["updated_at"]);
     
-    let response = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", config.auth_token.unwrap_or_default()))
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        let package: serde_json::Value = response.json().await?;
-        
-        println!("{}", "Package Information:".green().bold());
-        println!("{}", "─".repeat(50));
-        println!("{}: {}", "Name".cyan(), package["name"]);
-        println!("{}: {}", "Version".cyan(), package["version"]);
-        println!("{}: {}", "Description".cyan(), package["description"].as_str().unwrap_or("No description"));
-        println!("{}: {}", "Maintainer".cyan(), package["maintainer"]);
-        println!("{}: {}", "Architecture".cyan(), package["architecture"]);
-        println!("{}: {} bytes", "Size".cyan(), package["size"]);
-        println!("{}: {}", "Checksum".cyan(), package["checksum"]);
-        println!("{}: {}", "Created".cyan(), package["created_at"]);
-        println!("{}: {}", "Updated".cyan(), package["updated_at"]);
-        
-        if let Some(dependencies) = package["dependencies"].as_array() {
-            println!("{}:", "Dependencies".cyan());
-            for dep in dependencies {
-                println!("  - {} ({})", dep["name"], dep["version"]);
-            }
+    if let Some(dependencies) = package["dependencies"].as_array() {
+        println!("{}:", "Dependencies".cyan());
+        for dep in dependencies {
+            println!("  - {} ({})", dep["name"], dep["version"]);
         }
-        
-        if let Some(tags) = package["tags"].as_array() {
-            println!("{}:", "Tags".cyan());
-            for tag in tags {
-                println!("  - {}", tag["name"]);
-            }
+    }
+    
+    if let Some(tags) = package["tags"].as_array() {
+        println!("{}:", "Tags".cyan());
+        for tag in tags {
+            println!("  - {}", tag["name"]);
         }
-    } else {
-        println!("{}: {}", "Package not found".red(), response.text().await?);
     }
 
+    print_installed(installed, files);
+
     Ok(())
+}
+
+/// What the registry says about a package, or one of its versions, as of
+/// the local package index. The index has no name-level metadata, so the
+/// package as a whole is only its versions.
+fn describe(index: &Index, config: &Config, name: &str, version: Option<&str>) -> Result<Value, String> {
+    let versions = index
+        .versions(name)
+        .ok_or_else(|| format!("{} is not in the package index", name))?;
+
+    let Some(version) = version else {
+        let mut names: Vec<&str> = versions.iter().filter_map(|v| v["version"].as_str()).collect();
+        names.dedup();
+        return Ok(json!({ "versions": names, "latest": versions.first() }));
+    };
+
+    // A version built for several architectures is described for ours.
+    versions
+        .iter()
+        .filter(|v| v["version"] == version)
+        .max_by_key(|v| v["architecture"] == config.architecture.as_str())
+        .cloned()
+        .ok_or_else(|| format!("{} {} is not in the package index", name, version))
+}
+
+fn print_installed(installed: Option<&InstalledPackage>, files: bool) {
+    let Some(installed) = installed else {
+        println!("{}: {}", "Installed".cyan(), "no".dimmed());
+        return;
+    };
+
+    let unpacked: Vec<&String> = installed.files.iter().filter(|file| !file.ends_with('/')).collect();
+    println!(
+        "{}: {} ({}, {} files)",
+        "Installed".cyan(),
+        installed.version.green(),
+        installed.architecture,
+        unpacked.len()
+    );
+    if files {
+        for file in unpacked {
+            println!("  {}", file);
+        }
+    }
 }
\ No newline at end of file
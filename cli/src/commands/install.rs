@@ -1,63 +1,381 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use colored::*;
+use common::Version;
 use dialoguer::Confirm;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{
+    header::{IF_RANGE, RANGE},
+    StatusCode,
+};
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+};
+use crate::commands::{auth::send_authorized, encode_name};
 use crate::config::Config;
+use crate::database::{sha256_file, Database, InstalledPackage};
+use crate::index::Index;
+use crate::keyring::Keyring;
+use crate::repository::SignedIndex;
+use crate::resolver::{requested, Candidate, Plan, Resolver, Universe};
+use crate::transaction::Transaction;
 
-pub async fn execute(name: String, version: Option<String>) -> Result<()> {
-    let config = Config::load()?;
+pub async fn execute(name: String, version: Option<String>, install_recommends: bool) -> Result<()> {
+    let mut config = Config::load()?;
     let client = reqwest::Client::new();
     
-    println!("{} {} ({})", "Installing".green().bold(), name.cyan(), version.unwrap_or("latest".to_string()).yellow());
-    
-    let url = if let Some(v) = version {
-        format!("{}/api/packages/{}/{}", config.registry_url, name, v)
+    println!("{} {} ({})", "Installing".green().bold(), name.cyan(), version.as_deref().unwrap_or("latest").yellow());
+    println!("{}", "Resolving dependencies...".blue());
+
+    let roots = [requested(&name, version.as_deref())?];
+    let index = Index::open(&config)?;
+    let mut database = Database::open(&config)?;
+    // Installed packages too, so conflicts they declare are known.
+    let names: Vec<String> = std::iter::once(name.clone())
+        .chain(database.packages().map(|package| package.name.clone()))
+        .collect();
+    let universe = Universe::fetch(&client, &mut config, index.as_ref(), &names, install_recommends).await?;
+    if !universe.contains(&name) {
+        if index.is_some() {
+            println!(
+                "{}: {} is not in the package index; run `cpkgs update` or pass --online",
+                "Package not found".red(),
+                name
+            );
+        } else {
+            println!("{}: {} does not exist", "Package not found".red(), name);
+        }
+        return Ok(());
+    }
+
+    let installed = database.packages().map(|package| (package.name.clone(), package.version.clone()));
+    let mut plan = match Resolver::new(&universe, install_recommends).prefer(installed).resolve(&roots) {
+        Ok(plan) => plan,
+        Err(err) => {
+            println!("{}: {}", "Cannot install".red(), err);
+            return Ok(());
+        }
+    };
+
+    plan.install.retain(|package| {
+        let current = database.get(&package.name).is_some_and(|installed| installed.version == package.version);
+        if current {
+            println!("{} is already installed", package.to_string().cyan());
+        }
+        !current
+    });
+    if plan.install.is_empty() {
+        return Ok(());
+    }
+
+    let removals = conflicting(&database, &universe, &plan);
+    print_plan(&plan);
+    print_removals(&removals);
+
+    // Removing what is in the way has to be asked for explicitly.
+    if Confirm::new()
+        .with_prompt("Do you want to continue with installation?")
+        .default(removals.is_empty())
+        .interact()?
+    {
+        let remove: Vec<String> = removals.iter().map(|(installed, _)| installed.name.clone()).collect();
+        apply(&client, &mut config, &mut database, &plan.install, &remove).await?;
+
+        for package in &plan.install {
+            println!("{} {}", "Installed".green().bold(), package.to_string().cyan());
+        }
+        for name in remove {
+            println!("{} {}", "Removed".green().bold(), name.cyan());
+        }
+    }
+
+    Ok(())
+}
+
+/// Downloads `install`, then removes `remove` and installs `install` in a
+/// single transaction.
+pub async fn apply(
+    client: &reqwest::Client,
+    config: &mut Config,
+    database: &mut Database,
+    install: &[&Candidate],
+    remove: &[String],
+) -> Result<()> {
+    let keyring = Keyring::open(config)?;
+    let mut index = if keyring.is_empty() {
+        println!(
+            "{}",
+            "No trusted keys are configured, so signatures will not be checked (see `cpkgs key`).".yellow()
+        );
+        None
     } else {
-        format!("{}/api/packages/{}", config.registry_url, name)
+        SignedIndex::fetch(client, config, &keyring).await?
     };
-    
-    let response = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", config.auth_token.unwrap_or_default()))
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        let package: serde_json::Value = response.json().await?;
-        
-        println!("Package found:");
-        println!("  Name: {}", package["name"]);
-        println!("  Version: {}", package["version"]);
-        println!("  Size: {} bytes", package["size"]);
-        println!("  Maintainer: {}", package["maintainer"]);
-        
-        if Confirm::new()
-            .with_prompt("Do you want to continue with installation?")
-            .default(true)
-            .interact()?
-        {
-            println!("{} {}", "Downloading".blue(), name.cyan());
-            
-            let download_url = format!("{}/api/packages/{}/download", config.registry_url, package["id"]);
-            let download_response = client
-                .get(&download_url)
-                .header("Authorization", format!("Bearer {}", config.auth_token.unwrap_or_default()))
-                .send()
-                .await?;
-            
-            if download_response.status().is_success() {
-                let package_data = download_response.bytes().await?;
-                let file_path = config.install_dir.join(format!("{}-{}.deb", name, package["version"]));
-                
-                tokio::fs::write(&file_path, package_data).await?;
-                
-                println!("{} {} to {}", "Installed".green().bold(), name.cyan(), file_path.display());
-            } else {
-                println!("{}: {}", "Download failed".red(), download_response.text().await?);
+
+    let mut archives = Vec::new();
+    for package in install {
+        if let Some(index) = &mut index {
+            match index.checksum(client, config, package).await? {
+                Some(checksum) if checksum == package.checksum => {}
+                Some(_) => bail!("The checksum of {} does not match the signed repository index", package),
+                None => bail!("{} is not in the signed repository index", package),
             }
         }
-    } else {
-        println!("{}: {}", "Package not found".red(), response.text().await?);
+
+        println!("{} {}", "Downloading".blue(), package.to_string().cyan());
+        archives.push(fetch_archive(client, config, &keyring, package).await?);
+    }
+
+    let mut transaction = Transaction::begin(config)?;
+    let planned = remove
+        .iter()
+        .try_for_each(|name| {
+            println!("{} {}", "Removing".blue(), name.cyan());
+            transaction.remove(database, name)
+        })
+        .and_then(|_| {
+            install.iter().zip(&archives).try_for_each(|(package, archive)| {
+                transaction.check_interrupted()?;
+                println!("{} {}", "Unpacking".blue(), package.to_string().cyan());
+                transaction.install(database, package, archive)
+            })
+        });
+    transaction.finish(config, database, planned)
+}
+
+/// Downloads a package into `<cache_dir>/archives`, where it is kept, and
+/// returns its path. The download is deleted unless it matches the
+/// package's checksum and, when keys are trusted, its signature.
+pub async fn fetch_archive(
+    client: &reqwest::Client,
+    config: &mut Config,
+    keyring: &Keyring,
+    package: &Candidate,
+) -> Result<PathBuf> {
+    let partial_path = config
+        .cache_dir
+        .join(format!("{}_{}.deb.part", encode_name(&package.name), package.version));
+    download_package(
+        client,
+        config,
+        &package.name,
+        &package.version,
+        &package.checksum,
+        &partial_path,
+    )
+    .await?;
+
+    if let Err(err) = verify_archive(client, config, keyring, package, &partial_path).await {
+        let _ = tokio::fs::remove_file(&partial_path).await;
+        return Err(err);
+    }
+
+    let archive_path = config.cache_dir.join("archives").join(format!(
+        "{}_{}_{}.deb",
+        encode_name(&package.name),
+        package.version,
+        package.architecture
+    ));
+    tokio::fs::create_dir_all(config.cache_dir.join("archives")).await?;
+    tokio::fs::rename(&partial_path, &archive_path).await?;
+
+    Ok(archive_path)
+}
+
+async fn verify_archive(
+    client: &reqwest::Client,
+    config: &mut Config,
+    keyring: &Keyring,
+    package: &Candidate,
+    path: &Path,
+) -> Result<()> {
+    let checksum = sha256_file(path)?;
+    if checksum != package.checksum {
+        bail!(
+            "{} is corrupt or was tampered with: its SHA-256 is {}, expected {}",
+            package,
+            checksum,
+            package.checksum
+        );
+    }
+
+    if keyring.is_empty() {
+        return Ok(());
+    }
+
+    let url = format!(
+        "{}/api/packages/{}/{}/signature",
+        config.registry_url,
+        encode_name(&package.name),
+        package.version
+    );
+    let response = send_authorized(client, config, |auth| {
+        client.get(&url).header("Authorization", auth)
+    })
+    .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        bail!("{} is not signed", package);
+    }
+    if !response.status().is_success() {
+        bail!("Could not fetch the signature of {}: {}", package, response.text().await?);
+    }
+
+    let signature = response.bytes().await?;
+    keyring
+        .verify_detached(&signature, path)
+        .with_context(|| format!("{} failed signature verification", package))?;
+
+    Ok(())
+}
+
+fn print_plan(plan: &Plan) {
+    println!("The following packages will be installed:");
+    for package in &plan.install {
+        println!(
+            "  {} {} ({}, {} bytes)",
+            package.name.cyan(),
+            package.version.yellow(),
+            package.architecture,
+            package.size
+        );
+    }
+    let total: u64 = plan.install.iter().map(|p| p.size).sum();
+    println!("Total download size: {} bytes", total);
+
+    print_relations(plan);
+}
+
+/// Installed packages the plan does not replace that conflict with one it
+/// installs, in either direction, with the package they conflict with.
+pub fn conflicting<'a>(
+    database: &'a Database,
+    universe: &'a Universe,
+    plan: &Plan<'a>,
+) -> Vec<(&'a InstalledPackage, &'a Candidate)> {
+    database
+        .packages()
+        .filter(|installed| !plan.install.iter().any(|package| package.name == installed.name))
+        .filter_map(|installed| {
+            let version: Version = installed.version.parse().ok()?;
+            let registry = universe.find(&installed.name, &installed.version);
+            plan.install
+                .iter()
+                .find(|package| {
+                    package.conflicts_with_version(&installed.name, &version)
+                        || registry.is_some_and(|registry| registry.conflicts_with_version(&package.name, &package.parsed))
+                })
+                .map(|package| (installed, *package))
+        })
+        .collect()
+}
+
+pub fn print_removals(removals: &[(&InstalledPackage, &Candidate)]) {
+    if !removals.is_empty() {
+        println!("{}", "The following packages will be removed because they conflict:".yellow());
+        for (installed, package) in removals {
+            println!("  {} {} (conflicts with {})", installed.name.cyan(), installed.version.yellow(), package);
+        }
+    }
+}
+
+/// What a plan leaves to the system or to the user.
+pub fn print_relations(plan: &Plan) {
+    if !plan.external.is_empty() {
+        println!();
+        println!("{}", "Not in the registry, expected to be provided by the system:".yellow());
+        for (dependent, relation) in &plan.external {
+            println!("  {} (for {})", relation, dependent.name);
+        }
+    }
+
+    if !plan.recommended.is_empty() {
+        println!();
+        println!("Recommended packages (install them with --install-recommends):");
+        for relation in &plan.recommended {
+            println!("  {}", relation);
+        }
+    }
+
+    if !plan.suggested.is_empty() {
+        println!();
+        println!("Suggested packages:");
+        for relation in &plan.suggested {
+            println!("  {}", relation);
+        }
+    }
+}
+
+/// Downloads a package artifact to `path`, resuming from a previous partial
+/// download when one is present and still matches the same artifact.
+pub async fn download_package(
+    client: &reqwest::Client,
+    config: &mut Config,
+    name: &str,
+    version: &str,
+    checksum: &str,
+    path: &Path,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let existing = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+
+    let url = format!(
+        "{}/api/packages/{}/{}/download",
+        config.registry_url,
+        encode_name(name),
+        version
+    );
+    let mut response = send_authorized(client, config, |auth| {
+        let request = client.get(&url).header("Authorization", auth);
+        if existing > 0 {
+            request
+                .header(RANGE, format!("bytes={}-", existing))
+                .header(IF_RANGE, format!("\"{}\"", checksum))
+        } else {
+            request
+        }
+    })
+    .await?;
+
+    let mut file = match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            println!("{} from byte {}", "Resuming download".blue(), existing);
+            OpenOptions::new().append(true).open(path).await?
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE if existing > 0 => {
+            // The partial file is already complete.
+            return Ok(());
+        }
+        status if status.is_success() => File::create(path).await?,
+        _ => bail!("Download failed: {}", response.text().await?),
+    };
+
+    let offset = file.metadata().await?.len();
+    let total = response.content_length().map(|len| len + offset);
+    let pb = match total {
+        Some(total) => ProgressBar::new(total),
+        None => ProgressBar::new_spinner(),
+    };
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_position(offset);
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        pb.inc(chunk.len() as u64);
     }
+    file.flush().await?;
+    pb.finish_and_clear();
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,87 @@
+use anyhow::{bail, Result};
+use colored::*;
+use dialoguer::Confirm;
+use crate::config::Config;
+use crate::keyring::Keyring;
+use crate::KeyAction;
+
+pub async fn execute(action: KeyAction) -> Result<()> {
+    let mut config = Config::load()?;
+    let keyring = Keyring::open(&config)?;
+
+    match action {
+        KeyAction::List => {
+            if config.trusted_keys.is_empty() {
+                println!("{}", "No trusted keys; package signatures are not checked.".yellow());
+                return Ok(());
+            }
+            println!("{}", "Trusted keys:".green().bold());
+            println!("{}", "─".repeat(50));
+            for fingerprint in &config.trusted_keys {
+                match keyring.user_id(fingerprint) {
+                    Some(user_id) => println!("{} {}", fingerprint.cyan(), user_id),
+                    None => println!("{} {}", fingerprint.cyan(), "(missing from the keyring)".red()),
+                }
+            }
+        }
+        KeyAction::Add { file } => {
+            let armored = std::fs::read(&file)?;
+            trust(&mut config, &keyring, &armored, true)?;
+        }
+        KeyAction::Fetch { yes } => {
+            let url = format!("{}/apt/key.asc", config.registry_url);
+            let response = reqwest::get(&url).await?;
+            if !response.status().is_success() {
+                println!("{}: {}", "Error".red(), response.text().await?);
+                return Ok(());
+            }
+            let armored = response.bytes().await?;
+            trust(&mut config, &keyring, &armored, yes)?;
+        }
+        KeyAction::Remove { fingerprint } => {
+            let fingerprint = fingerprint.to_uppercase();
+            let before = config.trusted_keys.len();
+            config.trusted_keys.retain(|key| key.to_uppercase() != fingerprint);
+            if config.trusted_keys.len() == before {
+                bail!("{} is not a trusted key", fingerprint);
+            }
+            keyring.delete(&fingerprint)?;
+            config.save()?;
+            println!("{} {}", "✓ No longer trusting".green(), fingerprint.cyan());
+        }
+    }
+
+    Ok(())
+}
+
+/// Imports the keys in `armored` and adds them to the trusted keys, after
+/// showing their fingerprints for comparison unless `confirmed`.
+fn trust(config: &mut Config, keyring: &Keyring, armored: &[u8], confirmed: bool) -> Result<()> {
+    let keys = keyring.inspect(armored)?;
+    if keys.is_empty() {
+        bail!("no OpenPGP public keys found");
+    }
+
+    println!("The following keys will be trusted to sign packages:");
+    for (fingerprint, user_id) in &keys {
+        println!("  {} {}", fingerprint.cyan(), user_id);
+    }
+
+    if !confirmed
+        && !Confirm::new()
+            .with_prompt("Do these fingerprints match the ones your registry administrator published?")
+            .default(false)
+            .interact()?
+    {
+        return Ok(());
+    }
+
+    keyring.import(armored)?;
+    for (fingerprint, _) in keys {
+        if !config.trusted_keys.iter().any(|key| key.eq_ignore_ascii_case(&fingerprint)) {
+            config.trusted_keys.push(fingerprint.clone());
+        }
+        println!("{} {}", "✓ Trusting".green(), fingerprint.cyan());
+    }
+    config.save()
+}
@@ -1,68 +1,89 @@
 use anyhow::Result;
 use colored::*;
+use reqwest::Url;
+use std::io::{IsTerminal, Write};
 use crate::config::Config;
+use crate::database::Database;
+use crate::index::Index;
+use crate::pages::Pages;
 
 pub async fn execute(installed_only: bool) -> Result<()> {
-    let config = Config::load();
-    
+    let mut config = Config::load()?;
+
     if installed_only {
+        let database = Database::open(&config)?;
+
         println!("{}", "Installed Packages:".green().bold());
         println!("{}", "─".repeat(50));
-        
-        let install_dir = &config?.install_dir;
-        let mut packages = Vec::new();
-        
-        for entry in std::fs::read_dir(install_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                if filename.ends_with(".deb") {
-                    packages.push(filename.to_string());
-                }
-            }
+
+        let mut empty = true;
+        for (i, package) in database.packages().enumerate() {
+            empty = false;
+            println!("{}. {} ({}, {})", i + 1, package.name.cyan(), package.version.yellow(), package.architecture);
         }
-        
-        if packages.is_empty() {
+        if empty {
             println!("{}", "No packages installed.".yellow());
-        } else {
-            for (i, package) in packages.iter().enumerate() {
-                println!("{}. {}", i + 1, package.cyan());
-            }
+        }
+    } else if let Some(index) = Index::open(&config)? {
+        println!("{}", "Available Packages:".green().bold());
+        println!("{}", "─".repeat(50));
+
+        for (i, (name, versions)) in index.packages().enumerate() {
+            let Some(package) = versions.first() else {
+                continue;
+            };
+            let version = package["version"].as_str().unwrap_or("Unknown");
+            let maintainer = package["maintainer"].as_str().unwrap_or("Unknown");
+
+            println!("{}. {} ({})", i + 1, name.cyan(), version.yellow());
+            println!("   Maintainer: {}", maintainer.dimmed());
+            println!();
         }
     } else {
-        let config = config?;
         let client = reqwest::Client::new();
-        
-        let url = format!("{}/api/packages", config.registry_url);
-        
-        let response = client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", config.auth_token.unwrap_or_default()))
-            .send()
-            .await?;
 
-        if response.status().is_success() {
-            let packages: serde_json::Value = response.json().await?;
-            
-            if let Some(packages_array) = packages.as_array() {
+        let url = Url::parse(&format!("{}/api/packages", config.registry_url))?;
+        let mut pages = Pages::new(url);
+        let interactive = std::io::stdout().is_terminal() && std::io::stdin().is_terminal();
+        let mut shown = 0;
+
+        loop {
+            // Only ask for the next page once this one has been read.
+            if shown > 0 && interactive && !pages.buffered() && !pages.exhausted() {
+                let total = pages.total.map(|total| format!(" of {}", total)).unwrap_or_default();
+                print!("{}", format!("-- {}{} shown; Enter for more, q to stop --", shown, total).dimmed());
+                std::io::stdout().flush()?;
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if answer.trim().eq_ignore_ascii_case("q") {
+                    break;
+                }
+            }
+
+            let package = match pages.next(&client, &mut config).await {
+                Ok(Some(package)) => package,
+                Ok(None) => break,
+                Err(e) => {
+                    println!("{}: {}", "Error".red(), e);
+                    break;
+                }
+            };
+
+            if shown == 0 {
                 println!("{}", "Available Packages:".green().bold());
                 println!("{}", "─".repeat(50));
-                
-                for (i, package) in packages_array.iter().enumerate() {
-                    let name = package["name"].as_str().unwrap_or("Unknown");
-                    let version = package["version"].as_str().unwrap_or("Unknown");
-                    let maintainer = package["maintainer"].as_str().unwrap_or("Unknown");
-                    
-                    println!("{}. {} ({})", i + 1, name.cyan(), version.yellow());
-                    println!("   Maintainer: {}", maintainer.dimmed());
-                    println!();
-                }
             }
-        } else {
-            println!("{}: {}", "Error".red(), response.text().await?);
+            shown += 1;
+
+            let name = package["name"].as_str().unwrap_or("Unknown");
+            let version = package["version"].as_str().unwrap_or("Unknown");
+            let maintainer = package["maintainer"].as_str().unwrap_or("Unknown");
+
+            println!("{}. {} ({})", shown, name.cyan(), version.yellow());
+            println!("   Maintainer: {}", maintainer.dimmed());
+            println!();
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -6,4 +6,18 @@ pub mod info;
 pub mod update;
 pub mod upgrade;
 pub mod auth;
-pub mod admin;
\ No newline at end of file
+pub mod admin;
+pub mod owner;
+pub mod org;
+pub mod tag;
+pub mod recover;
+pub mod history;
+pub mod rollback;
+pub mod key;
+pub mod verify;
+
+/// Scoped names such as `@infra/agent` contain a slash, which has to be
+/// escaped to stay a single path segment.
+pub fn encode_name(package: &str) -> String {
+    package.replace('/', "%2F")
+}
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use colored::*;
+use serde_json::json;
+use crate::commands::auth::send_authorized;
+use crate::config::Config;
+use crate::{OrgAction, TeamAction};
+
+pub async fn execute(action: OrgAction) -> Result<()> {
+    let mut config = Config::load()?;
+    let client = reqwest::Client::new();
+
+    if config.access_token().is_none() {
+        println!("{}", "Error: Authentication required".red());
+        println!("Please run 'cpkgs auth login' first");
+        return Ok(());
+    }
+
+    let base = format!("{}/api/orgs", config.registry_url);
+
+    match action {
+        OrgAction::Create { name, display_name } => {
+            let response = send_authorized(&client, &mut config, |auth| {
+                client
+                    .post(&base)
+                    .header("Authorization", auth)
+                    .json(&json!({ "name": name, "display_name": display_name }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}", "✓ Created organization".green(), name.cyan());
+                println!("Publish into it with 'cpkgs admin upload --scope {} <file>'", name);
+            } else {
+                println!("{}: {}", "Failed to create organization".red(), response.text().await?);
+            }
+        }
+
+        OrgAction::List => {
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.get(&base).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let orgs: Vec<serde_json::Value> = response.json().await?;
+
+                if orgs.is_empty() {
+                    println!("{}", "You do not belong to any organization.".yellow());
+                }
+                for org in &orgs {
+                    println!(
+                        "{} ({})",
+                        format!("@{}", org["name"].as_str().unwrap_or_default()).cyan(),
+                        org["role"].as_str().unwrap_or_default().to_lowercase()
+                    );
+                }
+            } else {
+                println!("{}: {}", "Error".red(), response.text().await?);
+            }
+        }
+
+        OrgAction::Info { name } => {
+            let url = format!("{}/{}", base, name);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.get(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let org: serde_json::Value = response.json().await?;
+
+                println!("{}", format!("@{}", name).cyan().bold());
+                if let Some(display_name) = org["display_name"].as_str() {
+                    println!("  {}", display_name);
+                }
+                println!();
+                println!("{}", "Members:".bold());
+                for member in org["members"].as_array().into_iter().flatten() {
+                    println!(
+                        "  {} ({})",
+                        member["username"].as_str().unwrap_or_default(),
+                        member["role"].as_str().unwrap_or_default().to_lowercase()
+                    );
+                }
+                println!();
+                println!("{}", "Teams:".bold());
+                for team in org["teams"].as_array().into_iter().flatten() {
+                    let members: Vec<&str> = team["members"]
+                        .as_array()
+                        .map(|members| members.iter().filter_map(|m| m.as_str()).collect())
+                        .unwrap_or_default();
+                    let publish = if team["can_publish"].as_bool().unwrap_or(false) {
+                        " [publish]".green()
+                    } else {
+                        "".normal()
+                    };
+                    println!("  {}{}: {}", team["name"].as_str().unwrap_or_default(), publish, members.join(", "));
+                }
+            } else {
+                println!("{}: {}", "Error".red(), response.text().await?);
+            }
+        }
+
+        OrgAction::Delete { name } => {
+            let url = format!("{}/{}", base, name);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}", "✓ Deleted organization".green(), name.cyan());
+            } else {
+                println!("{}: {}", "Failed to delete organization".red(), response.text().await?);
+            }
+        }
+
+        OrgAction::Add { org, username, role } => {
+            let url = format!("{}/{}/members/{}", base, org, username);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client
+                    .put(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "role": role }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {} ({})", "✓ Added".green(), username.cyan(), "to".green(), org.cyan(), role);
+            } else {
+                println!("{}: {}", "Failed to add member".red(), response.text().await?);
+            }
+        }
+
+        OrgAction::Remove { org, username } => {
+            let url = format!("{}/{}/members/{}", base, org, username);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {}", "✓ Removed".green(), username.cyan(), "from".green(), org.cyan());
+            } else {
+                println!("{}: {}", "Failed to remove member".red(), response.text().await?);
+            }
+        }
+
+        OrgAction::Team { action } => team(&client, &mut config, &base, action).await?,
+    }
+
+    Ok(())
+}
+
+async fn team(client: &reqwest::Client, config: &mut Config, base: &str, action: TeamAction) -> Result<()> {
+    match action {
+        TeamAction::Create { org, team, publish } => {
+            let url = format!("{}/{}/teams", base, org);
+            let response = send_authorized(client, config, |auth| {
+                client
+                    .post(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "name": team, "can_publish": publish }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}/{}", "✓ Created team".green(), org.cyan(), team.cyan());
+            } else {
+                println!("{}: {}", "Failed to create team".red(), response.text().await?);
+            }
+        }
+
+        TeamAction::Delete { org, team } => {
+            let url = format!("{}/{}/teams/{}", base, org, team);
+            let response = send_authorized(client, config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}/{}", "✓ Deleted team".green(), org.cyan(), team.cyan());
+            } else {
+                println!("{}: {}", "Failed to delete team".red(), response.text().await?);
+            }
+        }
+
+        TeamAction::Publish { org, team, enabled } => {
+            let url = format!("{}/{}/teams/{}", base, org, team);
+            let response = send_authorized(client, config, |auth| {
+                client
+                    .put(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "can_publish": enabled }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let verb = if enabled { "can now" } else { "can no longer" };
+                println!("{} {}/{} {} publish to @{}", "✓".green(), org.cyan(), team.cyan(), verb, org);
+            } else {
+                println!("{}: {}", "Failed to update team".red(), response.text().await?);
+            }
+        }
+
+        TeamAction::Add { org, team, username } => {
+            let url = format!("{}/{}/teams/{}/members/{}", base, org, team, username);
+            let response = send_authorized(client, config, |auth| {
+                client.put(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {}/{}", "✓ Added".green(), username.cyan(), "to".green(), org.cyan(), team.cyan());
+            } else {
+                println!("{}: {}", "Failed to add team member".red(), response.text().await?);
+            }
+        }
+
+        TeamAction::Remove { org, team, username } => {
+            let url = format!("{}/{}/teams/{}/members/{}", base, org, team, username);
+            let response = send_authorized(client, config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {}/{}", "✓ Removed".green(), username.cyan(), "from".green(), org.cyan(), team.cyan());
+            } else {
+                println!("{}: {}", "Failed to remove team member".red(), response.text().await?);
+            }
+        }
+    }
+
+    Ok(())
+}
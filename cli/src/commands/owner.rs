@@ -0,0 +1,148 @@
+use anyhow::Result;
+use colored::*;
+use serde_json::json;
+use crate::commands::{auth::send_authorized, encode_name};
+use crate::config::Config;
+use crate::OwnerAction;
+
+pub async fn execute(action: OwnerAction) -> Result<()> {
+    let mut config = Config::load()?;
+    let client = reqwest::Client::new();
+
+    if config.access_token().is_none() && !matches!(action, OwnerAction::List { .. }) {
+        println!("{}", "Error: Authentication required".red());
+        println!("Please run 'cpkgs auth login' first");
+        return Ok(());
+    }
+
+    match action {
+        OwnerAction::List { package } => {
+            let url = format!("{}/api/owners/{}", config.registry_url, encode_name(&package));
+            let response = client.get(&url).send().await?;
+
+            if response.status().is_success() {
+                let owners: Vec<serde_json::Value> = response.json().await?;
+
+                println!("{} {}", "Owners of".blue().bold(), package.cyan());
+                for owner in &owners {
+                    let role = owner["role"].as_str().unwrap_or_default();
+                    let role = if role == "OWNER" { "owner".green() } else { "maintainer".normal() };
+                    println!("  {} ({})", owner["username"].as_str().unwrap_or_default().cyan(), role);
+                }
+            } else {
+                println!("{}: {}", "Error".red(), response.text().await?);
+            }
+        }
+
+        OwnerAction::Add { package, username } => {
+            let url = format!("{}/api/owners/{}", config.registry_url, encode_name(&package));
+            let response = send_authorized(&client, &mut config, |auth| {
+                client
+                    .post(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "username": username }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {}", "✓ Added".green(), username.cyan(), "as a maintainer of".green(), package.cyan());
+            } else {
+                println!("{}: {}", "Failed to add maintainer".red(), response.text().await?);
+            }
+        }
+
+        OwnerAction::Remove { package, username } => {
+            let url = format!("{}/api/owners/{}/{}", config.registry_url, encode_name(&package), username);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {}", "✓ Removed".green(), username.cyan(), "from".green(), package.cyan());
+            } else {
+                println!("{}: {}", "Failed to remove maintainer".red(), response.text().await?);
+            }
+        }
+
+        OwnerAction::Transfer { package, username } => {
+            let url = format!("{}/api/transfers", config.registry_url);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client
+                    .post(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "package": package, "username": username }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let transfer: serde_json::Value = response.json().await?;
+                println!("{} {} {} {}", "✓ Offered ownership of".green(), package.cyan(), "to".green(), username.cyan());
+                println!("They can accept it with 'cpkgs owner accept {}'", transfer["id"].as_str().unwrap_or_default());
+            } else {
+                println!("{}: {}", "Transfer failed".red(), response.text().await?);
+            }
+        }
+
+        OwnerAction::Transfers => {
+            let url = format!("{}/api/transfers", config.registry_url);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.get(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let transfers: Vec<serde_json::Value> = response.json().await?;
+
+                if transfers.is_empty() {
+                    println!("{}", "No pending transfers.".yellow());
+                }
+                for transfer in &transfers {
+                    println!(
+                        "{} ({})",
+                        transfer["package"].as_str().unwrap_or_default().cyan(),
+                        transfer["id"].as_str().unwrap_or_default()
+                    );
+                    println!(
+                        "  {} → {}",
+                        transfer["from"].as_str().unwrap_or_default(),
+                        transfer["to"].as_str().unwrap_or_default()
+                    );
+                    println!("  Expires: {}", transfer["expires_at"].as_str().unwrap_or_default());
+                }
+            } else {
+                println!("{}: {}", "Error".red(), response.text().await?);
+            }
+        }
+
+        OwnerAction::Accept { id } => {
+            let url = format!("{}/api/transfers/{}/accept", config.registry_url, id);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.post(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{}", "✓ Transfer accepted, you are now an owner".green());
+            } else {
+                println!("{}: {}", "Accept failed".red(), response.text().await?);
+            }
+        }
+
+        OwnerAction::Decline { id } => {
+            let url = format!("{}/api/transfers/{}", config.registry_url, id);
+            let response = send_authorized(&client, &mut config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}", "✓ Cancelled transfer".green(), id);
+            } else {
+                println!("{}: {}", "Error".red(), response.text().await?);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use colored::*;
+use crate::config::Config;
+use crate::transaction::{journals, Transaction};
+
+pub async fn execute() -> Result<()> {
+    let config = Config::load()?;
+
+    let incomplete: Vec<_> = journals(&config)?
+        .into_iter()
+        .filter(|journal| journal.is_incomplete())
+        .collect();
+    if incomplete.is_empty() {
+        println!("{}", "No interrupted transactions.".green());
+        return Ok(());
+    }
+
+    for journal in incomplete {
+        println!("{} transaction {} ({})", "Rolling back".yellow().bold(), journal.id, journal.command);
+        Transaction::load(&config, journal.id)?.roll_back()?;
+        println!("{} transaction {}", "✓ Rolled back".green(), journal.id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use anyhow::{bail, Result};
+use colored::*;
+use dialoguer::Confirm;
+use crate::commands::history::print_journal;
+use crate::config::Config;
+use crate::transaction::{journals, State, Transaction};
+
+/// Undoes transaction `id` and every transaction committed after it, newest
+/// first, so the root ends up as it was before `id` ran.
+pub async fn execute(id: u64) -> Result<()> {
+    let config = Config::load()?;
+    let journals = journals(&config)?;
+
+    if let Some(incomplete) = journals.iter().find(|journal| journal.is_incomplete()) {
+        bail!("transaction {} was interrupted; run `cpkgs recover` first", incomplete.id);
+    }
+    match journals.iter().find(|journal| journal.id == id) {
+        None => bail!("transaction {} does not exist", id),
+        Some(journal) if journal.state != State::Committed => {
+            bail!("transaction {} was already rolled back", id)
+        }
+        Some(_) => {}
+    }
+
+    let undone: Vec<_> = journals
+        .iter()
+        .rev()
+        .filter(|journal| journal.id >= id && journal.state == State::Committed)
+        .collect();
+    if let Some(pruned) = undone.iter().find(|journal| journal.pruned) {
+        bail!("the backups of transaction {} have been pruned, so it can no longer be rolled back", pruned.id);
+    }
+
+    println!("The following transactions will be rolled back:");
+    for journal in &undone {
+        print_journal(journal);
+    }
+
+    if Confirm::new()
+        .with_prompt("Do you want to roll them back?")
+        .default(false)
+        .interact()?
+    {
+        for journal in undone {
+            Transaction::load(&config, journal.id)?.roll_back()?;
+            println!("{} transaction {}", "✓ Rolled back".green(), journal.id);
+        }
+    }
+
+    Ok(())
+}
@@ -1,42 +1,259 @@
 use anyhow::Result;
 use colored::*;
-use serde_json::Value;
+use reqwest::Url;
 use crate::config::Config;
+use crate::index::Index;
+use crate::pages::Pages;
+use serde_json::Value;
 
-pub async fn execute(query: String, limit: Option<usize>) -> Result<()> {
-    let config = Config::load()?;
+pub struct Filters {
+    pub tags: Vec<String>,
+    pub any: bool,
+    pub architecture: Option<String>,
+    pub maintainer: Option<String>,
+    pub depends: Option<String>,
+}
+
+pub async fn execute(query: Option<String>, limit: Option<usize>, filters: Filters) -> Result<()> {
+    let mut config = Config::load()?;
     let client = reqwest::Client::new();
-    
-    let url = format!("{}/api/packages/search/{}", config.registry_url, query);
-    
-    let response = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", config.auth_token.unwrap_or_default()))
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        let packages: Vec<Value> = response.json().await?;
-        
-        if packages.is_empty() {
-            println!("{}", "No packages found.".red());
-        } else {
-            println!("{}", "Search Results:".green().bold());
-            println!("{}", "─".repeat(50));
-            
-            for (i, package) in packages.iter().take(limit.unwrap_or(10)).enumerate() {
-                let name = package["name"].as_str().unwrap_or("Unknown");
-                let version = package["version"].as_str().unwrap_or("Unknown");
-                let description = package["description"].as_str().unwrap_or("No description");
-                
-                println!("{}. {} ({})", i + 1, name.cyan(), version.yellow());
-                println!("   {}", description.dimmed());
-                println!();
+
+    let limit = limit.unwrap_or(10);
+
+    // Without a query, list what the filters select.
+    if query.is_none() && filters.tags.is_empty() && filters.maintainer.is_none() {
+        println!("{}: give a search query, --tag or --maintainer", "Error".red());
+        return Ok(());
+    }
+
+    if let Some(index) = Index::open(&config)? {
+        let results = search_index(&index, query.as_deref(), &filters);
+        for (i, package) in results.iter().take(limit).enumerate() {
+            print_result(i + 1, package);
+        }
+        print_summary(results.len().min(limit), Some(results.len() as u64));
+        return Ok(());
+    }
+
+    let page_size = limit.to_string();
+
+    let mut params: Vec<(&str, &str)> = filters.tags.iter().map(|tag| ("tag", tag.as_str())).collect();
+    if !filters.tags.is_empty() {
+        params.push(("match", if filters.any { "any" } else { "all" }));
+    }
+    if let Some(maintainer) = &filters.maintainer {
+        params.push(("maintainer", maintainer));
+    }
+    params.push(("limit", &page_size));
+
+    let url = match &query {
+        Some(query) => {
+            if let Some(architecture) = &filters.architecture {
+                params.push(("architecture", architecture));
+            }
+            if let Some(depends) = &filters.depends {
+                params.push(("depends", depends));
             }
+            params.push(("q", query));
+            Url::parse_with_params(&format!("{}/api/search", config.registry_url), params)?
         }
-    } else {
-        println!("{}: {}", "Error".red(), response.text().await?);
+        None if filters.architecture.is_some() || filters.depends.is_some() => {
+            println!("{}: --arch and --depends need a search query", "Error".red());
+            return Ok(());
+        }
+        None => Url::parse_with_params(&format!("{}/api/packages", config.registry_url), params)?,
+    };
+
+    let mut pages = Pages::new(url);
+    let mut shown = 0;
+
+    while shown < limit {
+        let package = match pages.next(&client, &mut config).await {
+            Ok(Some(package)) => package,
+            Ok(None) => break,
+            Err(e) => {
+                println!("{}: {}", "Error".red(), e);
+                return Ok(());
+            }
+        };
+
+        shown += 1;
+        print_result(shown, &package);
     }
 
+    print_summary(shown, pages.total);
+
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_result(n: usize, package: &Value) {
+    if n == 1 {
+        println!("{}", "Search Results:".green().bold());
+        println!("{}", "─".repeat(50));
+    }
+
+    let name = package["name"].as_str().unwrap_or("Unknown");
+    let version = package["version"].as_str().unwrap_or("Unknown");
+    let description = package["description"].as_str().unwrap_or("No description");
+
+    // Search results come ranked, with the matching part of the
+    // description; plain listings have neither.
+    match package["relevance"].as_f64() {
+        Some(relevance) => println!(
+            "{}. {} ({}) {}",
+            n,
+            name.cyan(),
+            version.yellow(),
+            format!("[{:.2}]", relevance).dimmed()
+        ),
+        None => println!("{}. {} ({})", n, name.cyan(), version.yellow()),
+    }
+    match package["snippet"].as_str() {
+        Some(snippet) => println!("   {}", highlight(snippet)),
+        None => println!("   {}", description.dimmed()),
+    }
+    let tags: Vec<&str> = package["tags"]
+        .as_array()
+        .map(|tags| tags.iter().filter_map(|tag| tag["name"].as_str()).collect())
+        .unwrap_or_default();
+    if !tags.is_empty() {
+        println!("   {} {}", "Tags:".dimmed(), tags.join(", ").magenta());
+    }
+    println!();
+}
+
+fn print_summary(shown: usize, total: Option<u64>) {
+    match total {
+        _ if shown == 0 => println!("{}", "No packages found.".red()),
+        Some(total) if total > shown as u64 => println!(
+            "{}",
+            format!("Showing {} of {} matches; use --limit to see more.", shown, total).dimmed()
+        ),
+        _ => {}
+    }
+}
+
+/// Searches the local package index the way the registry would. Each
+/// package stands for its newest version that passes `--arch`, and ranks by
+/// where the query's words appear: in its name, then its tags, then its
+/// description. Without a query it is listed by name.
+fn search_index(index: &Index, query: Option<&str>, filters: &Filters) -> Vec<Value> {
+    let terms: Vec<String> = query
+        .map(|query| query.split_whitespace().map(str::to_ascii_lowercase).collect())
+        .unwrap_or_default();
+    let wanted: Vec<String> = filters.tags.iter().map(|tag| tag.to_ascii_lowercase()).collect();
+
+    let mut results: Vec<(f64, Value)> = Vec::new();
+    for (name, versions) in index.packages() {
+        let Some(package) = versions.iter().find(|version| {
+            filters.architecture.as_deref().is_none_or(|architecture| {
+                version["architecture"] == architecture || version["architecture"] == "all"
+            })
+        }) else {
+            continue;
+        };
+
+        let tags: Vec<String> = package["tags"]
+            .as_array()
+            .map(|tags| tags.iter().filter_map(|tag| tag["name"].as_str()).map(str::to_ascii_lowercase).collect())
+            .unwrap_or_default();
+        let tagged = if filters.any {
+            wanted.is_empty() || wanted.iter().any(|tag| tags.contains(tag))
+        } else {
+            wanted.iter().all(|tag| tags.contains(tag))
+        };
+        let maintained = filters
+            .maintainer
+            .as_deref()
+            .is_none_or(|maintainer| versions.iter().any(|version| version["maintainer"] == maintainer));
+        let depends = filters.depends.as_deref().is_none_or(|depends| {
+            package["dependencies"]
+                .as_array()
+                .is_some_and(|dependencies| dependencies.iter().any(|dep| dep["name"] == depends))
+        });
+        if !(tagged && maintained && depends) {
+            continue;
+        }
+
+        if terms.is_empty() {
+            results.push((0.0, package.clone()));
+            continue;
+        }
+
+        let name = name.to_ascii_lowercase();
+        let description = package["description"].as_str().unwrap_or_default();
+        let scores: Vec<f64> = terms
+            .iter()
+            .map(|term| {
+                if name == *term {
+                    1.0
+                } else if name.starts_with(term.as_str()) {
+                    0.75
+                } else if name.contains(term.as_str()) {
+                    0.5
+                } else if tags.contains(term) {
+                    0.4
+                } else if description.to_ascii_lowercase().contains(term.as_str()) {
+                    0.25
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        if scores.contains(&0.0) {
+            continue;
+        }
+
+        let relevance = scores.iter().sum::<f64>() / scores.len() as f64;
+        let mut package = package.clone();
+        package["relevance"] = relevance.into();
+        if let Some(snippet) = mark(description, &terms) {
+            package["snippet"] = snippet.into();
+        }
+        results.push((relevance, package));
+    }
+
+    // Stable, so equal scores stay in name order.
+    results.sort_by(|a, b| b.0.total_cmp(&a.0));
+    results.into_iter().map(|(_, package)| package).collect()
+}
+
+/// Wraps the occurrences of `terms` in `text` in `<mark>`, as the
+/// registry's snippets do, or None if there are none.
+fn mark(text: &str, terms: &[String]) -> Option<String> {
+    let lower = text.to_ascii_lowercase();
+    let mut marked = String::new();
+    let mut found = false;
+    let mut pos = 0;
+
+    while let Some(c) = text[pos..].chars().next() {
+        match terms.iter().filter(|term| lower[pos..].starts_with(term.as_str())).max_by_key(|term| term.len()) {
+            Some(term) => {
+                marked.push_str(&format!("<mark>{}</mark>", &text[pos..pos + term.len()]));
+                pos += term.len();
+                found = true;
+            }
+            None => {
+                marked.push(c);
+                pos += c.len_utf8();
+            }
+        }
+    }
+
+    found.then_some(marked)
+}
+
+/// Renders the `<mark>`ed matches in a snippet in bold.
+fn highlight(snippet: &str) -> String {
+    let mut rendered = String::new();
+    for (i, part) in snippet.split("<mark>").enumerate() {
+        match part.split_once("</mark>") {
+            Some((matched, rest)) if i > 0 => {
+                rendered.push_str(&matched.bold().to_string());
+                rendered.push_str(rest);
+            }
+            _ => rendered.push_str(part),
+        }
+    }
+    rendered
+}
@@ -0,0 +1,154 @@
+use anyhow::Result;
+use colored::*;
+use serde_json::json;
+use crate::commands::{auth::send_authorized, encode_name};
+use crate::config::Config;
+use crate::TagAction;
+
+pub async fn execute(config: &mut Config, action: TagAction) -> Result<()> {
+    let client = reqwest::Client::new();
+    let base = format!("{}/api/tags", config.registry_url);
+
+    match action {
+        TagAction::List => {
+            let response = client.get(&base).send().await?;
+
+            if response.status().is_success() {
+                let tags: Vec<serde_json::Value> = response.json().await?;
+
+                if tags.is_empty() {
+                    println!("{}", "No tags yet.".yellow());
+                }
+                for tag in &tags {
+                    println!(
+                        "{} {} ({} packages)",
+                        tag["name"].as_str().unwrap_or_default().cyan(),
+                        tag["color"].as_str().unwrap_or_default().dimmed(),
+                        tag["packages"]
+                    );
+                }
+            } else {
+                println!("{}: {}", "Error".red(), response.text().await?);
+            }
+        }
+
+        TagAction::Create { name, color } => {
+            let response = send_authorized(&client, config, |auth| {
+                client
+                    .post(&base)
+                    .header("Authorization", auth)
+                    .json(&json!({ "name": name, "color": color }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}", "✓ Created tag".green(), name.cyan());
+            } else {
+                println!("{}: {}", "Failed to create tag".red(), response.text().await?);
+            }
+        }
+
+        TagAction::Rename { name, new_name } => {
+            let url = format!("{}/{}", base, name);
+            let response = send_authorized(&client, config, |auth| {
+                client
+                    .put(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "name": new_name }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} → {}", "✓ Renamed tag".green(), name.cyan(), new_name.cyan());
+            } else {
+                println!("{}: {}", "Failed to rename tag".red(), response.text().await?);
+            }
+        }
+
+        TagAction::Color { name, color } => {
+            let url = format!("{}/{}", base, name);
+            let response = send_authorized(&client, config, |auth| {
+                client
+                    .put(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "color": color }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {}", "✓ Recolored tag".green(), name.cyan(), color);
+            } else {
+                println!("{}: {}", "Failed to recolor tag".red(), response.text().await?);
+            }
+        }
+
+        TagAction::Merge { name, into } => {
+            let url = format!("{}/{}/merge", base, name);
+            let response = send_authorized(&client, config, |auth| {
+                client
+                    .post(&url)
+                    .header("Authorization", auth)
+                    .json(&json!({ "into": into }))
+            })
+            .await?;
+
+            if response.status().is_success() {
+                let tag: serde_json::Value = response.json().await?;
+                println!(
+                    "{} {} {} {} ({} packages)",
+                    "✓ Merged".green(),
+                    name.cyan(),
+                    "into".green(),
+                    into.cyan(),
+                    tag["packages"]
+                );
+            } else {
+                println!("{}: {}", "Failed to merge tags".red(), response.text().await?);
+            }
+        }
+
+        TagAction::Delete { name } => {
+            let url = format!("{}/{}", base, name);
+            let response = send_authorized(&client, config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {}", "✓ Deleted tag".green(), name.cyan());
+            } else {
+                println!("{}: {}", "Failed to delete tag".red(), response.text().await?);
+            }
+        }
+
+        TagAction::Add { package, tag } => {
+            let url = format!("{}/api/packages/{}/tags/{}", config.registry_url, encode_name(&package), tag);
+            let response = send_authorized(&client, config, |auth| {
+                client.put(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {}", "✓ Tagged".green(), package.cyan(), "with".green(), tag.cyan());
+            } else {
+                println!("{}: {}", "Failed to tag package".red(), response.text().await?);
+            }
+        }
+
+        TagAction::Remove { package, tag } => {
+            let url = format!("{}/api/packages/{}/tags/{}", config.registry_url, encode_name(&package), tag);
+            let response = send_authorized(&client, config, |auth| {
+                client.delete(&url).header("Authorization", auth)
+            })
+            .await?;
+
+            if response.status().is_success() {
+                println!("{} {} {} {}", "✓ Removed tag".green(), tag.cyan(), "from".green(), package.cyan());
+            } else {
+                println!("{}: {}", "Failed to remove tag".red(), response.text().await?);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -2,46 +2,59 @@ use anyhow::Result;
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use crate::config::Config;
+use crate::index::{mode, Index, Mode, Refresh};
 
 pub async fn execute() -> Result<()> {
-    let config = Config::load()?;
-    
+    let mut config = Config::load()?;
+
+    if mode() == Mode::Offline {
+        println!("{}: the package index cannot be updated offline", "Error".red());
+        return Ok(());
+    }
+
     println!("{}", "Updating package index...".blue().bold());
-    
+
     let pb = ProgressBar::new_spinner();
     pb.set_style(
         ProgressStyle::default_spinner()
             .template("{spinner:.green} [{elapsed_precise}] {msg}")
             .unwrap()
     );
-    pb.set_message("Fetching package list...");
-    
+    pb.set_message("Fetching package changes...");
+
     let client = reqwest::Client::new();
-    let url = format!("{}/api/packages", config.registry_url);
-    
+
     pb.inc(1);
-    
-    let response = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", config.auth_token.unwrap_or_default()))
-        .send()
-        .await?;
-
-    if response.status().is_success() {
-        let packages: serde_json::Value = response.json().await?;
-        
-        pb.set_message("Caching package information...");
-        pb.inc(1);
-        
-        let cache_file = config.cache_dir.join("packages.json");
-        tokio::fs::write(cache_file, serde_json::to_string_pretty(&packages)?).await?;
-        
-        pb.finish_with_message("Package index updated successfully!");
-        println!("{}", "✓ Package index updated".green().bold());
-    } else {
-        pb.finish_with_message("Failed to update package index");
-        println!("{}: {}", "Error".red(), response.text().await?);
+
+    // A corrupt index is replaced by a fresh one.
+    let current = Index::load(&config).unwrap_or(None);
+
+    match Index::refresh(&client, &mut config, current).await {
+        Ok((index, refresh)) => {
+            pb.set_message("Caching package information...");
+            pb.inc(1);
+
+            index.save(&config)?;
+
+            pb.finish_with_message("Package index updated successfully!");
+            let summary = match refresh {
+                Refresh::Unchanged => "no changes".to_string(),
+                Refresh::Changes(1) => "1 package changed".to_string(),
+                Refresh::Changes(changed) => format!("{} packages changed", changed),
+                Refresh::Full => "full download".to_string(),
+            };
+            println!(
+                "{} ({} packages, {})",
+                "✓ Package index updated".green().bold(),
+                index.len(),
+                summary
+            );
+        }
+        Err(e) => {
+            pb.finish_with_message("Failed to update package index");
+            println!("{}: {}", "Error".red(), e);
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,51 +1,146 @@
 use anyhow::Result;
 use colored::*;
+use common::Version;
 use dialoguer::Confirm;
+use crate::commands::install::{apply, conflicting, print_relations, print_removals};
 use crate::config::Config;
+use crate::database::{Database, InstalledPackage};
+use crate::index::Index;
+use crate::resolver::{newer_than, Candidate, Plan, Resolver, Universe};
 
-pub async fn execute(all: bool) -> Result<()> {
-    let config = Config::load()?;
-    
-    if all {
-        println!("{}", "Upgrading all packages...".blue().bold());
-        
-        let client = reqwest::Client::new();
-        let url = format!("{}/api/packages", config.registry_url);
-        
-        let response = client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", config.auth_token.unwrap_or_default()))
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let packages: serde_json::Value = response.json().await?;
-            
-            if let Some(packages_array) = packages.as_array() {
-                println!("Found {} packages to check for updates", packages_array.len());
-                
-                if Confirm::new()
-                    .with_prompt("Do you want to upgrade all packages?")
-                    .default(false)
-                    .interact()?
-                {
-                    for package in packages_array {
-                        let name = package["name"].as_str().unwrap_or("Unknown");
-                        let version = package["version"].as_str().unwrap_or("Unknown");
-                        
-                        println!("Upgrading {} to {}...", name.cyan(), version.yellow());
-                    }
-                    
-                    println!("{}", "✓ All packages upgraded".green().bold());
-                }
+pub async fn execute(packages: Vec<String>, all: bool, dry_run: bool, yes: bool) -> Result<()> {
+    let mut config = Config::load()?;
+
+    if packages.is_empty() && !all {
+        println!("{}", "Please specify a package to upgrade or use --all for all packages".yellow());
+        println!("Usage: cpkgs upgrade <package>... | cpkgs upgrade --all");
+        return Ok(());
+    }
+
+    let index = Index::open(&config)?;
+    let mut database = Database::open(&config)?;
+
+    let names: Vec<String> = if all {
+        database.packages().map(|package| package.name.clone()).collect()
+    } else {
+        packages
+    };
+
+    let client = reqwest::Client::new();
+    // Every installed package, so conflicts they declare are known too.
+    let installed: Vec<String> = database.packages().map(|package| package.name.clone()).collect();
+    let universe = Universe::fetch(&client, &mut config, index.as_ref(), &installed, false).await?;
+
+    let mut roots = Vec::new();
+    for name in &names {
+        let Some(installed) = database.get(name) else {
+            println!("{} is not installed", name.cyan());
+            continue;
+        };
+        let current: Version = installed.version.parse()?;
+        match universe.latest(name) {
+            Some(latest) if latest.parsed > current => roots.push(newer_than(name, &installed.version)?),
+            Some(_) if !all => println!("{} {} is up to date", name.cyan(), installed.version.yellow()),
+            Some(_) => {}
+            None => println!("{} is not in the registry", name.cyan()),
+        }
+    }
+    if roots.is_empty() {
+        println!("{}", "Nothing to upgrade.".green());
+        return Ok(());
+    }
+
+    println!("{}", "Resolving dependencies...".blue());
+
+    let versions = database.packages().map(|package| (package.name.clone(), package.version.clone()));
+    let mut plan = match Resolver::new(&universe, false).prefer(versions).resolve(&roots) {
+        Ok(plan) => plan,
+        Err(err) => {
+            println!("{}: {}", "Cannot upgrade".red(), err);
+            return Ok(());
+        }
+    };
+    plan.install.retain(|package| {
+        database.get(&package.name).is_none_or(|installed| installed.version != package.version)
+    });
+
+    let removals = conflicting(&database, &universe, &plan);
+    print_plan(&database, &plan, &removals);
+
+    if dry_run {
+        println!("{}", "Dry run: nothing was changed.".yellow());
+        return Ok(());
+    }
+
+    if yes
+        || Confirm::new()
+            .with_prompt("Do you want to continue with the upgrade?")
+            .default(true)
+            .interact()?
+    {
+        let before: Vec<Option<String>> = plan
+            .install
+            .iter()
+            .map(|package| database.get(&package.name).map(|installed| installed.version.clone()))
+            .collect();
+        let remove: Vec<String> = removals.iter().map(|(installed, _)| installed.name.clone()).collect();
+
+        apply(&client, &mut config, &mut database, &plan.install, &remove).await?;
+
+        for (package, from) in plan.install.iter().zip(before) {
+            match from {
+                Some(from) => println!(
+                    "{} {} {} → {}",
+                    "✓ Upgraded".green(),
+                    package.name.cyan(),
+                    from.yellow(),
+                    package.version.yellow()
+                ),
+                None => println!("{} {}", "✓ Installed".green(), package.to_string().cyan()),
             }
-        } else {
-            println!("{}: {}", "Error".red(), response.text().await?);
         }
-    } else {
-        println!("{}", "Please specify a package to upgrade or use --all for all packages".yellow());
-        println!("Usage: cpkgs upgrade --all");
+        for name in remove {
+            println!("{} {}", "✓ Removed".green(), name.cyan());
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_plan(database: &Database, plan: &Plan, removals: &[(&InstalledPackage, &Candidate)]) {
+    let mut upgrades = Vec::new();
+    let mut downgrades = Vec::new();
+    let mut new = Vec::new();
+    for package in &plan.install {
+        match database.get(&package.name) {
+            Some(installed) => {
+                let line = format!("  {} {} → {}", package.name.cyan(), installed.version.yellow(), package.version.yellow());
+                match installed.version.parse::<Version>() {
+                    Ok(current) if current > package.parsed => downgrades.push(line),
+                    _ => upgrades.push(line),
+                }
+            }
+            None => new.push(format!("  {} {}", package.name.cyan(), package.version.yellow())),
+        }
+    }
+
+    for (heading, lines) in [
+        ("The following packages will be upgraded:", upgrades),
+        ("The following packages will be downgraded:", downgrades),
+        ("The following new packages will be installed:", new),
+    ] {
+        if !lines.is_empty() {
+            println!("{}", heading);
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+    }
+
+    print_removals(removals);
+
+    let total: u64 = plan.install.iter().map(|p| p.size).sum();
+    println!("Total download size: {} bytes", total);
+
+    print_relations(plan);
+}
@@ -0,0 +1,67 @@
+use anyhow::{bail, Result};
+use colored::*;
+use std::fs;
+use crate::config::Config;
+use crate::database::{sha256_file, Database, InstalledPackage};
+
+/// Checks the files of one or every installed package against the
+/// checksums recorded when they were unpacked.
+pub async fn execute(name: Option<String>) -> Result<()> {
+    let config = Config::load()?;
+    let database = Database::open(&config)?;
+
+    let packages: Vec<&InstalledPackage> = match &name {
+        Some(name) => match database.get(name) {
+            Some(package) => vec![package],
+            None => bail!("{} is not installed", name),
+        },
+        None => database.packages().collect(),
+    };
+    if packages.is_empty() {
+        println!("{}", "No packages installed.".yellow());
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for package in packages {
+        let mut problems = Vec::new();
+        let mut unrecorded = 0;
+
+        for file in package.files.iter().filter(|file| !file.ends_with('/')) {
+            let path = config.root.join(file.trim_start_matches('/'));
+            if fs::symlink_metadata(&path).is_err() {
+                problems.push(format!("{} is missing", file));
+                continue;
+            }
+            let Some(expected) = package.checksums.get(file) else {
+                unrecorded += 1;
+                continue;
+            };
+            match sha256_file(&path) {
+                Ok(checksum) if checksum == *expected => {}
+                Ok(_) => problems.push(format!("{} has been modified", file)),
+                Err(err) => problems.push(format!("{}: {:#}", file, err)),
+            }
+        }
+
+        if problems.is_empty() {
+            print!("{} {} {}", "✓".green(), package.name.cyan(), package.version.yellow());
+            if unrecorded > 0 {
+                print!(" {}", format!("({} files have no recorded checksum)", unrecorded).dimmed());
+            }
+            println!();
+        } else {
+            println!("{} {} {}", "✗".red(), package.name.cyan(), package.version.yellow());
+            for problem in &problems {
+                println!("    {}", problem);
+            }
+            failed += problems.len();
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} files failed verification", failed);
+    }
+
+    Ok(())
+}
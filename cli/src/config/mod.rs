@@ -7,8 +7,51 @@ use anyhow::Result;
 pub struct Config {
     pub registry_url: String,
     pub auth_token: Option<String>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
     pub cache_dir: PathBuf,
-    pub install_dir: PathBuf,
+    /// Where packages are unpacked, as if it were `/`.
+    #[serde(default = "default_root")]
+    pub root: PathBuf,
+    /// The Debian architecture packages are installed for.
+    #[serde(default = "host_architecture")]
+    pub architecture: String,
+    /// Fingerprints of the OpenPGP keys packages and the repository index
+    /// must be signed with. Empty, signatures are not checked.
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    /// The apt suite of the registry's signed repository index.
+    #[serde(default = "default_suite")]
+    pub suite: String,
+    /// How old the package index may get before commands warn about it,
+    /// or refuse to use it with `--offline`.
+    #[serde(default = "default_index_max_age_hours")]
+    pub index_max_age_hours: u64,
+}
+
+fn default_root() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".cpkgs/root")
+}
+
+fn default_suite() -> String {
+    "stable".to_string()
+}
+
+fn default_index_max_age_hours() -> u64 {
+    24
+}
+
+/// The Debian name of the architecture cpkgs was built for.
+fn host_architecture() -> String {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "x86" => "i386",
+        "aarch64" => "arm64",
+        other => other,
+    }
+    .to_string()
 }
 
 impl Default for Config {
@@ -17,8 +60,13 @@ impl Default for Config {
         Self {
             registry_url: "http://localhost:3000".to_string(),
             auth_token: None,
+            refresh_token: None,
             cache_dir: home.join(".cpkgs/cache"),
-            install_dir: home.join(".cpkgs/packages"),
+            root: home.join(".cpkgs/root"),
+            architecture: host_architecture(),
+            trusted_keys: Vec::new(),
+            suite: default_suite(),
+            index_max_age_hours: default_index_max_age_hours(),
         }
     }
 }
@@ -50,16 +98,40 @@ impl Config {
         fs::write(config_file, content)?;
 
         fs::create_dir_all(&self.cache_dir)?;
-        fs::create_dir_all(&self.install_dir)?;
+        fs::create_dir_all(&self.root)?;
 
         Ok(())
     }
 
-    pub fn set_auth_token(&mut self, token: String) {
+    /// The token to authenticate with. `CPKGS_TOKEN` takes precedence over
+    /// the stored login so CI jobs can use an API token without touching
+    /// the config file.
+    pub fn access_token(&self) -> Option<String> {
+        std::env::var("CPKGS_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .or_else(|| self.auth_token.clone())
+    }
+
+    pub fn set_auth_token(&mut self, token: String, refresh_token: Option<String>) {
         self.auth_token = Some(token);
+        self.refresh_token = refresh_token;
     }
 
     pub fn clear_auth_token(&mut self) {
         self.auth_token = None;
+        self.refresh_token = None;
+    }
+
+    /// The GnuPG home holding the trusted keys.
+    pub fn keyring_dir(&self) -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".cpkgs/gnupg")
+    }
+
+    /// Where cpkgs keeps what it knows about the root, inside the root.
+    pub fn state_dir(&self) -> PathBuf {
+        self.root.join("var/lib/cpkgs")
     }
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+//! The record of what is installed in the root, kept as JSON under
+//! `<root>/var/lib/cpkgs/` so it moves with the root it describes.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+use crate::config::Config;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    pub architecture: String,
+    pub checksum: String,
+    /// Everything the package unpacked, as listed by `deb::extract_data`.
+    pub files: Vec<String>,
+    /// The SHA-256 of each regular file as it was unpacked, for `cpkgs
+    /// verify`. Packages installed before it was recorded have none.
+    #[serde(default)]
+    pub checksums: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Database {
+    #[serde(skip)]
+    path: PathBuf,
+    packages: BTreeMap<String, InstalledPackage>,
+}
+
+impl Database {
+    pub fn path(config: &Config) -> PathBuf {
+        config.state_dir().join("installed.json")
+    }
+
+    pub fn open(config: &Config) -> Result<Self> {
+        let path = Self::path(config);
+
+        let mut database: Database = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("{} is corrupt", path.display()))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Database::default(),
+            Err(err) => return Err(err).context(format!("failed to read {}", path.display())),
+        };
+        database.path = path;
+
+        Ok(database)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.packages.get(name)
+    }
+
+    /// Installed packages, by name.
+    pub fn packages(&self) -> impl Iterator<Item = &InstalledPackage> {
+        self.packages.values()
+    }
+
+    /// The installed package other than `except` that owns `file`.
+    pub fn owner(&self, file: &str, except: &str) -> Option<&InstalledPackage> {
+        self.packages
+            .values()
+            .filter(|package| package.name != except)
+            .find(|package| package.files.iter().any(|owned| owned == file))
+    }
+
+    pub fn insert(&mut self, package: InstalledPackage) {
+        self.packages.insert(package.name.clone(), package);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<InstalledPackage> {
+        self.packages.remove(name)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        save_json(&self.path, self)
+    }
+}
+
+/// Writes `value` to `path` as JSON, replacing the previous copy only once
+/// the new one is complete.
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// The hex SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -0,0 +1,95 @@
+//! Reading the payload of downloaded `.deb` archives.
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Component, Path},
+};
+use xz2::read::XzDecoder;
+
+/// Unpacks the payload of a package into `root` and returns the paths it
+/// installs, in the form recorded in the installed package database:
+/// absolute within the root, with directories ending in `/`, e.g. `/usr/`,
+/// `/usr/bin/` and `/usr/bin/tool`. Entries that would land outside `root`
+/// abort the extraction.
+pub fn extract_data(path: &Path, root: &Path) -> Result<Vec<String>> {
+    std::fs::create_dir_all(root)?;
+
+    with_data_archive(path, |mut tar| {
+        tar.set_preserve_permissions(true);
+        tar.set_overwrite(true);
+
+        let mut files = Vec::new();
+        for entry in tar.entries().context("failed to read data archive")? {
+            let mut entry = entry.context("failed to read data archive")?;
+            let Some(file) = manifest_path(&entry)? else {
+                continue;
+            };
+            if !entry
+                .unpack_in(root)
+                .with_context(|| format!("failed to unpack {}", file))?
+            {
+                bail!("refusing to unpack {} outside of {}", file, root.display());
+            }
+            files.push(file);
+        }
+        Ok(files)
+    })
+}
+
+/// Opens the `data.tar{,.gz,.xz,.zst}` member of a `.deb`.
+fn with_data_archive<T>(
+    path: &Path,
+    read: impl FnOnce(tar::Archive<Box<dyn Read + '_>>) -> Result<T>,
+) -> Result<T> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut archive = ar::Archive::new(BufReader::new(file));
+
+    while let Some(entry) = archive.next_entry() {
+        let entry = entry.context("not a valid .deb archive")?;
+        let identifier = String::from_utf8_lossy(entry.header().identifier())
+            .trim_end_matches('/')
+            .to_string();
+
+        if identifier.starts_with("data.tar") {
+            let reader: Box<dyn Read + '_> = match identifier.as_str() {
+                "data.tar" => Box::new(entry),
+                "data.tar.gz" => Box::new(GzDecoder::new(entry)),
+                "data.tar.xz" => Box::new(XzDecoder::new(entry)),
+                "data.tar.zst" => Box::new(zstd::stream::read::Decoder::new(entry)?),
+                other => bail!("unsupported data archive '{}'", other),
+            };
+            return read(tar::Archive::new(reader));
+        }
+    }
+
+    bail!("not a valid .deb archive: missing data.tar member")
+}
+
+/// The manifest form of an entry's path, or None for the archive's root
+/// (`./`) itself.
+fn manifest_path<R: Read>(entry: &tar::Entry<'_, R>) -> Result<Option<String>> {
+    let path = entry.path().context("invalid path in data archive")?;
+
+    let mut file = String::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(part) => {
+                file.push('/');
+                file.push_str(&part.to_string_lossy());
+            }
+            _ => bail!("refusing to unpack {}", path.display()),
+        }
+    }
+    if file.is_empty() {
+        return Ok(None);
+    }
+    if entry.header().entry_type().is_dir() {
+        file.push('/');
+    }
+
+    Ok(Some(file))
+}
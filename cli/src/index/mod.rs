@@ -0,0 +1,353 @@
+//! The local copy of the registry's package index that `cpkgs update` keeps
+//! in `<cache_dir>/index.json`: every version of every package, and the
+//! cursor of the last registry change it includes, so that later updates
+//! only fetch the packages that changed since.
+//!
+//! Commands that read package metadata use this copy when there is one.
+//! `--online` makes them ask the registry instead, and `--offline` makes a
+//! missing or too old copy an error rather than a warning.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use colored::*;
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::{BTreeMap, BTreeSet}, fs, io::ErrorKind, path::PathBuf, sync::OnceLock};
+use crate::commands::auth::send_authorized;
+use crate::config::Config;
+use crate::database::save_json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Use the index when there is one, warning when it is stale.
+    Auto,
+    /// Always ask the registry.
+    Online,
+    /// Only use the index, which must be there and fresh.
+    Offline,
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// Sets how commands get package metadata, for the rest of the process.
+pub fn set_mode(mode: Mode) {
+    let _ = MODE.set(mode);
+}
+
+pub fn mode() -> Mode {
+    MODE.get().copied().unwrap_or(Mode::Auto)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Index {
+    /// The last registry change the index includes.
+    cursor: i64,
+    /// The entity tag of the last response, for conditional requests.
+    etag: Option<String>,
+    /// When the registry last confirmed the index was current.
+    checked_at: DateTime<Utc>,
+    /// Every version of each package, newest first.
+    packages: BTreeMap<String, Vec<Value>>,
+}
+
+/// What `Index::refresh` had to transfer.
+pub enum Refresh {
+    /// The registry confirmed the index was current.
+    Unchanged,
+    /// Only the packages that changed, this many of them.
+    Changes(usize),
+    /// The whole index.
+    Full,
+}
+
+#[derive(Deserialize)]
+struct IndexPackage {
+    name: String,
+    versions: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct FullIndex {
+    cursor: i64,
+    packages: Vec<IndexPackage>,
+}
+
+#[derive(Deserialize)]
+struct Changes {
+    cursor: i64,
+    more: bool,
+    packages: Vec<IndexPackage>,
+}
+
+impl Index {
+    fn path(config: &Config) -> PathBuf {
+        config.cache_dir.join("index.json")
+    }
+
+    /// The cached index, or None before the first `cpkgs update`.
+    pub fn load(config: &Config) -> Result<Option<Self>> {
+        let path = Self::path(config);
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(Some(
+                serde_json::from_str(&content)
+                    .with_context(|| format!("{} is corrupt; run `cpkgs update`", path.display()))?,
+            )),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).context(format!("failed to read {}", path.display())),
+        }
+    }
+
+    /// The index commands should read package metadata from, or None when
+    /// they should ask the registry, depending on the mode.
+    pub fn open(config: &Config) -> Result<Option<Self>> {
+        let mode = mode();
+        if mode == Mode::Online {
+            return Ok(None);
+        }
+
+        let Some(index) = Self::load(config)? else {
+            if mode == Mode::Offline {
+                bail!("there is no package index to work offline with; run `cpkgs update` first");
+            }
+            return Ok(None);
+        };
+
+        let age = Utc::now() - index.checked_at;
+        if age.num_hours() >= config.index_max_age_hours as i64 {
+            if mode == Mode::Offline {
+                bail!(
+                    "the package index was last updated {} hours ago, more than index_max_age_hours ({}); run `cpkgs update`",
+                    age.num_hours(),
+                    config.index_max_age_hours
+                );
+            }
+            eprintln!(
+                "{}: the package index was last updated {} hours ago; run `cpkgs update` or pass --online",
+                "Warning".yellow(),
+                age.num_hours()
+            );
+        }
+
+        Ok(Some(index))
+    }
+
+    /// Brings the index up to date: through the changes feed when there is
+    /// an index to apply them to, otherwise, or when the registry no longer
+    /// knows its cursor, by downloading the whole index.
+    pub async fn refresh(client: &reqwest::Client, config: &mut Config, index: Option<Self>) -> Result<(Self, Refresh)> {
+        if let Some(mut index) = index
+            && let Some(refresh) = index.apply_changes(client, config).await?
+        {
+            return Ok((index, refresh));
+        }
+
+        let url = format!("{}/api/index", config.registry_url);
+        let response = send_authorized(client, config, |auth| {
+            client.get(&url).header("Authorization", auth)
+        })
+        .await?;
+        if !response.status().is_success() {
+            bail!("Could not fetch the package index: {}", response.text().await?);
+        }
+
+        let etag = entity_tag(&response);
+        let full: FullIndex = response.json().await?;
+        let index = Index {
+            cursor: full.cursor,
+            etag,
+            checked_at: Utc::now(),
+            packages: full.packages.into_iter().map(|package| (package.name, package.versions)).collect(),
+        };
+
+        Ok((index, Refresh::Full))
+    }
+
+    /// Applies the changes after the cursor, a page at a time. None when
+    /// the registry does not know the cursor.
+    async fn apply_changes(&mut self, client: &reqwest::Client, config: &mut Config) -> Result<Option<Refresh>> {
+        let mut changed = BTreeSet::new();
+        let mut first = true;
+
+        loop {
+            let url = format!("{}/api/index/changes?since={}", config.registry_url, self.cursor);
+            // Only the first request can be answered with "not modified";
+            // later pages are there because there were changes.
+            let etag = self.etag.clone().filter(|_| first);
+            let response = send_authorized(client, config, |auth| {
+                let mut request = client.get(&url).header("Authorization", auth);
+                if let Some(etag) = &etag {
+                    request = request.header(header::IF_NONE_MATCH, etag);
+                }
+                request
+            })
+            .await?;
+
+            match response.status() {
+                StatusCode::NOT_MODIFIED => {
+                    self.checked_at = Utc::now();
+                    return Ok(Some(Refresh::Unchanged));
+                }
+                StatusCode::GONE => return Ok(None),
+                status if !status.is_success() => {
+                    bail!("Could not fetch package index changes: {}", response.text().await?);
+                }
+                _ => {}
+            }
+
+            let etag = entity_tag(&response);
+            let changes: Changes = response.json().await?;
+            self.merge(changes.packages, &mut changed);
+            self.cursor = changes.cursor;
+            self.etag = etag;
+            first = false;
+
+            if !changes.more {
+                break;
+            }
+        }
+
+        self.checked_at = Utc::now();
+        Ok(Some(if changed.is_empty() { Refresh::Unchanged } else { Refresh::Changes(changed.len()) }))
+    }
+
+    /// Replaces the versions of each changed package, dropping the ones
+    /// left without any, and adds their names to `changed`.
+    fn merge(&mut self, packages: Vec<IndexPackage>, changed: &mut BTreeSet<String>) {
+        for package in packages {
+            changed.insert(package.name.clone());
+            if package.versions.is_empty() {
+                self.packages.remove(&package.name);
+            } else {
+                self.packages.insert(package.name, package.versions);
+            }
+        }
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        save_json(&Self::path(config), self)?;
+
+        // Left behind by versions that only cached the package list.
+        let _ = fs::remove_file(config.cache_dir.join("packages.json"));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Each package with all its versions, newest first, by name.
+    pub fn packages(&self) -> impl Iterator<Item = (&str, &[Value])> {
+        self.packages.iter().map(|(name, versions)| (name.as_str(), versions.as_slice()))
+    }
+
+    /// Every version of `name`, newest first, or None if the registry did
+    /// not have it.
+    pub fn versions(&self, name: &str) -> Option<&[Value]> {
+        self.packages.get(name).map(Vec::as_slice)
+    }
+}
+
+fn entity_tag(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// An index holding `packages`, as `cpkgs update` would have saved it.
+    fn index(packages: Value) -> Index {
+        serde_json::from_value(json!({
+            "cursor": 1,
+            "etag": "\"1\"",
+            "checked_at": "2026-10-18T12:00:00Z",
+            "packages": packages,
+        }))
+        .unwrap()
+    }
+
+    /// A page of the changes feed, as the registry sends it.
+    fn page(packages: Value) -> Vec<IndexPackage> {
+        let changes: Changes = serde_json::from_value(json!({
+            "cursor": 2,
+            "more": false,
+            "packages": packages,
+        }))
+        .unwrap();
+        changes.packages
+    }
+
+    fn versions(index: &Index, name: &str) -> Option<Vec<String>> {
+        index
+            .versions(name)
+            .map(|versions| versions.iter().map(|version| version["version"].as_str().unwrap().to_string()).collect())
+    }
+
+    #[test]
+    fn merge_replaces_adds_and_removes_packages() {
+        let mut index = index(json!({
+            "hello": [{ "version": "1.1" }, { "version": "1.0" }],
+            "old": [{ "version": "0.1" }],
+            "same": [{ "version": "2.0" }],
+        }));
+        let mut changed = BTreeSet::new();
+
+        index.merge(
+            page(json!([
+                { "name": "hello", "versions": [{ "version": "1.2" }, { "version": "1.1" }, { "version": "1.0" }] },
+                { "name": "old", "versions": [] },
+                { "name": "new", "versions": [{ "version": "0.1" }] },
+            ])),
+            &mut changed,
+        );
+
+        assert_eq!(versions(&index, "hello").unwrap(), ["1.2", "1.1", "1.0"]);
+        assert_eq!(versions(&index, "old"), None);
+        assert_eq!(versions(&index, "new").unwrap(), ["0.1"]);
+        assert_eq!(versions(&index, "same").unwrap(), ["2.0"]);
+        assert_eq!(changed.into_iter().collect::<Vec<_>>(), ["hello", "new", "old"]);
+        assert_eq!(index.len(), 3);
+    }
+
+    #[test]
+    fn merge_keeps_the_latest_page_and_counts_each_package_once() {
+        let mut index = index(json!({ "hello": [{ "version": "1.0" }] }));
+        let mut changed = BTreeSet::new();
+
+        index.merge(page(json!([{ "name": "hello", "versions": [{ "version": "1.1" }, { "version": "1.0" }] }])), &mut changed);
+        index.merge(page(json!([{ "name": "hello", "versions": [] }])), &mut changed);
+        index.merge(page(json!([{ "name": "hello", "versions": [{ "version": "2.0" }] }])), &mut changed);
+
+        assert_eq!(versions(&index, "hello").unwrap(), ["2.0"]);
+        assert_eq!(changed.len(), 1);
+    }
+
+    #[test]
+    fn merge_ignores_removing_a_package_it_never_had() {
+        let mut index = index(json!({}));
+        let mut changed = BTreeSet::new();
+
+        index.merge(page(json!([{ "name": "ghost", "versions": [] }])), &mut changed);
+
+        assert_eq!(versions(&index, "ghost"), None);
+        assert_eq!(index.len(), 0);
+    }
+
+    #[test]
+    fn saved_index_reads_back() {
+        let index = index(json!({ "hello": [{ "version": "1.0" }] }));
+
+        let saved: Index = serde_json::from_str(&serde_json::to_string(&index).unwrap()).unwrap();
+
+        assert_eq!(saved.cursor, 1);
+        assert_eq!(saved.etag.as_deref(), Some("\"1\""));
+        assert_eq!(saved.checked_at, index.checked_at);
+        assert_eq!(versions(&saved, "hello").unwrap(), ["1.0"]);
+    }
+}
@@ -0,0 +1,165 @@
+//! Trusted OpenPGP keys and the signatures made with them. Like the
+//! registry's signer, this drives `gpg`, against a keyring private to cpkgs;
+//! a signature only counts when its key's fingerprint is listed in
+//! `trusted_keys` in the config.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    thread,
+};
+use crate::config::Config;
+
+pub struct Keyring {
+    homedir: PathBuf,
+    trusted: Vec<String>,
+}
+
+impl Keyring {
+    pub fn open(config: &Config) -> Result<Self> {
+        let homedir = config.keyring_dir();
+        fs::create_dir_all(&homedir)?;
+        fs::set_permissions(&homedir, fs::Permissions::from_mode(0o700))?;
+
+        Ok(Self {
+            homedir,
+            trusted: config.trusted_keys.iter().map(|key| key.to_uppercase()).collect(),
+        })
+    }
+
+    /// Whether no key is trusted, in which case nothing can be verified.
+    pub fn is_empty(&self) -> bool {
+        self.trusted.is_empty()
+    }
+
+    /// The primary fingerprints and user IDs of the keys in an armored
+    /// key block, without importing them.
+    pub fn inspect(&self, armored: &[u8]) -> Result<Vec<(String, String)>> {
+        let listing = self.gpg(&["--with-colons", "--import-options", "show-only", "--import"], Some(armored))?;
+        Ok(parse_keys(&String::from_utf8_lossy(&listing.stdout)))
+    }
+
+    pub fn import(&self, armored: &[u8]) -> Result<()> {
+        self.gpg(&["--import"], Some(armored))?;
+        Ok(())
+    }
+
+    /// The user ID of an imported key.
+    pub fn user_id(&self, fingerprint: &str) -> Option<String> {
+        let listing = self.gpg(&["--with-colons", "--list-keys", fingerprint], None).ok()?;
+        parse_keys(&String::from_utf8_lossy(&listing.stdout))
+            .into_iter()
+            .next()
+            .map(|(_, user_id)| user_id)
+    }
+
+    pub fn delete(&self, fingerprint: &str) -> Result<()> {
+        self.gpg(&["--yes", "--delete-keys", fingerprint], None)?;
+        Ok(())
+    }
+
+    /// Checks a detached signature over the file at `data` and returns the
+    /// fingerprint of the trusted key that made it.
+    pub fn verify_detached(&self, signature: &[u8], data: &Path) -> Result<String> {
+        let data = data.to_string_lossy();
+        let output = self.gpg(&["--status-fd", "1", "--verify", "-", &data], Some(signature))?;
+        self.trusted_signer(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Checks a clearsigned message and returns the text that was signed.
+    pub fn verify_clearsigned(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let output = self.gpg(&["--status-fd", "2", "--output", "-", "--decrypt"], Some(message))?;
+        self.trusted_signer(&String::from_utf8_lossy(&output.stderr))?;
+        Ok(output.stdout)
+    }
+
+    /// The trusted key behind the good signature reported in `status`.
+    fn trusted_signer(&self, status: &str) -> Result<String> {
+        let signers: Vec<&str> = status
+            .lines()
+            .filter_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+            // The primary key's fingerprint comes last, after the
+            // signing subkey's and the signature's details.
+            .filter_map(|fields| fields.split_whitespace().last())
+            .collect();
+
+        match signers.iter().find(|signer| self.trusted.iter().any(|key| key == *signer)) {
+            Some(signer) => Ok(signer.to_string()),
+            None if signers.is_empty() => bail!("the signature is not valid"),
+            None => bail!("the signature was made by {}, which is not a trusted key", signers.join(", ")),
+        }
+    }
+
+    fn gpg(&self, args: &[&str], input: Option<&[u8]>) -> Result<Output> {
+        let mut child = Command::new("gpg")
+            .arg("--homedir")
+            .arg(&self.homedir)
+            .args(["--batch", "--no-tty", "--quiet", "--trust-model", "always"])
+            .args(args)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("failed to run gpg; is GnuPG installed?")?;
+
+        // Written from another thread so that gpg filling its output pipes
+        // while reading a large input cannot block both sides.
+        let writer = match (input, child.stdin.take()) {
+            (Some(input), Some(mut stdin)) => {
+                let input = input.to_vec();
+                Some(thread::spawn(move || stdin.write_all(&input)))
+            }
+            _ => None,
+        };
+
+        let output = child.wait_with_output()?;
+        let written = match writer {
+            Some(writer) => writer.join().map_err(|_| anyhow!("writing to gpg panicked"))?,
+            None => Ok(()),
+        };
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .filter(|line| !line.starts_with("[GNUPG:]"))
+                .collect::<Vec<_>>()
+                .join("; ");
+            bail!("gpg failed: {}", message.trim());
+        }
+        // Only now: gpg stops reading when it fails, and its message says
+        // more than the broken pipe that leaves.
+        written?;
+
+        Ok(output)
+    }
+}
+
+/// Pairs of primary fingerprint and first user ID from `--with-colons`
+/// output.
+fn parse_keys(listing: &str) -> Vec<(String, String)> {
+    let mut keys: Vec<(String, String)> = Vec::new();
+    let mut in_primary = false;
+
+    for line in listing.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.first() {
+            Some(&"pub") => in_primary = true,
+            Some(&"sub") => in_primary = false,
+            Some(&"fpr") if in_primary => {
+                keys.push((fields.get(9).unwrap_or(&"").to_string(), String::new()));
+                in_primary = false;
+            }
+            Some(&"uid") => {
+                if let Some((_, user_id)) = keys.last_mut().filter(|(_, user_id)| user_id.is_empty()) {
+                    *user_id = fields.get(9).unwrap_or(&"").to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    keys
+}
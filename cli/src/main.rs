@@ -1,15 +1,28 @@
 use clap::{Parser, Subcommand};
-use colored::*;
 use anyhow::Result;
 
 mod commands;
 mod config;
+mod database;
+mod deb;
+mod index;
+mod keyring;
+mod pages;
+mod repository;
+mod resolver;
+mod transaction;
 
 #[derive(Parser)]
 #[command(name = "cpkgs")]
 #[command(about = "Package Registry CLI for Sky Genesis Enterprise")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Only use the local package index; fail if it is missing or stale
+    #[arg(long, global = true, conflicts_with = "online")]
+    offline: bool,
+    /// Ask the registry instead of using the local package index
+    #[arg(long, global = true)]
+    online: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -18,15 +31,32 @@ struct Cli {
 enum Commands {
     /// Search for packages
     Search {
-        query: String,
+        query: Option<String>,
         #[arg(short, long)]
         limit: Option<usize>,
+        /// Only show packages with this tag; repeat for several
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        /// Match packages with any of the tags instead of all of them
+        #[arg(long)]
+        any: bool,
+        /// Only packages built for this architecture (or `all`)
+        #[arg(long = "arch")]
+        architecture: Option<String>,
+        #[arg(long)]
+        maintainer: Option<String>,
+        /// Only packages that depend on this package
+        #[arg(long)]
+        depends: Option<String>,
     },
-    /// Install a package
+    /// Install a package and its dependencies
     Install {
         name: String,
         #[arg(short, long)]
         version: Option<String>,
+        /// Also install recommended packages
+        #[arg(long)]
+        install_recommends: bool,
     },
     /// Remove a package
     Remove {
@@ -44,19 +74,57 @@ enum Commands {
         name: String,
         #[arg(short, long)]
         version: Option<String>,
+        /// List the files the installed version unpacked
+        #[arg(long)]
+        files: bool,
     },
     /// Update package index
     Update,
-    /// Upgrade packages
+    /// Upgrade installed packages to the newest versions in the package index
     Upgrade {
-        #[arg(short, long)]
+        packages: Vec<String>,
+        /// Upgrade every installed package
+        #[arg(short, long, conflicts_with = "packages")]
         all: bool,
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Roll back transactions that were interrupted
+    Recover,
+    /// Show the transactions that changed the install root
+    History,
+    /// Undo a transaction and every transaction after it
+    Rollback {
+        id: u64,
+    },
+    /// Check installed files against the checksums recorded when they were unpacked
+    Verify {
+        name: Option<String>,
+    },
+    /// Manage the keys packages must be signed with
+    Key {
+        #[command(subcommand)]
+        action: KeyAction,
     },
     /// User authentication
     Auth {
         #[command(subcommand)]
         action: AuthAction,
     },
+    /// Manage who may publish a package
+    Owner {
+        #[command(subcommand)]
+        action: OwnerAction,
+    },
+    /// Manage organizations, their members and teams
+    Org {
+        #[command(subcommand)]
+        action: OrgAction,
+    },
     /// Package management (admin only)
     Admin {
         #[command(subcommand)]
@@ -65,17 +133,169 @@ enum Commands {
 }
 
 #[derive(Subcommand)]
-enum AuthAction {
+pub enum AuthAction {
     Login,
     Logout,
     Register,
     Status,
+    /// Manage API tokens for CI and other non-interactive use
+    Token {
+        #[command(subcommand)]
+        action: TokenAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TokenAction {
+    /// Create a token; the secret is shown only once
+    Create {
+        name: String,
+        /// packages:publish, packages:delete or users:admin
+        #[arg(short, long = "scope", required = true)]
+        scopes: Vec<String>,
+        /// Only allow packages whose name matches this glob, e.g. "infra-*"
+        #[arg(short, long)]
+        package: Option<String>,
+        #[arg(short, long)]
+        expires_in_days: Option<i64>,
+    },
+    List,
+    Revoke {
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyAction {
+    /// List the trusted keys
+    List,
+    /// Trust the keys in an armored public key file
+    Add {
+        file: std::path::PathBuf,
+    },
+    /// Trust the keys the registry signs with, after comparing fingerprints
+    Fetch {
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Stop trusting a key
+    Remove {
+        fingerprint: String,
+    },
 }
 
 #[derive(Subcommand)]
-enum AdminAction {
+pub enum OwnerAction {
+    /// Show the owners and maintainers of a package
+    List {
+        package: String,
+    },
+    /// Allow a user to publish new versions of a package
+    Add {
+        package: String,
+        username: String,
+    },
+    /// Remove a maintainer, or yourself
+    Remove {
+        package: String,
+        username: String,
+    },
+    /// Offer ownership of a package to another user
+    Transfer {
+        package: String,
+        username: String,
+    },
+    /// List transfers offered to or by you
+    Transfers,
+    /// Accept a transfer offered to you
+    Accept {
+        id: String,
+    },
+    /// Decline or withdraw a transfer
+    Decline {
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum OrgAction {
+    /// Create an organization, with yourself as owner
+    Create {
+        name: String,
+        #[arg(short, long)]
+        display_name: Option<String>,
+    },
+    /// List the organizations you belong to
+    List,
+    /// Show an organization's members and teams
+    Info {
+        name: String,
+    },
+    /// Delete an organization without packages
+    Delete {
+        name: String,
+    },
+    /// Add a member or change their role
+    Add {
+        org: String,
+        username: String,
+        /// owner, admin or member
+        #[arg(short, long, default_value = "member")]
+        role: String,
+    },
+    /// Remove a member, or leave the organization
+    Remove {
+        org: String,
+        username: String,
+    },
+    /// Manage teams
+    Team {
+        #[command(subcommand)]
+        action: TeamAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TeamAction {
+    Create {
+        org: String,
+        team: String,
+        /// Let members publish packages in the organization's namespace
+        #[arg(long)]
+        publish: bool,
+    },
+    Delete {
+        org: String,
+        team: String,
+    },
+    /// Grant or withdraw the team's publish permission
+    Publish {
+        org: String,
+        team: String,
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    /// Add an organization member to a team
+    Add {
+        org: String,
+        team: String,
+        username: String,
+    },
+    Remove {
+        org: String,
+        team: String,
+        username: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminAction {
     Upload {
         package_file: String,
+        /// Publish into an organization's namespace, e.g. "infra" for @infra/<name>
+        #[arg(short, long)]
+        scope: Option<String>,
     },
     Remove {
         name: String,
@@ -86,18 +306,68 @@ enum AdminAction {
         username: String,
         email: String,
     },
+    /// Manage tags and the tags of packages
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TagAction {
+    /// List tags with their package counts
+    List,
+    Create {
+        name: String,
+        /// A color of the form #rrggbb
+        #[arg(short, long)]
+        color: Option<String>,
+    },
+    Rename {
+        name: String,
+        new_name: String,
+    },
+    Color {
+        name: String,
+        color: String,
+    },
+    /// Move every package tagged NAME over to INTO and delete NAME
+    Merge {
+        name: String,
+        into: String,
+    },
+    Delete {
+        name: String,
+    },
+    /// Tag a package
+    Add {
+        package: String,
+        tag: String,
+    },
+    /// Remove a tag from a package
+    Remove {
+        package: String,
+        tag: String,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    index::set_mode(match (cli.offline, cli.online) {
+        (true, _) => index::Mode::Offline,
+        (_, true) => index::Mode::Online,
+        _ => index::Mode::Auto,
+    });
+
     match cli.command {
-        Commands::Search { query, limit } => {
-            commands::search::execute(query, limit).await?;
+        Commands::Search { query, limit, tags, any, architecture, maintainer, depends } => {
+            let filters = commands::search::Filters { tags, any, architecture, maintainer, depends };
+            commands::search::execute(query, limit, filters).await?;
         }
-        Commands::Install { name, version } => {
-            commands::install::execute(name, version).await?;
+        Commands::Install { name, version, install_recommends } => {
+            commands::install::execute(name, version, install_recommends).await?;
         }
         Commands::Remove { name, version } => {
             commands::remove::execute(name, version).await?;
@@ -105,18 +375,39 @@ async fn main() -> Result<()> {
         Commands::List { installed } => {
             commands::list::execute(installed).await?;
         }
-        Commands::Info { name, version } => {
-            commands::info::execute(name, version).await?;
+        Commands::Info { name, version, files } => {
+            commands::info::execute(name, version, files).await?;
         }
         Commands::Update => {
             commands::update::execute().await?;
         }
-        Commands::Upgrade { all } => {
-            commands::upgrade::execute(all).await?;
+        Commands::Upgrade { packages, all, dry_run, yes } => {
+            commands::upgrade::execute(packages, all, dry_run, yes).await?;
+        }
+        Commands::Recover => {
+            commands::recover::execute().await?;
+        }
+        Commands::History => {
+            commands::history::execute().await?;
+        }
+        Commands::Rollback { id } => {
+            commands::rollback::execute(id).await?;
+        }
+        Commands::Verify { name } => {
+            commands::verify::execute(name).await?;
+        }
+        Commands::Key { action } => {
+            commands::key::execute(action).await?;
         }
         Commands::Auth { action } => {
             commands::auth::execute(action).await?;
         }
+        Commands::Owner { action } => {
+            commands::owner::execute(action).await?;
+        }
+        Commands::Org { action } => {
+            commands::org::execute(action).await?;
+        }
         Commands::Admin { action } => {
             commands::admin::execute(action).await?;
         }
@@ -0,0 +1,92 @@
+//! Walks the registry's paginated listings, fetching each page only once
+//! the previous one has been used up.
+
+use anyhow::{bail, Result};
+use reqwest::{header::LINK, Url};
+use serde_json::Value;
+use std::collections::VecDeque;
+use crate::commands::auth::send_authorized;
+use crate::config::Config;
+
+pub struct Pages {
+    next: Option<Url>,
+    buffered: VecDeque<Value>,
+    /// The number of matching rows, known once the first page is in.
+    pub total: Option<u64>,
+}
+
+impl Pages {
+    pub fn new(url: Url) -> Self {
+        Self {
+            next: Some(url),
+            buffered: VecDeque::new(),
+            total: None,
+        }
+    }
+
+    /// The next item, fetching another page when the buffered one runs out.
+    pub async fn next(&mut self, client: &reqwest::Client, config: &mut Config) -> Result<Option<Value>> {
+        if self.buffered.is_empty() {
+            self.fetch(client, config).await?;
+        }
+        Ok(self.buffered.pop_front())
+    }
+
+    /// Whether the items buffered so far are all there is.
+    pub fn exhausted(&self) -> bool {
+        self.buffered.is_empty() && self.next.is_none()
+    }
+
+    /// Whether the next item is already buffered, i.e. `next` will not
+    /// have to go back to the registry.
+    pub fn buffered(&self) -> bool {
+        !self.buffered.is_empty()
+    }
+
+    async fn fetch(&mut self, client: &reqwest::Client, config: &mut Config) -> Result<()> {
+        let Some(url) = self.next.take() else {
+            return Ok(());
+        };
+
+        let response = send_authorized(client, config, |auth| {
+            client.get(url.clone()).header("Authorization", auth)
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            bail!(response.text().await?);
+        }
+
+        self.total = response
+            .headers()
+            .get("X-Total-Count")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .or(self.total);
+        self.next = response
+            .headers()
+            .get(LINK)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| link_target(value, "next"))
+            .and_then(|target| response.url().join(&target).ok());
+
+        let items: Vec<Value> = response.json().await?;
+        self.buffered.extend(items);
+
+        Ok(())
+    }
+}
+
+/// The target of the link with relation `rel` in an RFC 8288 `Link` header.
+fn link_target(header: &str, rel: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (target, params) = link.trim().split_once(';')?;
+        let matches = params.split(';').any(|param| {
+            param
+                .trim()
+                .strip_prefix("rel=")
+                .is_some_and(|value| value.trim_matches('"').split_whitespace().any(|r| r == rel))
+        });
+        matches.then(|| target.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
@@ -0,0 +1,125 @@
+//! The registry's signed apt index (`/apt/dists/<suite>/`), used to check
+//! the checksums the API reports against ones signed by a trusted key.
+
+use anyhow::{bail, Result};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use crate::config::Config;
+use crate::keyring::Keyring;
+use crate::resolver::Candidate;
+
+pub struct SignedIndex {
+    base_url: String,
+    components: Vec<String>,
+    /// SHA-256 of each index file listed in `InRelease`, by path.
+    files: HashMap<String, String>,
+    /// SHA-256 of each package, by Debian name, version and architecture,
+    /// for the `Packages` files read so far.
+    packages: HashMap<(String, String, String), String>,
+    read: Vec<String>,
+}
+
+impl SignedIndex {
+    /// Reads and checks the suite's `InRelease`. None when the registry
+    /// does not sign its repository.
+    pub async fn fetch(client: &reqwest::Client, config: &Config, keyring: &Keyring) -> Result<Option<Self>> {
+        let base_url = format!("{}/apt/dists/{}", config.registry_url, config.suite);
+        let response = client.get(format!("{}/InRelease", base_url)).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("Could not fetch the repository index: {}", response.status());
+        }
+
+        let release = match keyring.verify_clearsigned(&response.bytes().await?) {
+            Ok(release) => String::from_utf8_lossy(&release).into_owned(),
+            Err(err) => bail!("The repository index failed verification: {:#}", err),
+        };
+
+        let mut index = SignedIndex {
+            base_url,
+            components: Vec::new(),
+            files: HashMap::new(),
+            packages: HashMap::new(),
+            read: Vec::new(),
+        };
+        let mut in_sha256 = false;
+        for line in release.lines() {
+            if let Some(entry) = line.strip_prefix(' ').filter(|_| in_sha256) {
+                if let [checksum, _size, path] = entry.split_whitespace().collect::<Vec<_>>()[..] {
+                    index.files.insert(path.to_string(), checksum.to_string());
+                }
+                continue;
+            }
+            in_sha256 = line == "SHA256:";
+            if let Some(components) = line.strip_prefix("Components:") {
+                index.components = components.split_whitespace().map(str::to_string).collect();
+            }
+        }
+
+        Ok(Some(index))
+    }
+
+    /// The signed SHA-256 of a package's artifact, or None if the index
+    /// does not list it.
+    pub async fn checksum(
+        &mut self,
+        client: &reqwest::Client,
+        config: &Config,
+        package: &Candidate,
+    ) -> Result<Option<String>> {
+        // Scoped packages are in their organization's component under their
+        // Debian name; the rest in the first component the suite lists.
+        let (component, name) = match package.name.strip_prefix('@').and_then(|name| name.split_once('/')) {
+            Some((org, name)) => (org, name),
+            None => (self.components.first().map(String::as_str).unwrap_or("main"), package.name.as_str()),
+        };
+        let architecture = match package.architecture.as_str() {
+            "all" => config.architecture.as_str(),
+            architecture => architecture,
+        };
+        let path = format!("{}/binary-{}/Packages", component, architecture);
+
+        if !self.read.contains(&path) {
+            self.read_packages(client, &path).await?;
+        }
+
+        let key = (name.to_string(), package.version.clone(), package.architecture.clone());
+        Ok(self.packages.get(&key).cloned())
+    }
+
+    async fn read_packages(&mut self, client: &reqwest::Client, path: &str) -> Result<()> {
+        let Some(expected) = self.files.get(path) else {
+            self.read.push(path.to_string());
+            return Ok(());
+        };
+
+        let response = client.get(format!("{}/{}", self.base_url, path)).send().await?;
+        if !response.status().is_success() {
+            bail!("Could not fetch {}: {}", path, response.status());
+        }
+        let data = response.bytes().await?;
+        if hex::encode(Sha256::digest(&data)) != *expected {
+            bail!("{} does not match the signed repository index", path);
+        }
+
+        for stanza in String::from_utf8_lossy(&data).split("\n\n") {
+            let field = |name: &str| {
+                stanza
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                    .map(|value| value.trim().to_string())
+            };
+            if let (Some(name), Some(version), Some(architecture), Some(checksum)) =
+                (field("Package"), field("Version"), field("Architecture"), field("SHA256"))
+            {
+                self.packages.insert((name, version, architecture), checksum);
+            }
+        }
+        self.read.push(path.to_string());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,806 @@
+//! Builds install plans from the dependency relations of registry packages.
+//!
+//! The registry's versions of every package that could take part in a plan
+//! are fetched up front, then a backtracking search picks one version per
+//! package such that every `Depends` group is satisfied and no two chosen
+//! packages conflict. Packages the registry does not know are left to the
+//! system package manager.
+
+use anyhow::{bail, Result};
+use colored::*;
+use common::{Constraint, Operator, Version};
+use reqwest::StatusCode;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+};
+use crate::commands::{auth::send_authorized, encode_name};
+use crate::config::Config;
+use crate::index::Index;
+
+/// Candidate versions tried before giving up on a plan. Backtracking is
+/// exponential in the worst case; real graphs settle far below this.
+const MAX_STEPS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationKind {
+    Requires,
+    Recommends,
+    Suggests,
+    Conflicts,
+}
+
+/// One package name with an optional version constraint, e.g. `libc6 (>= 2.28)`.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    pub constraint: Option<Constraint>,
+}
+
+impl Target {
+    fn matches(&self, candidate: &Candidate) -> bool {
+        self.matches_version(&candidate.name, &candidate.parsed)
+    }
+
+    fn matches_version(&self, name: &str, version: &Version) -> bool {
+        name == self.name
+            && self
+                .constraint
+                .as_ref()
+                .is_none_or(|constraint| constraint.matches(version))
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.constraint {
+            Some(constraint) => write!(f, "{} ({})", self.name, constraint),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// A relationship group: any one of the alternatives satisfies it.
+#[derive(Debug, Clone)]
+pub struct Relation {
+    pub kind: RelationKind,
+    pub alternatives: Vec<Target>,
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alternatives: Vec<String> = self.alternatives.iter().map(|t| t.to_string()).collect();
+        f.write_str(&alternatives.join(" | "))
+    }
+}
+
+/// A published version of a package.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub name: String,
+    /// The version exactly as the registry stores it.
+    pub version: String,
+    pub parsed: Version,
+    pub architecture: String,
+    pub size: u64,
+    pub checksum: String,
+    pub relations: Vec<Relation>,
+}
+
+impl Candidate {
+    /// Reads a version from its registry response. Versions that do not
+    /// parse cannot be ordered or constrained and are left out; relations
+    /// whose constraint does not parse are left out with a warning.
+    pub fn from_json(package: &serde_json::Value) -> Option<Candidate> {
+        let name = package["name"].as_str()?;
+        let version = package["version"].as_str()?.to_string();
+        let parsed = version.parse().ok()?;
+
+        let mut groups: BTreeMap<i64, Relation> = BTreeMap::new();
+        for dependency in package["dependencies"].as_array().into_iter().flatten() {
+            let kind = match dependency["dependency_type"]
+                .as_str()
+                .unwrap_or_default()
+                .to_ascii_uppercase()
+                .as_str()
+            {
+                "REQUIRES" => RelationKind::Requires,
+                "RECOMMENDS" => RelationKind::Recommends,
+                "SUGGESTS" => RelationKind::Suggests,
+                "CONFLICTS" => RelationKind::Conflicts,
+                _ => continue,
+            };
+            let Some(target_name) = dependency["name"].as_str() else {
+                continue;
+            };
+            let constraint = match dependency["version"].as_str().unwrap_or_default().trim() {
+                "" => None,
+                constraint => match constraint.parse() {
+                    Ok(constraint) => Some(constraint),
+                    Err(err) => {
+                        eprintln!(
+                            "{}: ignoring {} ({}) of {} {}: {}",
+                            "Warning".yellow(),
+                            target_name,
+                            constraint,
+                            name,
+                            version,
+                            err
+                        );
+                        continue;
+                    }
+                },
+            };
+            let target = Target {
+                name: target_name.to_string(),
+                constraint,
+            };
+
+            groups
+                .entry(dependency["alternative_group"].as_i64().unwrap_or_default())
+                .or_insert_with(|| Relation {
+                    kind,
+                    alternatives: Vec::new(),
+                })
+                .alternatives
+                .push(target);
+        }
+
+        Some(Candidate {
+            name: name.to_string(),
+            version,
+            parsed,
+            architecture: package["architecture"].as_str().unwrap_or_default().to_string(),
+            size: package["size"].as_u64().unwrap_or_default(),
+            checksum: package["checksum"].as_str().unwrap_or_default().to_string(),
+            relations: groups.into_values().collect(),
+        })
+    }
+
+    fn relations(&self, kind: RelationKind) -> impl Iterator<Item = &Relation> {
+        self.relations.iter().filter(move |r| r.kind == kind)
+    }
+
+    fn conflicts_with(&self, other: &Candidate) -> bool {
+        self.conflicts_with_version(&other.name, &other.parsed)
+    }
+
+    /// Whether this version declares a conflict with version `version` of
+    /// package `name`.
+    pub fn conflicts_with_version(&self, name: &str, version: &Version) -> bool {
+        self.name != name
+            && self
+                .relations(RelationKind::Conflicts)
+                .flat_map(|r| &r.alternatives)
+                .any(|target| target.matches_version(name, version))
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+/// Every version of a package the registry has, or None if it has none.
+async fn fetch_versions(client: &reqwest::Client, config: &mut Config, name: &str) -> Result<Option<Vec<serde_json::Value>>> {
+    let url = format!("{}/api/packages/{}/versions", config.registry_url, encode_name(name));
+    let response = send_authorized(client, config, |auth| {
+        client.get(&url).header("Authorization", auth)
+    })
+    .await?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        bail!("Could not fetch {}: {}", name, response.text().await?);
+    }
+
+    Ok(Some(response.json().await?))
+}
+
+/// The registry's versions of every package a plan may need, newest first.
+#[derive(Default)]
+pub struct Universe {
+    packages: HashMap<String, Vec<Candidate>>,
+}
+
+impl Universe {
+    /// Fetches the versions of `roots` and, transitively, of everything
+    /// their dependencies (and recommendations, if wanted) could pull in,
+    /// from the local package index when given one.
+    pub async fn fetch(
+        client: &reqwest::Client,
+        config: &mut Config,
+        index: Option<&Index>,
+        roots: &[String],
+        install_recommends: bool,
+    ) -> Result<Self> {
+        let mut universe = Universe::default();
+        let mut seen: HashSet<String> = roots.iter().cloned().collect();
+        let mut queue: VecDeque<String> = roots.iter().cloned().collect();
+
+        while let Some(name) = queue.pop_front() {
+            let versions = match index {
+                Some(index) => match index.versions(&name) {
+                    Some(versions) => versions.to_vec(),
+                    None => continue,
+                },
+                None => match fetch_versions(client, config, &name).await? {
+                    Some(versions) => versions,
+                    None => continue,
+                },
+            };
+            let mut candidates: Vec<Candidate> = versions
+                .iter()
+                .filter_map(Candidate::from_json)
+                .filter(|c| c.architecture == config.architecture || c.architecture == "all")
+                .collect();
+            candidates.sort_by(|a, b| b.parsed.cmp(&a.parsed));
+
+            for candidate in &candidates {
+                let relations = candidate.relations.iter().filter(|r| {
+                    r.kind == RelationKind::Requires
+                        || (install_recommends && r.kind == RelationKind::Recommends)
+                });
+                for target in relations.flat_map(|r| &r.alternatives) {
+                    if seen.insert(target.name.clone()) {
+                        queue.push_back(target.name.clone());
+                    }
+                }
+            }
+
+            universe.packages.insert(name, candidates);
+        }
+
+        Ok(universe)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.packages.contains_key(name)
+    }
+
+    /// The newest version of a package.
+    pub fn latest(&self, name: &str) -> Option<&Candidate> {
+        self.candidates(name).first()
+    }
+
+    /// The registry's copy of one version of a package.
+    pub fn find(&self, name: &str, version: &str) -> Option<&Candidate> {
+        self.candidates(name).iter().find(|candidate| candidate.version == version)
+    }
+
+    fn candidates(&self, name: &str) -> &[Candidate] {
+        self.packages.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// What installing a set of packages involves.
+pub struct Plan<'a> {
+    /// Packages to install, dependencies before their dependents.
+    pub install: Vec<&'a Candidate>,
+    /// Requirements left to the system because the registry has none of
+    /// their alternatives, with the package that needs them.
+    pub external: Vec<(&'a Candidate, &'a Relation)>,
+    /// Recommendations that were not installed.
+    pub recommended: Vec<&'a Relation>,
+    pub suggested: Vec<&'a Relation>,
+}
+
+/// A requirement still to be satisfied, with the chain of packages that
+/// led to it.
+#[derive(Clone)]
+struct Obligation<'a> {
+    relation: &'a Relation,
+    optional: bool,
+    chain: Vec<&'a Candidate>,
+}
+
+#[derive(Clone, Default)]
+struct State<'a> {
+    selected: BTreeMap<&'a str, (&'a Candidate, Vec<&'a Candidate>)>,
+    external: Vec<(&'a Candidate, &'a Relation)>,
+}
+
+/// Why a branch of the search failed. The failure found deepest in the
+/// dependency chain is usually the one worth reporting.
+struct Failure {
+    depth: usize,
+    message: String,
+}
+
+pub struct Resolver<'a> {
+    universe: &'a Universe,
+    install_recommends: bool,
+    preferred: HashMap<String, String>,
+    steps: usize,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(universe: &'a Universe, install_recommends: bool) -> Self {
+        Self {
+            universe,
+            install_recommends,
+            preferred: HashMap::new(),
+            steps: 0,
+        }
+    }
+
+    /// Tries these versions, by package name, before newer ones, so that
+    /// installed dependencies are only upgraded when they have to be.
+    pub fn prefer(mut self, versions: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.preferred.extend(versions);
+        self
+    }
+
+    /// Finds versions of `roots` and their dependencies that can be
+    /// installed together, or explains why there are none.
+    pub fn resolve(&mut self, roots: &'a [Relation]) -> Result<Plan<'a>> {
+        let pending = roots
+            .iter()
+            .map(|relation| Obligation {
+                relation,
+                optional: false,
+                chain: Vec::new(),
+            })
+            .collect();
+
+        let state = self
+            .solve(State::default(), pending)
+            .map_err(|failure| anyhow::anyhow!(failure.message))?;
+
+        Ok(self.plan(state))
+    }
+
+    fn solve(&mut self, state: State<'a>, mut pending: VecDeque<Obligation<'a>>) -> Result<State<'a>, Failure> {
+        let Some(obligation) = pending.pop_front() else {
+            return Ok(state);
+        };
+        let relation = obligation.relation;
+
+        let satisfied = relation.alternatives.iter().any(|target| {
+            state
+                .selected
+                .get(target.name.as_str())
+                .is_some_and(|(candidate, _)| target.matches(candidate))
+        });
+        if satisfied {
+            return self.solve(state, pending);
+        }
+
+        if !relation.alternatives.iter().any(|t| self.universe.contains(&t.name)) {
+            let mut state = state;
+            if let Some(dependent) = obligation.chain.last() {
+                state.external.push((dependent, relation));
+            } else {
+                return Err(Failure {
+                    depth: 0,
+                    message: format!("{} was not found in the registry", relation),
+                });
+            }
+            return self.solve(state, pending);
+        }
+
+        let depth = obligation.chain.len();
+        let needed_by = describe_need(&obligation.chain, relation);
+        let mut failure: Option<Failure> = None;
+        let mut record = |found: Failure| {
+            if failure.as_ref().is_none_or(|f| found.depth >= f.depth) {
+                failure = Some(found);
+            }
+        };
+
+        for target in &relation.alternatives {
+            if let Some((chosen, chain)) = state.selected.get(target.name.as_str()) {
+                record(Failure {
+                    depth,
+                    message: format!("{}, but {} is already {}", needed_by, chosen, describe_origin(chain)),
+                });
+                continue;
+            }
+
+            let candidates = self.universe.candidates(&target.name);
+            let preferred = self.preferred.get(&target.name);
+            let (first, rest): (Vec<&'a Candidate>, Vec<&'a Candidate>) = candidates
+                .iter()
+                .filter(|c| target.matches(c))
+                .partition(|c| preferred == Some(&c.version));
+            let mut matched = false;
+            for candidate in first.into_iter().chain(rest) {
+                matched = true;
+
+                let conflict = state.selected.values().find(|(chosen, _)| {
+                    candidate.conflicts_with(chosen) || chosen.conflicts_with(candidate)
+                });
+                if let Some((chosen, chain)) = conflict {
+                    record(Failure {
+                        depth,
+                        message: format!(
+                            "{} ({}) conflicts with {} ({})",
+                            candidate,
+                            needed_by,
+                            chosen,
+                            describe_origin(chain)
+                        ),
+                    });
+                    continue;
+                }
+
+                self.steps += 1;
+                if self.steps > MAX_STEPS {
+                    return Err(Failure {
+                        depth: usize::MAX,
+                        message: format!(
+                            "Gave up after trying {} combinations of versions; the dependencies are too tangled to resolve",
+                            MAX_STEPS
+                        ),
+                    });
+                }
+
+                let mut chain = obligation.chain.clone();
+                chain.push(candidate);
+
+                let mut next_state = state.clone();
+                next_state
+                    .selected
+                    .insert(candidate.name.as_str(), (candidate, obligation.chain.clone()));
+
+                let mut next_pending = pending.clone();
+                for relation in &candidate.relations {
+                    let optional = match relation.kind {
+                        RelationKind::Requires => false,
+                        RelationKind::Recommends if self.install_recommends => true,
+                        _ => continue,
+                    };
+                    next_pending.push_back(Obligation {
+                        relation,
+                        optional,
+                        chain: chain.clone(),
+                    });
+                }
+
+                match self.solve(next_state, next_pending) {
+                    Ok(solved) => return Ok(solved),
+                    Err(found) if found.depth == usize::MAX => return Err(found),
+                    Err(found) => record(found),
+                }
+            }
+
+            if !matched && self.universe.contains(&target.name) {
+                let available: Vec<&str> = candidates.iter().map(|c| c.version.as_str()).collect();
+                record(Failure {
+                    depth,
+                    message: if available.is_empty() {
+                        format!("{}, but the registry has no build of {} for this architecture", needed_by, target.name)
+                    } else {
+                        format!(
+                            "{}, but the registry only has {} {}",
+                            needed_by,
+                            target.name,
+                            available.join(", ")
+                        )
+                    },
+                });
+            }
+        }
+
+        if obligation.optional {
+            return self.solve(state, pending);
+        }
+
+        Err(failure.unwrap_or_else(|| Failure {
+            depth,
+            message: format!("{} cannot be satisfied", needed_by),
+        }))
+    }
+
+    fn plan(&self, state: State<'a>) -> Plan<'a> {
+        // Dependencies first, so each package lands after what it needs.
+        let mut install = Vec::new();
+        let mut visited = HashSet::new();
+        for (candidate, _) in state.selected.values() {
+            visit(candidate, &state, &mut visited, &mut install);
+        }
+
+        let unselected = |kind: RelationKind| -> Vec<&'a Relation> {
+            let mut relations: Vec<&'a Relation> = Vec::new();
+            for (candidate, _) in state.selected.values() {
+                for relation in candidate.relations(kind) {
+                    let chosen = relation
+                        .alternatives
+                        .iter()
+                        .any(|t| state.selected.contains_key(t.name.as_str()));
+                    if !chosen && !relations.iter().any(|r| r.to_string() == relation.to_string()) {
+                        relations.push(relation);
+                    }
+                }
+            }
+            relations
+        };
+
+        Plan {
+            install,
+            recommended: unselected(RelationKind::Recommends),
+            suggested: unselected(RelationKind::Suggests),
+            external: state.external,
+        }
+    }
+}
+
+fn visit<'a>(
+    candidate: &'a Candidate,
+    state: &State<'a>,
+    visited: &mut HashSet<&'a str>,
+    install: &mut Vec<&'a Candidate>,
+) {
+    if !visited.insert(candidate.name.as_str()) {
+        return;
+    }
+    for relation in candidate.relations(RelationKind::Requires) {
+        for target in &relation.alternatives {
+            if let Some((dependency, _)) = state.selected.get(target.name.as_str()) {
+                visit(dependency, state, visited, install);
+            }
+        }
+    }
+    install.push(candidate);
+}
+
+/// Renders `app 1.0 → libfoo 2.0`.
+fn describe_chain(chain: &[&Candidate]) -> String {
+    chain.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" → ")
+}
+
+fn describe_origin(chain: &[&Candidate]) -> String {
+    if chain.is_empty() {
+        "requested".to_string()
+    } else {
+        format!("required by {}", describe_chain(chain))
+    }
+}
+
+fn describe_need(chain: &[&Candidate], relation: &Relation) -> String {
+    if chain.is_empty() {
+        format!("{} was requested", relation)
+    } else {
+        format!("{} depends on {}", describe_chain(chain), relation)
+    }
+}
+
+/// The relation a user asks for on the command line: a name, optionally
+/// pinned to one version.
+pub fn requested(name: &str, version: Option<&str>) -> Result<Relation> {
+    let constraint = match version {
+        Some(version) => Some(Constraint {
+            operator: Operator::Exactly,
+            version: version.parse()?,
+        }),
+        None => None,
+    };
+
+    Ok(Relation {
+        kind: RelationKind::Requires,
+        alternatives: vec![Target {
+            name: name.to_string(),
+            constraint,
+        }],
+    })
+}
+
+/// The relation `cpkgs upgrade` asks for: any version of `name` later than
+/// the installed `version`.
+pub fn newer_than(name: &str, version: &str) -> Result<Relation> {
+    Ok(Relation {
+        kind: RelationKind::Requires,
+        alternatives: vec![Target {
+            name: name.to_string(),
+            constraint: Some(Constraint {
+                operator: Operator::Later,
+                version: version.parse()?,
+            }),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A relation written as in a control file, e.g. `liba (>= 1.0) | libb`.
+    fn relation(kind: RelationKind, text: &str) -> Relation {
+        let alternatives = text
+            .split('|')
+            .map(|alternative| match alternative.trim().split_once(" (") {
+                Some((name, constraint)) => Target {
+                    name: name.to_string(),
+                    constraint: Some(constraint.trim_end_matches(')').parse().unwrap()),
+                },
+                None => Target {
+                    name: alternative.trim().to_string(),
+                    constraint: None,
+                },
+            })
+            .collect();
+        Relation { kind, alternatives }
+    }
+
+    /// A package's name, version and relations.
+    type Package<'a> = (&'a str, &'a str, &'a [(RelationKind, &'a str)]);
+
+    fn universe(packages: &[Package]) -> Universe {
+        let mut universe = Universe::default();
+        for (name, version, relations) in packages {
+            universe.packages.entry(name.to_string()).or_default().push(Candidate {
+                name: name.to_string(),
+                version: version.to_string(),
+                parsed: version.parse().unwrap(),
+                architecture: "all".to_string(),
+                size: 0,
+                checksum: String::new(),
+                relations: relations.iter().map(|(kind, text)| relation(*kind, text)).collect(),
+            });
+        }
+        for candidates in universe.packages.values_mut() {
+            candidates.sort_by(|a, b| b.parsed.cmp(&a.parsed));
+        }
+        universe
+    }
+
+    fn install(plan: &Plan) -> Vec<String> {
+        plan.install.iter().map(|candidate| candidate.to_string()).collect()
+    }
+
+    use RelationKind::*;
+
+    #[test]
+    fn installs_the_newest_versions_dependencies_first() {
+        let universe = universe(&[
+            ("app", "1.0", &[(Requires, "lib (>= 1.0)")]),
+            ("app", "2.0", &[(Requires, "lib (>= 2.0)")]),
+            ("lib", "1.0", &[]),
+            ("lib", "2.0", &[]),
+        ]);
+        let roots = [requested("app", None).unwrap()];
+
+        let plan = Resolver::new(&universe, false).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["lib 2.0", "app 2.0"]);
+    }
+
+    #[test]
+    fn takes_the_first_alternative_that_works() {
+        let universe = universe(&[
+            ("app", "1.0", &[(Requires, "liba (>= 2.0) | libb")]),
+            ("liba", "1.0", &[]),
+            ("libb", "1.0", &[]),
+            ("tool", "1.0", &[(Requires, "liba | libb")]),
+        ]);
+
+        let roots = [requested("app", None).unwrap()];
+        let plan = Resolver::new(&universe, false).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["libb 1.0", "app 1.0"]);
+
+        let roots = [requested("tool", None).unwrap()];
+        let plan = Resolver::new(&universe, false).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["liba 1.0", "tool 1.0"]);
+    }
+
+    #[test]
+    fn avoids_conflicting_versions() {
+        let universe = universe(&[
+            ("app", "1.0", &[(Requires, "lib"), (Requires, "tool")]),
+            ("lib", "1.0", &[]),
+            ("lib", "2.0", &[(Conflicts, "tool (<< 2.0)")]),
+            ("tool", "1.0", &[]),
+        ]);
+        let roots = [requested("app", None).unwrap()];
+
+        let plan = Resolver::new(&universe, false).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["lib 1.0", "tool 1.0", "app 1.0"]);
+    }
+
+    #[test]
+    fn explains_conflicts_it_cannot_avoid() {
+        let universe = universe(&[
+            ("app", "1.0", &[(Requires, "lib"), (Requires, "tool")]),
+            ("lib", "1.0", &[(Conflicts, "tool")]),
+            ("tool", "1.0", &[]),
+        ]);
+        let roots = [requested("app", None).unwrap()];
+
+        let err = Resolver::new(&universe, false).resolve(&roots).err().unwrap();
+        assert!(err.to_string().contains("conflicts with lib 1.0"), "{}", err);
+    }
+
+    #[test]
+    fn backtracks_when_a_later_requirement_rules_a_choice_out() {
+        let universe = universe(&[
+            ("app", "1.0", &[(Requires, "lib"), (Requires, "tool")]),
+            ("lib", "1.0", &[]),
+            ("lib", "2.0", &[]),
+            ("tool", "1.0", &[(Requires, "lib (<< 2.0)")]),
+        ]);
+        let roots = [requested("app", None).unwrap()];
+
+        let plan = Resolver::new(&universe, false).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["lib 1.0", "tool 1.0", "app 1.0"]);
+    }
+
+    #[test]
+    fn gives_up_after_max_steps() {
+        // Twenty packages with two versions each and a requirement none of
+        // them can meet: every one of the 2^20 combinations fails.
+        let names: Vec<String> = (0..20).map(|i| format!("lib{}", i)).collect();
+        let requires: Vec<(RelationKind, &str)> = names
+            .iter()
+            .map(|name| (Requires, name.as_str()))
+            .chain([(Requires, "missing (>= 2.0)")])
+            .collect();
+        let mut packages: Vec<Package> = vec![("app", "1.0", &requires), ("missing", "1.0", &[])];
+        for name in &names {
+            packages.push((name, "1.0", &[]));
+            packages.push((name, "2.0", &[]));
+        }
+        let universe = universe(&packages);
+        let roots = [requested("app", None).unwrap()];
+
+        let mut resolver = Resolver::new(&universe, false);
+        let err = resolver.resolve(&roots).err().unwrap();
+        assert!(err.to_string().starts_with("Gave up"), "{}", err);
+        assert_eq!(resolver.steps, MAX_STEPS + 1);
+    }
+
+    #[test]
+    fn keeps_preferred_versions_that_still_fit() {
+        let universe = universe(&[
+            ("app", "1.0", &[(Requires, "lib (>= 1.0)")]),
+            ("tool", "1.0", &[(Requires, "lib (>= 2.0)")]),
+            ("lib", "1.0", &[]),
+            ("lib", "2.0", &[]),
+            ("lib", "3.0", &[]),
+        ]);
+        let installed = || [("lib".to_string(), "1.0".to_string())];
+
+        let roots = [requested("app", None).unwrap()];
+        let plan = Resolver::new(&universe, false).prefer(installed()).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["lib 1.0", "app 1.0"]);
+
+        let roots = [requested("tool", None).unwrap()];
+        let plan = Resolver::new(&universe, false).prefer(installed()).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["lib 3.0", "tool 1.0"]);
+    }
+
+    #[test]
+    fn leaves_unknown_dependencies_to_the_system() {
+        let universe = universe(&[("app", "1.0", &[(Requires, "libc6 (>= 2.28)")])]);
+
+        let roots = [requested("app", None).unwrap()];
+        let plan = Resolver::new(&universe, false).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["app 1.0"]);
+        assert_eq!(plan.external.len(), 1);
+        assert_eq!(plan.external[0].1.to_string(), "libc6 (>= 2.28)");
+
+        let roots = [requested("libc6", None).unwrap()];
+        assert!(Resolver::new(&universe, false).resolve(&roots).is_err());
+    }
+
+    #[test]
+    fn skips_recommendations_that_cannot_be_met() {
+        let universe = universe(&[
+            ("app", "1.0", &[(Recommends, "extra (>= 2.0)"), (Recommends, "docs"), (Suggests, "tool")]),
+            ("extra", "1.0", &[]),
+            ("docs", "1.0", &[]),
+            ("tool", "1.0", &[]),
+        ]);
+        let roots = [requested("app", None).unwrap()];
+
+        let plan = Resolver::new(&universe, true).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["app 1.0", "docs 1.0"]);
+        assert_eq!(plan.recommended.len(), 1);
+        assert_eq!(plan.suggested.len(), 1);
+
+        let plan = Resolver::new(&universe, false).resolve(&roots).unwrap();
+        assert_eq!(install(&plan), ["app 1.0"]);
+        assert_eq!(plan.recommended.len(), 2);
+    }
+}
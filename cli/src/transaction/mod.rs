@@ -0,0 +1,777 @@
+//! Changes to the install root, applied all-or-nothing.
+//!
+//! A transaction unpacks every package it installs into a staging area next
+//! to the root, then writes a journal of the file changes it is about to
+//! make before making them. Files it replaces or removes are moved into the
+//! transaction's backup directory rather than deleted. Undoing a change
+//! only looks at what is on disk, so replaying the journal backwards is safe
+//! whether the change was made, half made or never made: that is how a
+//! failed or interrupted transaction is rolled back, at the time or by
+//! `cpkgs recover` on a later run, and how `cpkgs rollback` undoes a
+//! committed one.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+};
+use crate::commands::encode_name;
+use crate::config::Config;
+use crate::database::{save_json, sha256_file, Database, InstalledPackage};
+use crate::deb;
+use crate::resolver::Candidate;
+
+/// Committed transactions keep their backups, and so can be rolled back,
+/// until this many newer ones have been committed.
+const KEPT_BACKUPS: usize = 20;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Set while a transaction runs, when Ctrl-C stops the transaction rather
+/// than the process.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    /// Started but not committed. Found on a later run, it was interrupted.
+    Pending,
+    Committed,
+    /// Being undone. Found on a later run, the undo was interrupted.
+    RollingBack,
+    RolledBack,
+}
+
+/// A package the transaction installs, upgrades, downgrades or removes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub name: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// One step of a transaction. Paths are in the manifest form, absolute
+/// within the root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Change {
+    CreateDir { path: String },
+    /// Moves the staged file of `package` into place.
+    Create { path: String, package: String },
+    /// As `Create`, backing up what is there first.
+    Replace { path: String, package: String },
+    /// Moves the file into the backup.
+    Remove { path: String },
+    /// Removes the directory if nothing else is left in it.
+    RemoveDir { path: String },
+}
+
+impl Change {
+    fn path(&self) -> &str {
+        match self {
+            Change::CreateDir { path }
+            | Change::Create { path, .. }
+            | Change::Replace { path, .. }
+            | Change::Remove { path }
+            | Change::RemoveDir { path } => path,
+        }
+    }
+
+    /// The order changes are applied in: directories before what goes in
+    /// them, files before the directories they are removed from.
+    fn sort_key(&self) -> (u8, usize) {
+        let depth = self.path().matches('/').count();
+        match self {
+            Change::CreateDir { .. } => (0, depth),
+            Change::Create { .. } | Change::Replace { .. } => (1, 0),
+            Change::Remove { .. } => (2, 0),
+            Change::RemoveDir { .. } => (3, usize::MAX - depth),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pub id: u64,
+    /// The command line that started the transaction.
+    pub command: String,
+    pub started_at: DateTime<Utc>,
+    pub state: State,
+    pub packages: Vec<PackageChange>,
+    /// Set once the backups have been pruned, after which the transaction
+    /// can no longer be rolled back.
+    #[serde(default)]
+    pub pruned: bool,
+    changes: Vec<Change>,
+}
+
+impl Journal {
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self.state, State::Pending | State::RollingBack)
+    }
+}
+
+pub struct Transaction {
+    dir: PathBuf,
+    root: PathBuf,
+    database_path: PathBuf,
+    journal: Journal,
+    /// Whether this transaction was begun rather than loaded, and so owns
+    /// the handling of Ctrl-C until it is dropped.
+    running: bool,
+}
+
+impl Transaction {
+    /// Starts a transaction, refusing while an interrupted one is waiting
+    /// for `cpkgs recover`. Until the transaction is over, a first Ctrl-C
+    /// stops it at the next safe point, where it is rolled back, and a
+    /// second one quits at once, leaving the rollback to `cpkgs recover`.
+    pub fn begin(config: &Config) -> Result<Self> {
+        let journals = journals(config)?;
+        if let Some(incomplete) = journals.iter().find(|journal| journal.is_incomplete()) {
+            bail!(
+                "transaction {} ({}) was interrupted; run `cpkgs recover` first",
+                incomplete.id,
+                incomplete.command
+            );
+        }
+
+        let id = journals.last().map_or(1, |journal| journal.id + 1);
+        let dir = transactions_dir(config).join(id.to_string());
+        fs::create_dir_all(&dir)?;
+
+        let database_path = Database::path(config);
+        match fs::copy(&database_path, dir.join("database.json")) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        RUNNING.store(true, Ordering::SeqCst);
+        handle_ctrl_c();
+
+        let transaction = Transaction {
+            dir,
+            root: config.root.clone(),
+            database_path,
+            journal: Journal {
+                id,
+                command: std::iter::once("cpkgs".to_string())
+                    .chain(std::env::args().skip(1))
+                    .map(|arg| if arg.contains(' ') { format!("'{}'", arg) } else { arg })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                started_at: Utc::now(),
+                state: State::Pending,
+                packages: Vec::new(),
+                pruned: false,
+                changes: Vec::new(),
+            },
+            running: true,
+        };
+        transaction.save()?;
+
+        Ok(transaction)
+    }
+
+    /// Opens a transaction from its journal to roll it back.
+    pub fn load(config: &Config, id: u64) -> Result<Self> {
+        let dir = transactions_dir(config).join(id.to_string());
+        let content = fs::read_to_string(dir.join("journal.json"))
+            .with_context(|| format!("transaction {} does not exist", id))?;
+
+        Ok(Transaction {
+            dir,
+            root: config.root.clone(),
+            database_path: Database::path(config),
+            journal: serde_json::from_str(&content)
+                .with_context(|| format!("the journal of transaction {} is corrupt", id))?,
+            running: false,
+        })
+    }
+
+    /// Fails once Ctrl-C has been pressed, so long-running steps between
+    /// changes can stop early.
+    pub fn check_interrupted(&self) -> Result<()> {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            bail!("interrupted");
+        }
+        Ok(())
+    }
+
+    /// Unpacks a downloaded package into the staging area and plans putting
+    /// it in place of any installed version, recording it in `database`.
+    pub fn install(&mut self, database: &mut Database, package: &Candidate, archive: &Path) -> Result<()> {
+        let staging = self.staging_dir(&package.name);
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        let files = deb::extract_data(archive, &staging)?;
+
+        for file in files.iter().filter(|file| !file.ends_with('/')) {
+            if let Some(owner) = database.owner(file, &package.name) {
+                bail!("{} would overwrite {}, which belongs to {}", package, file, owner.name);
+            }
+        }
+
+        let mut checksums = BTreeMap::new();
+        for file in &files {
+            let staged = self.staged(&package.name, file);
+            if fs::symlink_metadata(&staged).is_ok_and(|metadata| metadata.is_file()) {
+                checksums.insert(file.clone(), sha256_file(&staged)?);
+            }
+        }
+
+        let previous = database.remove(&package.name);
+        let shipped: HashSet<&String> = files.iter().collect();
+
+        for file in &files {
+            // Another package in this transaction may have planned to remove
+            // the file it is taking over.
+            self.journal
+                .changes
+                .retain(|change| !matches!(change, Change::Remove { path } if path == file));
+
+            let existing = match self.target(file) {
+                Ok(target) => fs::symlink_metadata(&target).ok(),
+                // Its directory is still to be created.
+                Err(err) if is_not_found(&err) => None,
+                Err(err) => return Err(err),
+            };
+            if file.ends_with('/') {
+                let planned = self
+                    .journal
+                    .changes
+                    .iter()
+                    .any(|change| matches!(change, Change::CreateDir { path } if path == file));
+                if existing.is_none() && !planned {
+                    self.journal.changes.push(Change::CreateDir { path: file.clone() });
+                }
+                continue;
+            }
+
+            let path = file.clone();
+            let package = package.name.clone();
+            self.journal.changes.push(match existing {
+                Some(metadata) if metadata.is_dir() => {
+                    bail!("{} would replace the directory {}", package, file);
+                }
+                Some(_) => Change::Replace { path, package },
+                None => Change::Create { path, package },
+            });
+        }
+
+        if let Some(previous) = &previous {
+            let stale: Vec<String> = previous
+                .files
+                .iter()
+                .filter(|file| !shipped.contains(file))
+                .cloned()
+                .collect();
+            self.plan_removal(database, &stale);
+        }
+
+        self.journal.packages.push(PackageChange {
+            name: package.name.clone(),
+            from: previous.map(|previous| previous.version),
+            to: Some(package.version.clone()),
+        });
+        database.insert(InstalledPackage {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            architecture: package.architecture.clone(),
+            checksum: package.checksum.clone(),
+            files,
+            checksums,
+        });
+
+        Ok(())
+    }
+
+    /// Plans removing an installed package, dropping it from `database`.
+    pub fn remove(&mut self, database: &mut Database, name: &str) -> Result<()> {
+        let Some(installed) = database.remove(name) else {
+            bail!("{} is not installed", name);
+        };
+        self.plan_removal(database, &installed.files);
+
+        self.journal.packages.push(PackageChange {
+            name: installed.name,
+            from: Some(installed.version),
+            to: None,
+        });
+
+        Ok(())
+    }
+
+    /// Plans removing `files`, except those another package owns or this
+    /// transaction is putting in place.
+    fn plan_removal(&mut self, database: &Database, files: &[String]) {
+        for file in files {
+            if database.owner(file, "").is_some()
+                || self.journal.changes.iter().any(|change| change.path() == file)
+            {
+                continue;
+            }
+            let path = file.clone();
+            self.journal.changes.push(if file.ends_with('/') {
+                Change::RemoveDir { path }
+            } else {
+                Change::Remove { path }
+            });
+        }
+    }
+
+    /// Commits the planned changes unless planning them failed, rolling
+    /// everything back if anything goes wrong on the way.
+    pub fn finish(mut self, config: &Config, database: &Database, planned: Result<()>) -> Result<()> {
+        if let Err(err) = planned.and_then(|_| self.commit(database)) {
+            let id = self.journal.id;
+            self.roll_back().with_context(|| {
+                format!("{:#}; rolling back transaction {} failed too, run `cpkgs recover`", err, id)
+            })?;
+            return Err(err.context(format!("transaction {} was rolled back", id)));
+        }
+
+        prune_backups(config)
+    }
+
+    /// Journals the planned changes, makes them and saves `database`.
+    fn commit(&mut self, database: &Database) -> Result<()> {
+        self.journal.changes.sort_by_key(Change::sort_key);
+        self.save()?;
+
+        for change in &self.journal.changes {
+            self.check_interrupted()?;
+            self.apply(change)
+                .with_context(|| format!("failed to update {}", change.path()))?;
+        }
+
+        database.save()?;
+        self.journal.state = State::Committed;
+        self.save()?;
+
+        let _ = fs::remove_dir_all(self.dir.join("staging"));
+        Ok(())
+    }
+
+    /// Undoes the transaction, whether it was committed, failed part way
+    /// or was interrupted, and restores the database from before it.
+    pub fn roll_back(&mut self) -> Result<()> {
+        if self.journal.pruned {
+            bail!("the backups of transaction {} have been pruned", self.journal.id);
+        }
+
+        self.journal.state = State::RollingBack;
+        self.save()?;
+
+        for change in self.journal.changes.iter().rev() {
+            self.undo(change)
+                .with_context(|| format!("failed to restore {}", change.path()))?;
+        }
+
+        let snapshot = self.dir.join("database.json");
+        if snapshot.exists() {
+            fs::copy(&snapshot, &self.database_path)?;
+        } else {
+            match fs::remove_file(&self.database_path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+
+        self.journal.state = State::RolledBack;
+        self.save()?;
+
+        let _ = fs::remove_dir_all(self.dir.join("staging"));
+        let _ = fs::remove_dir_all(self.dir.join("backup"));
+        Ok(())
+    }
+
+    fn apply(&self, change: &Change) -> Result<()> {
+        let target = self.target(change.path())?;
+        match change {
+            Change::CreateDir { .. } => match fs::create_dir(&target) {
+                Err(err) if err.kind() != ErrorKind::AlreadyExists => return Err(err.into()),
+                _ => {}
+            },
+            Change::Create { path, package } => {
+                fs::rename(self.staged(package, path), &target)?;
+            }
+            Change::Replace { path, package } => {
+                move_into(&target, &self.backup(path))?;
+                fs::rename(self.staged(package, path), &target)?;
+            }
+            Change::Remove { path } => move_into(&target, &self.backup(path))?,
+            Change::RemoveDir { .. } => {
+                // Fails while the directory still holds files that are not
+                // ours, which is exactly when it should stay.
+                let _ = fs::remove_dir(&target);
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&self, change: &Change) -> Result<()> {
+        let target = match self.target(change.path()) {
+            // Nothing was created in a directory that is not there.
+            Err(err) if matches!(change, Change::CreateDir { .. } | Change::Create { .. }) && is_not_found(&err) => {
+                return Ok(());
+            }
+            target => target?,
+        };
+        match change {
+            Change::CreateDir { .. } => {
+                let _ = fs::remove_dir(&target);
+            }
+            Change::Create { .. } => match fs::remove_file(&target) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            },
+            Change::Replace { path, .. } | Change::Remove { path } => {
+                let backup = self.backup(path);
+                if fs::symlink_metadata(&backup).is_ok() {
+                    fs::rename(&backup, &target)?;
+                }
+            }
+            Change::RemoveDir { .. } => fs::create_dir_all(&target)?,
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        save_json(&self.dir.join("journal.json"), &self.journal)
+    }
+
+    /// Where `path` is on disk. Its directory is resolved and must be
+    /// within the root, so that neither a symlinked directory, whether a
+    /// package shipped it or it was there before, nor `..` can lead a change
+    /// outside it. The last component is not followed.
+    fn target(&self, path: &str) -> Result<PathBuf> {
+        let joined = self.root.join(path.trim_start_matches('/'));
+        let (Some(parent), Some(name)) = (joined.parent(), joined.file_name()) else {
+            bail!("{} is not a path within {}", path, self.root.display());
+        };
+
+        let root = fs::canonicalize(&self.root)
+            .with_context(|| format!("failed to resolve {}", self.root.display()))?;
+        let parent = fs::canonicalize(parent)?;
+        if !parent.starts_with(&root) {
+            bail!("{} leads outside {}", path, self.root.display());
+        }
+
+        Ok(parent.join(name))
+    }
+
+    fn staging_dir(&self, package: &str) -> PathBuf {
+        self.dir.join("staging").join(encode_name(package))
+    }
+
+    fn staged(&self, package: &str, path: &str) -> PathBuf {
+        self.staging_dir(package).join(path.trim_start_matches('/'))
+    }
+
+    fn backup(&self, path: &str) -> PathBuf {
+        self.dir.join("backup").join(path.trim_start_matches('/'))
+    }
+}
+
+impl Drop for Transaction {
+    /// Lets Ctrl-C quit again once the transaction is over, however it
+    /// ended.
+    fn drop(&mut self) {
+        if self.running {
+            RUNNING.store(false, Ordering::SeqCst);
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Takes over Ctrl-C for the rest of the process, the first time a
+/// transaction begins. Outside a transaction it still quits at once.
+fn handle_ctrl_c() {
+    static HANDLER: Once = Once::new();
+
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    HANDLER.call_once(|| {
+        runtime.spawn(async {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                interrupt();
+            }
+        });
+    });
+}
+
+/// Asks the running transaction to stop, or quits if there is none or it
+/// was already asked.
+fn interrupt() {
+    if !RUNNING.load(Ordering::SeqCst) || INTERRUPTED.swap(true, Ordering::SeqCst) {
+        std::process::exit(130);
+    }
+    eprintln!(
+        "{}: stopping at the next safe point; press Ctrl-C again to quit now",
+        "Warning".yellow()
+    );
+}
+
+/// Every transaction's journal, oldest first.
+pub fn journals(config: &Config) -> Result<Vec<Journal>> {
+    let dir = transactions_dir(config);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut journals = Vec::new();
+    for entry in entries {
+        let path = entry?.path().join("journal.json");
+        // A transaction that died before writing its journal changed
+        // nothing.
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        journals.push(
+            serde_json::from_str::<Journal>(&content)
+                .with_context(|| format!("{} is corrupt", path.display()))?,
+        );
+    }
+    journals.sort_by_key(|journal| journal.id);
+
+    Ok(journals)
+}
+
+/// Deletes the backups of all but the most recent committed transactions.
+pub fn prune_backups(config: &Config) -> Result<()> {
+    let committed: Vec<Journal> = journals(config)?
+        .into_iter()
+        .filter(|journal| journal.state == State::Committed && !journal.pruned)
+        .collect();
+
+    for journal in committed.iter().rev().skip(KEPT_BACKUPS) {
+        let mut transaction = Transaction::load(config, journal.id)?;
+        let _ = fs::remove_dir_all(transaction.dir.join("backup"));
+        transaction.journal.pruned = true;
+        transaction.save()?;
+    }
+
+    Ok(())
+}
+
+fn transactions_dir(config: &Config) -> PathBuf {
+    config.state_dir().join("transactions")
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|err| err.kind() == ErrorKind::NotFound)
+}
+
+/// Renames `from` to `to`, creating the parents of `to`.
+fn move_into(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Transactions share the Ctrl-C flags, so tests that begin one take
+    /// turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// A config whose root is a new, empty directory.
+    fn config(name: &str) -> Config {
+        let root = std::env::temp_dir().join(format!("cpkgs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Config { root, ..Config::default() }
+    }
+
+    fn write(config: &Config, path: &str, content: &str) {
+        let path = config.root.join(path.trim_start_matches('/'));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn read(config: &Config, path: &str) -> Option<String> {
+        fs::read_to_string(config.root.join(path.trim_start_matches('/'))).ok()
+    }
+
+    /// Stages `content` as the file `path` of `package`, as `install` does.
+    fn stage(transaction: &Transaction, package: &str, path: &str, content: &str) {
+        let staged = transaction.staged(package, path);
+        fs::create_dir_all(staged.parent().unwrap()).unwrap();
+        fs::write(staged, content).unwrap();
+    }
+
+    fn state(config: &Config, id: u64) -> State {
+        journals(config)
+            .unwrap()
+            .into_iter()
+            .find(|journal| journal.id == id)
+            .unwrap()
+            .state
+    }
+
+    #[test]
+    fn interrupt_rolls_back_and_is_cleared() {
+        let _serial = SERIAL.lock().unwrap_or_else(|err| err.into_inner());
+        let config = config("interrupt");
+        write(&config, "/etc/hello.conf", "old");
+        let database = Database::open(&config).unwrap();
+
+        let mut transaction = Transaction::begin(&config).unwrap();
+        let id = transaction.journal.id;
+        stage(&transaction, "hello", "/etc/hello.conf", "new");
+        transaction.journal.changes.push(Change::Replace {
+            path: "/etc/hello.conf".to_string(),
+            package: "hello".to_string(),
+        });
+        interrupt();
+
+        let err = transaction.finish(&config, &database, Ok(())).unwrap_err();
+        assert!(format!("{:#}", err).contains("interrupted"));
+        assert_eq!(read(&config, "/etc/hello.conf").as_deref(), Some("old"));
+        assert_eq!(state(&config, id), State::RolledBack);
+        assert!(!INTERRUPTED.load(Ordering::SeqCst));
+        assert!(!RUNNING.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn commit_applies_changes_and_can_be_rolled_back() {
+        let _serial = SERIAL.lock().unwrap_or_else(|err| err.into_inner());
+        let config = config("commit");
+        write(&config, "/etc/hello.conf", "old");
+        write(&config, "/usr/share/hello/stale", "stale");
+        let mut database = Database::open(&config).unwrap();
+
+        let mut transaction = Transaction::begin(&config).unwrap();
+        let id = transaction.journal.id;
+        stage(&transaction, "hello", "/etc/hello.conf", "new");
+        stage(&transaction, "hello", "/usr/bin/hello", "binary");
+        transaction.journal.changes.extend([
+            Change::Remove { path: "/usr/share/hello/stale".to_string() },
+            Change::RemoveDir { path: "/usr/share/hello/".to_string() },
+            Change::Create { path: "/usr/bin/hello".to_string(), package: "hello".to_string() },
+            Change::CreateDir { path: "/usr/bin/".to_string() },
+            Change::CreateDir { path: "/usr/".to_string() },
+            Change::Replace { path: "/etc/hello.conf".to_string(), package: "hello".to_string() },
+        ]);
+        database.insert(InstalledPackage {
+            name: "hello".to_string(),
+            version: "1.0".to_string(),
+            architecture: "all".to_string(),
+            checksum: String::new(),
+            files: vec!["/etc/hello.conf".to_string(), "/usr/bin/hello".to_string()],
+            checksums: BTreeMap::new(),
+        });
+        transaction.finish(&config, &database, Ok(())).unwrap();
+
+        assert_eq!(read(&config, "/etc/hello.conf").as_deref(), Some("new"));
+        assert_eq!(read(&config, "/usr/bin/hello").as_deref(), Some("binary"));
+        assert!(!config.root.join("usr/share/hello").exists());
+        assert!(Database::open(&config).unwrap().get("hello").is_some());
+        assert_eq!(state(&config, id), State::Committed);
+        assert!(!transactions_dir(&config).join(id.to_string()).join("staging").exists());
+
+        Transaction::load(&config, id).unwrap().roll_back().unwrap();
+
+        assert_eq!(read(&config, "/etc/hello.conf").as_deref(), Some("old"));
+        assert_eq!(read(&config, "/usr/share/hello/stale").as_deref(), Some("stale"));
+        assert!(!config.root.join("usr/bin").exists());
+        assert!(Database::open(&config).unwrap().get("hello").is_none());
+        assert_eq!(state(&config, id), State::RolledBack);
+    }
+
+    #[test]
+    fn failed_step_rolls_back_the_earlier_ones() {
+        let _serial = SERIAL.lock().unwrap_or_else(|err| err.into_inner());
+        let config = config("failed-step");
+        write(&config, "/etc/hello.conf", "old");
+        let database = Database::open(&config).unwrap();
+
+        let mut transaction = Transaction::begin(&config).unwrap();
+        let id = transaction.journal.id;
+        stage(&transaction, "hello", "/etc/hello.conf", "new");
+        // Nothing was staged for the second file, so moving it in fails.
+        transaction.journal.changes.extend([
+            Change::Replace { path: "/etc/hello.conf".to_string(), package: "hello".to_string() },
+            Change::Create { path: "/etc/hello.d".to_string(), package: "hello".to_string() },
+        ]);
+
+        let err = transaction.finish(&config, &database, Ok(())).unwrap_err();
+        assert!(format!("{:#}", err).contains("failed to update /etc/hello.d"));
+        assert_eq!(read(&config, "/etc/hello.conf").as_deref(), Some("old"));
+        assert!(!config.root.join("etc/hello.d").exists());
+        assert_eq!(state(&config, id), State::RolledBack);
+        assert!(!transactions_dir(&config).join(id.to_string()).join("backup").exists());
+    }
+
+    #[test]
+    fn crashed_transaction_blocks_the_next_until_rolled_back() {
+        let _serial = SERIAL.lock().unwrap_or_else(|err| err.into_inner());
+        let config = config("crash");
+        write(&config, "/etc/hello.conf", "old");
+
+        let mut transaction = Transaction::begin(&config).unwrap();
+        let id = transaction.journal.id;
+        stage(&transaction, "hello", "/etc/hello.conf", "new");
+        stage(&transaction, "hello", "/etc/hello.d", "new");
+        transaction.journal.changes.extend([
+            Change::Replace { path: "/etc/hello.conf".to_string(), package: "hello".to_string() },
+            Change::Create { path: "/etc/hello.d".to_string(), package: "hello".to_string() },
+        ]);
+        // The process dies after journaling the changes and making the
+        // first one.
+        transaction.save().unwrap();
+        transaction.apply(&transaction.journal.changes[0]).unwrap();
+        drop(transaction);
+
+        let err = Transaction::begin(&config).err().unwrap();
+        assert!(err.to_string().contains("run `cpkgs recover` first"));
+
+        // What `cpkgs recover` does with it.
+        let incomplete: Vec<Journal> = journals(&config)
+            .unwrap()
+            .into_iter()
+            .filter(Journal::is_incomplete)
+            .collect();
+        assert_eq!(incomplete.len(), 1);
+        Transaction::load(&config, incomplete[0].id).unwrap().roll_back().unwrap();
+
+        assert_eq!(read(&config, "/etc/hello.conf").as_deref(), Some("old"));
+        assert!(!config.root.join("etc/hello.d").exists());
+        assert_eq!(state(&config, id), State::RolledBack);
+        Transaction::begin(&config).unwrap();
+    }
+
+    #[test]
+    fn transaction_without_journal_is_ignored() {
+        let _serial = SERIAL.lock().unwrap_or_else(|err| err.into_inner());
+        let config = config("no-journal");
+
+        let transaction = Transaction::begin(&config).unwrap();
+        let dir = transaction.dir.clone();
+        drop(transaction);
+        fs::remove_file(dir.join("journal.json")).unwrap();
+
+        assert!(journals(&config).unwrap().is_empty());
+        assert_eq!(Transaction::begin(&config).unwrap().journal.id, 1);
+    }
+}
@@ -0,0 +1,5 @@
+//! Types shared by the registry API and the cpkgs CLI.
+
+pub mod version;
+
+pub use version::{Constraint, Operator, ParseVersionError, Version};
@@ -0,0 +1,421 @@
+//! Debian package versions, `[epoch:]upstream_version[-debian_revision]`,
+//! compared as described in Debian Policy §5.6.12.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+/// A parsed Debian version.
+///
+/// Equality follows the ordering, so `1.0`, `0:1.0` and `1.0-0` are equal.
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub epoch: u32,
+    pub upstream: String,
+    pub revision: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError {
+    version: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' is not a valid version: {}", self.version, self.reason)
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let error = |reason| ParseVersionError {
+            version: version.to_string(),
+            reason,
+        };
+
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) => {
+                if epoch.is_empty() || !epoch.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(error("the epoch must be a number"));
+                }
+                let epoch = epoch.parse().map_err(|_| error("the epoch is too large"))?;
+                (epoch, rest)
+            }
+            None => (0, version),
+        };
+
+        // The revision is whatever follows the last hyphen, so the upstream
+        // version may itself contain hyphens when a revision is present.
+        let (upstream, revision) = match rest.rsplit_once('-') {
+            Some((upstream, revision)) => {
+                if revision.is_empty() {
+                    return Err(error("the revision is empty"));
+                }
+                if !revision
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+.~".contains(c))
+                {
+                    return Err(error("the revision may only contain alphanumerics and + . ~"));
+                }
+                (upstream, revision)
+            }
+            None => (rest, ""),
+        };
+
+        if !upstream.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(error("the upstream version must start with a digit"));
+        }
+        if !upstream
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".+~-".contains(c))
+        {
+            return Err(error("the upstream version may only contain alphanumerics and . + ~ -"));
+        }
+
+        Ok(Version {
+            epoch,
+            upstream: upstream.to_string(),
+            revision: revision.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.epoch > 0 {
+            write!(f, "{}:", self.epoch)?;
+        }
+        f.write_str(&self.upstream)?;
+        if !self.revision.is_empty() {
+            write!(f, "-{}", self.revision)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| compare_part(&self.upstream, &other.upstream))
+            .then_with(|| compare_part(&self.revision, &other.revision))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl Hash for Version {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Consistent with `eq`: leading zeros and an absent `0` revision do
+        // not change a version, so only hash what always does.
+        self.epoch.hash(state);
+    }
+}
+
+/// Compares two upstream versions or revisions the way dpkg does: runs of
+/// non-digits compare character by character, with `~` before everything
+/// (even the end of the string) and letters before other symbols, and runs
+/// of digits compare numerically.
+fn compare_part(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+
+    while !a.is_empty() || !b.is_empty() {
+        let a_text = a.iter().take_while(|c| !c.is_ascii_digit()).count();
+        let b_text = b.iter().take_while(|c| !c.is_ascii_digit()).count();
+        for i in 0..a_text.max(b_text) {
+            let ordering = symbol_order(a.get(i).filter(|_| i < a_text))
+                .cmp(&symbol_order(b.get(i).filter(|_| i < b_text)));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        a = &a[a_text..];
+        b = &b[b_text..];
+
+        let a_digits = a.iter().take_while(|c| c.is_ascii_digit()).count();
+        let b_digits = b.iter().take_while(|c| c.is_ascii_digit()).count();
+        let ordering = compare_digits(&a[..a_digits], &b[..b_digits]);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        a = &a[a_digits..];
+        b = &b[b_digits..];
+    }
+
+    Ordering::Equal
+}
+
+fn symbol_order(c: Option<&u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_alphabetic() => i32::from(*c),
+        Some(c) => i32::from(*c) + 256,
+    }
+}
+
+/// Compares digit runs numerically without parsing, so arbitrarily long
+/// runs such as dates do not overflow.
+fn compare_digits(a: &[u8], b: &[u8]) -> Ordering {
+    let trim = |digits: &[u8]| {
+        let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+        digits[zeros..].to_vec()
+    };
+    let (a, b) = (trim(a), trim(b));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(&b))
+}
+
+/// A relation operator from a `Depends`-style field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// `<<`
+    Earlier,
+    /// `<=`
+    EarlierOrEqual,
+    /// `=`
+    Exactly,
+    /// `>=`
+    LaterOrEqual,
+    /// `>>`
+    Later,
+}
+
+impl Operator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Operator::Earlier => "<<",
+            Operator::EarlierOrEqual => "<=",
+            Operator::Exactly => "=",
+            Operator::LaterOrEqual => ">=",
+            Operator::Later => ">>",
+        }
+    }
+}
+
+/// A version constraint such as `>= 1.2-1`, in the `<op> <version>` form
+/// the registry stores with dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub operator: Operator,
+    pub version: Version,
+}
+
+impl Constraint {
+    pub fn matches(&self, version: &Version) -> bool {
+        let ordering = version.cmp(&self.version);
+        match self.operator {
+            Operator::Earlier => ordering == Ordering::Less,
+            Operator::EarlierOrEqual => ordering != Ordering::Greater,
+            Operator::Exactly => ordering == Ordering::Equal,
+            Operator::LaterOrEqual => ordering != Ordering::Less,
+            Operator::Later => ordering == Ordering::Greater,
+        }
+    }
+}
+
+impl FromStr for Constraint {
+    type Err = ParseVersionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let op_len = text
+            .find(|c: char| !matches!(c, '<' | '>' | '='))
+            .unwrap_or(text.len());
+        let (op, version) = text.split_at(op_len);
+
+        let operator = match op {
+            "<<" => Operator::Earlier,
+            // `<` and `>` are obsolete forms that dpkg still reads as `<=`
+            // and `>=`.
+            "<=" | "<" => Operator::EarlierOrEqual,
+            "=" => Operator::Exactly,
+            ">=" | ">" => Operator::LaterOrEqual,
+            ">>" => Operator::Later,
+            _ => {
+                return Err(ParseVersionError {
+                    version: text.to_string(),
+                    reason: "unknown version operator",
+                });
+            }
+        };
+
+        Ok(Constraint {
+            operator,
+            version: version.trim().parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.operator.as_str(), self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version {
+        text.parse().unwrap()
+    }
+
+    fn constraint(text: &str) -> Constraint {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_epoch_upstream_and_revision() {
+        let parsed = version("2:1.0-beta-3ubuntu1");
+        assert_eq!(parsed.epoch, 2);
+        assert_eq!(parsed.upstream, "1.0-beta");
+        assert_eq!(parsed.revision, "3ubuntu1");
+
+        let plain = version("1.2.3");
+        assert_eq!((plain.epoch, plain.upstream.as_str(), plain.revision.as_str()), (0, "1.2.3", ""));
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        for invalid in ["", "a1.0", ":1.0", "x:1.0", "1.0-", "1.0-a_b", "1.0_1", "99999999999:1.0"] {
+            assert!(invalid.parse::<Version>().is_err(), "{} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn displays_as_parsed() {
+        for text in ["1.0", "1:1.0", "1.0-1", "3:2.0~rc1-0ubuntu2"] {
+            assert_eq!(version(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn compares_digit_runs_numerically() {
+        assert!(version("1.10") > version("1.9"));
+        assert!(version("1.0.1") > version("1.0"));
+        assert!(version("20261018000000000001") > version("20261018000000000000"));
+        assert_eq!(version("1.01"), version("1.1"));
+    }
+
+    #[test]
+    fn sorts_tilde_before_everything() {
+        assert!(version("1.0~rc1") < version("1.0"));
+        assert!(version("1.0~~") < version("1.0~"));
+        assert!(version("1.0~rc1") < version("1.0~rc2"));
+        assert!(version("1.0~") < version("1.0a"));
+        assert!(version("1.0-1~bpo1") < version("1.0-1"));
+    }
+
+    #[test]
+    fn sorts_letters_before_other_symbols() {
+        assert!(version("1.0a") > version("1.0"));
+        assert!(version("1.0a") < version("1.0+"));
+        assert!(version("1.0+b1") < version("1.0.b1"));
+        assert!(version("1.0b") > version("1.0a"));
+    }
+
+    #[test]
+    fn compares_epochs_first() {
+        assert!(version("1:0.1") > version("9.9"));
+        assert!(version("2:1.0") > version("1:2.0"));
+        assert_eq!(version("0:1.0"), version("1.0"));
+    }
+
+    #[test]
+    fn compares_revisions_last() {
+        assert!(version("1.0-2") > version("1.0-1"));
+        assert!(version("1.0-10") > version("1.0-9"));
+        assert!(version("1.1-1") > version("1.0-9"));
+        assert_eq!(version("1.0-0"), version("1.0"));
+    }
+
+    #[test]
+    fn hashes_equal_versions_alike() {
+        use std::collections::HashSet;
+
+        let set: HashSet<Version> = ["1.0", "0:1.0", "1.0-0", "1.00"].into_iter().map(version).collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn matches_earlier() {
+        let earlier = constraint("<< 1.0");
+        assert!(earlier.matches(&version("0.9")));
+        assert!(earlier.matches(&version("1.0~rc1")));
+        assert!(!earlier.matches(&version("1.0")));
+        assert!(!earlier.matches(&version("1.1")));
+    }
+
+    #[test]
+    fn matches_earlier_or_equal() {
+        for text in ["<= 1.0", "< 1.0"] {
+            let constraint = constraint(text);
+            assert_eq!(constraint.operator, Operator::EarlierOrEqual);
+            assert!(constraint.matches(&version("0.9")));
+            assert!(constraint.matches(&version("1.0")));
+            assert!(!constraint.matches(&version("1.0-1")));
+        }
+    }
+
+    #[test]
+    fn matches_exactly() {
+        let exactly = constraint("= 1:1.0-1");
+        assert!(exactly.matches(&version("1:1.0-1")));
+        assert!(exactly.matches(&version("1:1.00-1")));
+        assert!(!exactly.matches(&version("1.0-1")));
+        assert!(!exactly.matches(&version("1:1.0-2")));
+    }
+
+    #[test]
+    fn matches_later_or_equal() {
+        for text in [">= 1.0", "> 1.0"] {
+            let constraint = constraint(text);
+            assert_eq!(constraint.operator, Operator::LaterOrEqual);
+            assert!(constraint.matches(&version("1.0")));
+            assert!(constraint.matches(&version("1:0.1")));
+            assert!(!constraint.matches(&version("1.0~rc1")));
+        }
+    }
+
+    #[test]
+    fn matches_later() {
+        let later = constraint(">> 1.0");
+        assert!(later.matches(&version("1.0+b1")));
+        assert!(later.matches(&version("1.0-1")));
+        assert!(!later.matches(&version("1.0")));
+        assert!(!later.matches(&version("0.9")));
+    }
+
+    #[test]
+    fn parses_constraints_loosely_spaced() {
+        assert_eq!(constraint(" >=1.0 "), constraint(">= 1.0"));
+        assert_eq!(constraint(">>  2:1.0-1").to_string(), ">> 2:1.0-1");
+    }
+
+    #[test]
+    fn rejects_invalid_constraints() {
+        for invalid in ["1.0", "=< 1.0", "<<< 1.0", "!= 1.0", ">= ", ">= x"] {
+            assert!(invalid.parse::<Constraint>().is_err(), "{} parsed", invalid);
+        }
+    }
+}